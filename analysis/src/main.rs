@@ -0,0 +1,171 @@
+//! Measures distortion and aliasing in oscillator outputs.
+//!
+//! Each target module's pitch is swept across the audio range, and the spectrum of each of its
+//! outputs is analysed at every step. Results are written as CSV and PNG plots to the directory
+//! given as the first argument (by default `target/analysis`).
+
+use std::{fs::File, io::Write, path::PathBuf};
+
+use eurorack::V_OCT_F0;
+use module::Module;
+use module_test::{Harness, Input};
+use modules::{lfo::Lfo, oscillators::Vco};
+
+use crate::spectrum::{Measurement, Spectrum};
+
+mod plot;
+mod spectrum;
+
+const SAMPLE_RATE: usize = 48_000;
+
+/// The number of samples analysed at each step.
+const FFT_SIZE: usize = 1 << 15;
+
+/// How long to let each output settle before analysing it, in seconds.
+const SETTLE_SECS: f32 = 0.1;
+
+/// The sweep, in volts relative to C4.
+const SWEEP_VOLTS: [f32; 15] = [
+    -2.0, -1.5, -1.0, -0.5, 0.0, 0.5, 1.0, 1.5, 2.0, 2.5, 3.0, 3.5, 4.0, 4.5, 5.0,
+];
+
+/// The step of the sweep whose spectra are plotted (C7, about 2 kHz).
+const SPECTRUM_STEP: usize = 10;
+
+/// A module to analyse.
+struct Target {
+    name: &'static str,
+    create: fn() -> Box<dyn Module>,
+    set_frequency: fn(&mut Harness, f32),
+    waveforms: &'static [(&'static str, usize)],
+}
+
+const TARGETS: [Target; 2] = [
+    Target {
+        name: "vco",
+        create: || Box::new(Vco::default()),
+        set_frequency: |harness, frequency| {
+            harness.set_input(
+                Vco::V_OCT_IN,
+                Input::Constant((frequency / V_OCT_F0).log2()),
+            );
+        },
+        waveforms: &[
+            ("saw", Vco::SAW_OUT),
+            ("square", Vco::SQUARE_OUT),
+            ("triangle", Vco::TRI_OUT),
+        ],
+    },
+    Target {
+        name: "lfo",
+        create: || Box::new(Lfo::default()),
+        set_frequency: |harness, frequency| {
+            harness.set_param("frequency", frequency);
+        },
+        waveforms: &[
+            ("sine", Lfo::SINE_OUT),
+            ("saw", Lfo::SAW_OUT),
+            ("square", Lfo::SQUARE_OUT),
+            ("triangle", Lfo::TRI_OUT),
+        ],
+    },
+];
+
+/// The analysis of one output at one step of the sweep.
+struct Step {
+    frequency: f32,
+    measurement: Measurement,
+}
+
+/// The results of sweeping a single output.
+struct Sweep {
+    waveform: &'static str,
+    steps: Vec<Step>,
+    /// The spectrum at [`SPECTRUM_STEP`].
+    spectrum: Spectrum,
+}
+
+fn main() -> anyhow::Result<()> {
+    let output_dir: PathBuf = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "target/analysis".to_owned())
+        .into();
+    std::fs::create_dir_all(&output_dir)?;
+    plot::register_fonts()?;
+
+    let mut csv = File::create(output_dir.join("analysis.csv"))?;
+    writeln!(csv, "module,waveform,frequency_hz,thd_db,aliasing_db")?;
+    for target in &TARGETS {
+        let sweeps = sweep(target);
+        println!("{}:", target.name);
+        for sweep in &sweeps {
+            for step in &sweep.steps {
+                writeln!(
+                    csv,
+                    "{},{},{:.2},{:.2},{:.2}",
+                    target.name,
+                    sweep.waveform,
+                    step.frequency,
+                    step.measurement.thd_db,
+                    step.measurement.aliasing_db
+                )?;
+            }
+            let worst = sweep
+                .steps
+                .iter()
+                .max_by(|a, b| {
+                    let (a, b) = (a.measurement.aliasing_db, b.measurement.aliasing_db);
+                    a.total_cmp(&b)
+                })
+                .unwrap();
+            println!(
+                "  {:<10} worst aliasing {:7.2} dB at {:7.1} Hz",
+                sweep.waveform, worst.measurement.aliasing_db, worst.frequency
+            );
+        }
+        plot::sweeps(&output_dir.join(format!("{}.png", target.name)), &sweeps)?;
+        for sweep in &sweeps {
+            plot::spectrum(
+                &output_dir.join(format!("{}_{}_spectrum.png", target.name, sweep.waveform)),
+                sweep,
+            )?;
+        }
+    }
+    println!("Wrote results to {}", output_dir.display());
+    Ok(())
+}
+
+fn sweep(target: &Target) -> Vec<Sweep> {
+    let mut steps: Vec<Vec<Step>> = target.waveforms.iter().map(|_| Vec::new()).collect();
+    let mut spectra = Vec::new();
+    for (i, volts) in SWEEP_VOLTS.iter().enumerate() {
+        let frequency = V_OCT_F0 * volts.exp2();
+        let mut harness = Harness::new((target.create)(), SAMPLE_RATE);
+        (target.set_frequency)(&mut harness, frequency);
+        harness.run(SETTLE_SECS);
+        let capture = harness.run_samples(FFT_SIZE);
+
+        for (w, (_, output)) in target.waveforms.iter().enumerate() {
+            let spectrum = Spectrum::new(capture.output(*output).samples(), SAMPLE_RATE);
+            steps[w].push(Step {
+                frequency,
+                measurement: spectrum.measure(frequency),
+            });
+            if i == SPECTRUM_STEP {
+                spectra.push(spectrum);
+            }
+        }
+    }
+
+    target
+        .waveforms
+        .iter()
+        .zip(steps)
+        .zip(spectra)
+        .map(|(((waveform, _), steps), spectrum)| Sweep {
+            waveform,
+            steps,
+            spectrum,
+        })
+        .collect()
+}
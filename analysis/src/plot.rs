@@ -0,0 +1,104 @@
+use std::path::Path;
+
+use plotters::prelude::*;
+
+use crate::Sweep;
+
+const SIZE: (u32, u32) = (1200, 600);
+
+/// Plots never have less than this, in dB, on their y axis.
+const FLOOR_DB: f32 = -140.0;
+
+/// Plotters has no built in fonts without a system font library, so we provide our own.
+pub fn register_fonts() -> anyhow::Result<()> {
+    plotters::style::register_font(
+        "sans-serif",
+        FontStyle::Normal,
+        include_bytes!("../../assets/SpaceGrotesk-Regular.ttf"),
+    )
+    .map_err(|_| anyhow::anyhow!("invalid font"))
+}
+
+/// Plots THD and aliasing against frequency for each waveform, side by side.
+pub fn sweeps(path: &Path, sweeps: &[Sweep]) -> anyhow::Result<()> {
+    let root = BitMapBackend::new(path, SIZE).into_drawing_area();
+    root.fill(&WHITE)?;
+    let (left, right) = root.split_horizontally(SIZE.0 / 2);
+    for (area, title, figure) in [
+        (
+            left,
+            "THD",
+            (|s| s.measurement.thd_db) as fn(&crate::Step) -> f32,
+        ),
+        (right, "Aliasing", |s| s.measurement.aliasing_db),
+    ] {
+        let points = sweeps.iter().flat_map(|sweep| &sweep.steps);
+        let min_hz = points.clone().map(|s| s.frequency).fold(f32::MAX, f32::min);
+        let max_hz = points.clone().map(|s| s.frequency).fold(0.0, f32::max);
+        let max_db = points.map(figure).fold(FLOOR_DB, f32::max);
+
+        let mut chart = ChartBuilder::on(&area)
+            .caption(title, ("sans-serif", 24))
+            .margin(10)
+            .x_label_area_size(40)
+            .y_label_area_size(50)
+            .build_cartesian_2d(
+                (min_hz..max_hz).log_scale(),
+                FLOOR_DB..(max_db + 10.0).min(10.0),
+            )?;
+        chart
+            .configure_mesh()
+            .x_desc("Frequency (Hz)")
+            .y_desc("dB")
+            .draw()?;
+        for (i, sweep) in sweeps.iter().enumerate() {
+            let color = Palette99::pick(i).to_rgba();
+            chart
+                .draw_series(LineSeries::new(
+                    sweep.steps.iter().map(|s| (s.frequency, figure(s))),
+                    color.stroke_width(2),
+                ))?
+                .label(sweep.waveform)
+                .legend(move |(x, y)| PathElement::new([(x, y), (x + 20, y)], color));
+        }
+        chart
+            .configure_series_labels()
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .draw()?;
+    }
+    root.present()?;
+    Ok(())
+}
+
+/// Plots the spectrum a sweep kept, relative to its fundamental.
+pub fn spectrum(path: &Path, sweep: &Sweep) -> anyhow::Result<()> {
+    let step = &sweep.steps[crate::SPECTRUM_STEP];
+    let nyquist = crate::SAMPLE_RATE as f32 / 2.0;
+
+    let root = BitMapBackend::new(path, SIZE).into_drawing_area();
+    root.fill(&WHITE)?;
+    let mut chart = ChartBuilder::on(&root)
+        .caption(
+            format!("{} at {:.0} Hz", sweep.waveform, step.frequency),
+            ("sans-serif", 24),
+        )
+        .margin(10)
+        .x_label_area_size(40)
+        .y_label_area_size(50)
+        .build_cartesian_2d(0.0..nyquist, FLOOR_DB..10.0)?;
+    chart
+        .configure_mesh()
+        .x_desc("Frequency (Hz)")
+        .y_desc("dB")
+        .draw()?;
+    chart.draw_series(LineSeries::new(
+        sweep
+            .spectrum
+            .decibels(step.measurement.fundamental)
+            .map(|(hz, db)| (hz, db.max(FLOOR_DB))),
+        &BLUE,
+    ))?;
+    root.present()?;
+    Ok(())
+}
@@ -0,0 +1,111 @@
+use std::f32::consts::PI;
+
+use rustfft::{num_complex::Complex, FftPlanner};
+
+/// How many bins either side of a peak belong to it. The Blackman-Harris main lobe is 4 bins wide
+/// either side, and we leave a little room for frequency error.
+const PEAK_HALF_WIDTH: usize = 6;
+
+/// The power spectrum of a windowed signal.
+pub struct Spectrum {
+    sample_rate: f32,
+    fft_size: usize,
+    power: Vec<f32>,
+}
+
+impl Spectrum {
+    /// Computes the spectrum of `samples`, whose length should be a power of two.
+    pub fn new(samples: &[f32], sample_rate: usize) -> Self {
+        let fft_size = samples.len();
+        let mut buffer: Vec<Complex<f32>> = samples
+            .iter()
+            .enumerate()
+            .map(|(i, x)| Complex::new(x * blackman_harris(i, fft_size), 0.0))
+            .collect();
+        FftPlanner::new()
+            .plan_fft_forward(fft_size)
+            .process(&mut buffer);
+        Spectrum {
+            sample_rate: sample_rate as f32,
+            fft_size,
+            power: buffer[..fft_size / 2]
+                .iter()
+                .map(|c| c.norm_sqr())
+                .collect(),
+        }
+    }
+
+    pub fn bin_frequency(&self, bin: usize) -> f32 {
+        bin as f32 * self.sample_rate / self.fft_size as f32
+    }
+
+    /// The power in each bin up to Nyquist, in dB relative to `reference`.
+    pub fn decibels(&self, reference: f32) -> impl Iterator<Item = (f32, f32)> + '_ {
+        self.power
+            .iter()
+            .enumerate()
+            .map(move |(bin, p)| (self.bin_frequency(bin), decibels(*p / reference)))
+    }
+
+    /// Splits the spectrum's energy into the fundamental at `frequency`, its harmonics up to
+    /// Nyquist, and everything else.
+    ///
+    /// A bandlimited periodic signal only has energy at exact harmonics, so whatever is left
+    /// (besides DC) must have folded back from above Nyquist. Aliases that happen to land on a
+    /// harmonic are counted as distortion instead, which is fine for comparing figures over time.
+    pub fn measure(&self, frequency: f32) -> Measurement {
+        let fundamental_bin = frequency * self.fft_size as f32 / self.sample_rate;
+        let mut claimed = vec![false; self.power.len()];
+        let mut claim = |center: usize| -> f32 {
+            let start = center.saturating_sub(PEAK_HALF_WIDTH);
+            let end = (center + PEAK_HALF_WIDTH + 1).min(self.power.len());
+            (start..end)
+                .filter(|&bin| !std::mem::replace(&mut claimed[bin], true))
+                .map(|bin| self.power[bin])
+                .sum()
+        };
+
+        claim(0);
+        let fundamental = claim(fundamental_bin.round() as usize);
+        let mut harmonics = 0.0;
+        let mut k = 2.0;
+        while k * fundamental_bin < self.power.len() as f32 {
+            harmonics += claim((k * fundamental_bin).round() as usize);
+            k += 1.0;
+        }
+        let aliasing: f32 = self
+            .power
+            .iter()
+            .zip(&claimed)
+            .filter(|(_, claimed)| !**claimed)
+            .map(|(p, _)| p)
+            .sum();
+
+        Measurement {
+            fundamental,
+            thd_db: decibels(harmonics / fundamental),
+            aliasing_db: decibels(aliasing / fundamental),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Measurement {
+    /// The power of the fundamental, for use as a reference level.
+    pub fundamental: f32,
+    /// The power of all harmonics below Nyquist, relative to the fundamental.
+    pub thd_db: f32,
+    /// The power of all inharmonic components, relative to the fundamental.
+    pub aliasing_db: f32,
+}
+
+/// A 4-term Blackman-Harris window, which keeps sidelobes below -92 dB so that they don't swamp
+/// the aliasing we're trying to measure.
+fn blackman_harris(i: usize, len: usize) -> f32 {
+    let phase = 2.0 * PI * i as f32 / len as f32;
+    0.35875 - 0.48829 * phase.cos() + 0.14128 * (2.0 * phase).cos() - 0.01168 * (3.0 * phase).cos()
+}
+
+fn decibels(power: f32) -> f32 {
+    10.0 * power.max(1e-20).log10()
+}
@@ -1,17 +1,34 @@
-use std::sync::mpsc;
+use std::{
+    sync::{mpsc, Arc},
+    time::Instant,
+};
 
 use cpal::{
     traits::{DeviceTrait, HostTrait, StreamTrait},
     BufferSize, Stream,
 };
-use eurorack::AUDIO_VOLTS;
-use module::{AudioUnit, ModuleHandle, ModuleInput, ModuleOutput};
-use rack::Rack;
+use module::{AudioUnit, ModuleHandle, ModuleInput, ModuleOutput, ModuleState, SignalLevel};
+use rack::{ModuleFault, Rack};
+
+mod master;
+mod metering;
+
+pub use crate::master::{MasterBus, MasterSettings};
+use crate::metering::{LoadMeter, SignalMeter};
+
+/// The number of events that can be queued for the UI before new ones are dropped.
+const EVENT_QUEUE_SIZE: usize = 4096;
+/// The number of signal levels that can be queued for the UI. Levels have a queue of their own,
+/// as there are many of them, and a fault mustn't be dropped to make room for them.
+const LEVEL_QUEUE_SIZE: usize = 4096;
 
 pub struct AudioHost {
     buffer_size: u32,
     stream: Option<Stream>,
     tx: Option<mpsc::Sender<AudioMessage>>,
+    events: Option<rtrb::Consumer<AudioEvent>>,
+    levels: Option<rtrb::Consumer<AudioEvent>>,
+    master: Arc<MasterSettings>,
 }
 
 impl AudioHost {
@@ -20,15 +37,42 @@ impl AudioHost {
             buffer_size,
             stream: None,
             tx: None,
+            events: None,
+            levels: None,
+            master: Arc::new(MasterSettings::default()),
         }
     }
 
+    /// Creates a host with no audio device, which passes the messages it's sent to the returned
+    /// receiver instead of a rack. Useful for checking what a UI asks of the audio thread.
+    pub fn detached() -> (Self, mpsc::Receiver<AudioMessage>) {
+        let (tx, rx) = mpsc::channel();
+        let host = AudioHost {
+            tx: Some(tx),
+            ..AudioHost::default()
+        };
+        (host, rx)
+    }
+
+    /// Returns the settings for the master output stage, which may be changed at any time.
+    pub fn master(&self) -> &Arc<MasterSettings> {
+        &self.master
+    }
+
     pub fn send_message(&self, msg: AudioMessage) {
         if let Some(tx) = &self.tx {
             tx.send(msg).unwrap();
         }
     }
 
+    /// Returns the next pending event reported by the audio thread, if any.
+    pub fn poll_event(&mut self) -> Option<AudioEvent> {
+        self.events
+            .as_mut()
+            .and_then(|rx| rx.pop().ok())
+            .or_else(|| self.levels.as_mut().and_then(|rx| rx.pop().ok()))
+    }
+
     pub fn start(&mut self, mut rack: Rack) -> Result<(), AudioHostError> {
         let host = cpal::default_host();
         let device = host
@@ -41,13 +85,23 @@ impl AudioHost {
         rack.reset(config.sample_rate.0 as usize);
 
         let (tx, rx) = mpsc::channel();
+        // If the UI isn't keeping up with events, new ones are simply dropped.
+        let (mut events_tx, events_rx) = rtrb::RingBuffer::new(EVENT_QUEUE_SIZE);
+        let (mut levels_tx, levels_rx) = rtrb::RingBuffer::new(LEVEL_QUEUE_SIZE);
+        let mut master = MasterBus::new(self.master.clone(), config.sample_rate.0 as usize);
+        let mut meter = LoadMeter::new(config.sample_rate.0 as usize);
+        let mut signal_meter = SignalMeter::new(config.sample_rate.0 as usize);
         let stream = device.build_output_stream(
             &config,
-            move |samples: &mut [f32], _: &cpal::OutputCallbackInfo| {
+            move |samples: &mut [f32], info: &cpal::OutputCallbackInfo| {
+                let started = Instant::now();
                 while let Ok(msg) = rx.try_recv() {
                     match msg {
-                        AudioMessage::AddModule(handle, inputs, outputs, audio_unit) => {
-                            rack.add_audio_unit(handle, inputs, outputs, audio_unit);
+                        AudioMessage::AddModule(handle, inputs, outputs, bypass, audio_unit) => {
+                            rack.add_audio_unit(handle, inputs, outputs, bypass, audio_unit);
+                        }
+                        AudioMessage::RemoveModule(handle) => {
+                            rack.remove_module(handle).unwrap();
                         }
                         AudioMessage::ConnectModules(output, input) => {
                             rack.connect(output, input).unwrap();
@@ -55,17 +109,44 @@ impl AudioHost {
                         AudioMessage::DisconnectModules(output, input) => {
                             rack.disconnect(output, input).unwrap();
                         }
+                        AudioMessage::ReplaceAudioUnit(handle, audio_unit) => {
+                            rack.replace_audio_unit(handle, audio_unit).unwrap();
+                        }
+                        AudioMessage::SetModuleState(handle, state) => {
+                            rack.set_module_state(handle, state).unwrap();
+                        }
+                        AudioMessage::ResetModule(handle) => {
+                            rack.reset_module(handle).unwrap();
+                        }
+                        AudioMessage::SetProfiling(enabled) => rack.set_profiling(enabled),
                     }
                 }
                 for s in samples.iter_mut() {
-                    *s = rack.tick() / AUDIO_VOLTS;
+                    *s = master.process(rack.tick());
                 }
+                rack.drain_faults(|handle, fault| {
+                    let _ = events_tx.push(AudioEvent::ModuleFault(handle, fault));
+                });
+                signal_meter.record(samples.len(), &mut rack, |event| {
+                    let _ = levels_tx.push(event);
+                });
+                meter.record(
+                    info.timestamp().callback,
+                    started.elapsed(),
+                    samples.len(),
+                    &mut rack,
+                    |event| {
+                        let _ = events_tx.push(event);
+                    },
+                );
             },
             move |err| println!("cpal error: {:?}", err),
         )?;
         stream.play()?;
         self.stream = Some(stream);
         self.tx = Some(tx);
+        self.events = Some(events_rx);
+        self.levels = Some(levels_rx);
 
         Ok(())
     }
@@ -91,9 +172,37 @@ impl Default for AudioHost {
 }
 
 pub enum AudioMessage {
-    AddModule(ModuleHandle, usize, usize, Box<dyn AudioUnit>),
+    /// Adds a module with the given handle, input and output counts, bypass routes and unit.
+    AddModule(
+        ModuleHandle,
+        usize,
+        usize,
+        Vec<(usize, usize)>,
+        Box<dyn AudioUnit>,
+    ),
+    /// Removes a module, along with any cables connected to it.
+    RemoveModule(ModuleHandle),
     ConnectModules(ModuleOutput, ModuleInput),
     DisconnectModules(ModuleOutput, ModuleInput),
+    ReplaceAudioUnit(ModuleHandle, Box<dyn AudioUnit>),
+    SetModuleState(ModuleHandle, ModuleState),
+    ResetModule(ModuleHandle),
+    SetProfiling(bool),
+}
+
+/// Status reported from the audio thread back to the UI.
+#[derive(Copy, Clone, Debug)]
+pub enum AudioEvent {
+    /// The fraction of the buffer duration spent in the audio callback, averaged over the last
+    /// report interval, along with the peak for any single callback and the total xrun count.
+    DspLoad { load: f32, peak: f32, xruns: usize },
+    /// The fraction of the buffer duration spent ticking a single module. Only reported while
+    /// profiling is enabled.
+    ModuleLoad(ModuleHandle, f32),
+    /// A module has been isolated from the rest of the rack.
+    ModuleFault(ModuleHandle, ModuleFault),
+    /// The recent signal on a module output, reported many times a second for every output.
+    SignalLevel(ModuleOutput, SignalLevel),
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -0,0 +1,181 @@
+use std::{
+    collections::VecDeque,
+    f32::consts::PI,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use eurorack::{Voltage, AUDIO_VOLTS};
+use portable_atomic::AtomicF32;
+
+/// The cutoff of the DC blocking filter, in Hz.
+const DC_BLOCKER_CUTOFF: f32 = 10.0;
+
+/// The highest sample level the limiter will let through (about -1 dBFS).
+const LIMITER_CEILING: f32 = 0.89;
+
+/// How long the limiter takes to recover from gain reduction, in seconds.
+const LIMITER_RELEASE_SECS: f32 = 0.1;
+
+/// How far ahead the limiter looks for peaks, in seconds. The output is delayed by this much, and
+/// the gain is brought down gradually over the same time, so that peaks are never cut off
+/// abruptly.
+const LIMITER_LOOKAHEAD_SECS: f32 = 0.002;
+
+/// How long volume changes (including muting) take to fade in, in seconds.
+const VOLUME_SMOOTHING_SECS: f32 = 0.01;
+
+/// Settings for the master output stage, shared between the UI and the audio thread.
+#[derive(Debug)]
+pub struct MasterSettings {
+    /// The output gain, from 0 to 1.
+    pub volume: AtomicF32,
+    pub muted: AtomicBool,
+    pub dc_blocker: AtomicBool,
+    pub limiter: AtomicBool,
+}
+
+impl Default for MasterSettings {
+    fn default() -> Self {
+        MasterSettings {
+            volume: AtomicF32::new(1.0),
+            muted: AtomicBool::new(false),
+            dc_blocker: AtomicBool::new(true),
+            limiter: AtomicBool::new(true),
+        }
+    }
+}
+
+/// Converts the rack's output voltage into samples that are safe to send to the device.
+pub struct MasterBus {
+    settings: Arc<MasterSettings>,
+    dc_coefficient: f32,
+    last_in: f32,
+    last_out: f32,
+    gain: f32,
+    gain_coefficient: f32,
+    limiter: Limiter,
+}
+
+impl MasterBus {
+    pub fn new(settings: Arc<MasterSettings>, sample_rate: usize) -> Self {
+        let sample_rate = sample_rate as f32;
+        MasterBus {
+            settings,
+            dc_coefficient: 1.0 - 2.0 * PI * DC_BLOCKER_CUTOFF / sample_rate,
+            last_in: 0.0,
+            last_out: 0.0,
+            gain: 0.0,
+            gain_coefficient: (-1.0 / (VOLUME_SMOOTHING_SECS * sample_rate)).exp(),
+            limiter: Limiter::new(sample_rate),
+        }
+    }
+
+    pub fn process(&mut self, v: Voltage) -> f32 {
+        let mut x = v / AUDIO_VOLTS;
+
+        // Remove any DC offset with a one-pole highpass.
+        // Ref: DAFX, Section 2.2
+        if self.settings.dc_blocker.load(Ordering::Relaxed) {
+            let y = x - self.last_in + self.dc_coefficient * self.last_out;
+            self.last_in = x;
+            self.last_out = y;
+            x = y;
+        }
+
+        // Smooth volume changes, so that turning the knob (or muting) doesn't click.
+        let target = if self.settings.muted.load(Ordering::Relaxed) {
+            0.0
+        } else {
+            self.settings.volume.load(Ordering::Relaxed)
+        };
+        self.gain = target + self.gain_coefficient * (self.gain - target);
+        // The smoothing stalls just short of the target once the steps round away to nothing.
+        if (self.gain - target).abs() < 1e-4 {
+            self.gain = target;
+        }
+        x *= self.gain;
+
+        // The output is delayed by the lookahead even while the limiter is off, so that turning
+        // it on and off doesn't skip.
+        let enabled = self.settings.limiter.load(Ordering::Relaxed);
+        self.limiter.process(x, enabled).clamp(-1.0, 1.0)
+    }
+}
+
+/// A lookahead peak limiter.
+///
+/// Each sample is held back for the lookahead time. The gain applied to it is the average, over
+/// the lookahead, of the lowest gain needed by any sample in the window before it. Every gain in
+/// that average is low enough for the sample itself, so it never exceeds the ceiling, while the
+/// gain ramps smoothly down ahead of each peak. Afterwards, it recovers over the release time.
+struct Limiter {
+    /// The lookahead, in samples.
+    lookahead: usize,
+    /// The last `lookahead` input samples.
+    delay: Vec<f32>,
+    /// The gains needed by recent samples, along with their positions. Gains increase from front
+    /// to back, as a gain is useless once a lower one has arrived after it.
+    minima: VecDeque<(usize, f32)>,
+    /// The last `lookahead + 1` gains, after release, and their sum.
+    gains: Vec<f32>,
+    gain_sum: f32,
+    release_coefficient: f32,
+    /// The number of samples processed.
+    position: usize,
+}
+
+impl Limiter {
+    fn new(sample_rate: f32) -> Self {
+        let lookahead = ((LIMITER_LOOKAHEAD_SECS * sample_rate) as usize).max(1);
+        Limiter {
+            lookahead,
+            delay: vec![0.0; lookahead],
+            minima: VecDeque::with_capacity(lookahead + 2),
+            gains: vec![1.0; lookahead + 1],
+            gain_sum: (lookahead + 1) as f32,
+            release_coefficient: (-1.0 / (LIMITER_RELEASE_SECS * sample_rate)).exp(),
+            position: 0,
+        }
+    }
+
+    /// Takes a sample, and returns the one from the lookahead time ago with its gain applied.
+    fn process(&mut self, x: f32, enabled: bool) -> f32 {
+        let position = self.position;
+        self.position += 1;
+
+        let needed = if enabled && x.abs() > LIMITER_CEILING {
+            LIMITER_CEILING / x.abs()
+        } else {
+            1.0
+        };
+        while self.minima.back().is_some_and(|&(_, gain)| gain >= needed) {
+            self.minima.pop_back();
+        }
+        self.minima.push_back((position, needed));
+        while self.minima[0].0 + self.lookahead < position {
+            self.minima.pop_front();
+        }
+        let lowest = self.minima[0].1;
+
+        let slot = position % self.gains.len();
+        let previous = self.gains[(position + self.lookahead) % self.gains.len()];
+        let mut released = 1.0 + self.release_coefficient * (previous - 1.0);
+        // Like the volume, the release would otherwise stall just short of unity gain.
+        if released > 1.0 - 1e-3 {
+            released = 1.0;
+        }
+        let gain = lowest.min(released);
+        self.gain_sum += gain - self.gains[slot];
+        self.gains[slot] = gain;
+        // Recompute the sum once per lap, so that rounding errors can't build up.
+        if slot == 0 {
+            self.gain_sum = self.gains.iter().sum();
+        }
+
+        let delayed = std::mem::replace(&mut self.delay[position % self.lookahead], x);
+        delayed * self.gain_sum / self.gains.len() as f32
+    }
+}
@@ -0,0 +1,112 @@
+use std::time::Duration;
+
+use cpal::StreamInstant;
+use rack::Rack;
+
+use crate::AudioEvent;
+
+/// How often load reports are sent back to the UI.
+const REPORT_INTERVAL_SECS: f32 = 0.25;
+/// How often signal levels are sent back to the UI, which is often enough for jacks to follow
+/// the signal smoothly.
+const SIGNAL_INTERVAL_SECS: f32 = 1.0 / 30.0;
+
+/// Measures how much of each buffer's duration the audio callback consumes.
+pub(crate) struct LoadMeter {
+    sample_rate: f32,
+    busy: Duration,
+    samples: usize,
+    peak: f32,
+    xruns: usize,
+    last_callback: Option<StreamInstant>,
+}
+
+impl LoadMeter {
+    pub(crate) fn new(sample_rate: usize) -> Self {
+        LoadMeter {
+            sample_rate: sample_rate as f32,
+            busy: Duration::ZERO,
+            samples: 0,
+            peak: 0.0,
+            xruns: 0,
+            last_callback: None,
+        }
+    }
+
+    /// Records a single callback, which spent `busy` rendering `samples` samples. Once enough audio
+    /// has been rendered, a report is emitted through `emit`.
+    pub(crate) fn record(
+        &mut self,
+        callback: StreamInstant,
+        busy: Duration,
+        samples: usize,
+        rack: &mut Rack,
+        mut emit: impl FnMut(AudioEvent),
+    ) {
+        let buffer_secs = samples as f32 / self.sample_rate;
+        let load = busy.as_secs_f32() / buffer_secs;
+
+        // We count an xrun whenever we miss our deadline, or whenever the device waits noticeably
+        // longer than a buffer between callbacks (which means it ran out of samples).
+        let late = self
+            .last_callback
+            .and_then(|last| callback.duration_since(&last))
+            .is_some_and(|gap| gap.as_secs_f32() > 1.5 * buffer_secs);
+        if load > 1.0 || late {
+            self.xruns += 1;
+        }
+        self.last_callback = Some(callback);
+
+        self.busy += busy;
+        self.samples += samples;
+        self.peak = self.peak.max(load);
+
+        let elapsed_secs = self.samples as f32 / self.sample_rate;
+        if elapsed_secs >= REPORT_INTERVAL_SECS {
+            emit(AudioEvent::DspLoad {
+                load: self.busy.as_secs_f32() / elapsed_secs,
+                peak: self.peak,
+                xruns: self.xruns,
+            });
+            rack.drain_tick_times(|handle, time| {
+                emit(AudioEvent::ModuleLoad(
+                    handle,
+                    time.as_secs_f32() / elapsed_secs,
+                ));
+            });
+            self.busy = Duration::ZERO;
+            self.samples = 0;
+            self.peak = 0.0;
+        }
+    }
+}
+
+/// Reports the level of every module output at a steady rate.
+pub(crate) struct SignalMeter {
+    sample_rate: f32,
+    samples: usize,
+}
+
+impl SignalMeter {
+    pub(crate) fn new(sample_rate: usize) -> Self {
+        SignalMeter {
+            sample_rate: sample_rate as f32,
+            samples: 0,
+        }
+    }
+
+    /// Records that `samples` more samples have been rendered, emitting the levels through `emit`
+    /// once it's time for another report.
+    pub(crate) fn record(
+        &mut self,
+        samples: usize,
+        rack: &mut Rack,
+        mut emit: impl FnMut(AudioEvent),
+    ) {
+        self.samples += samples;
+        if self.samples as f32 / self.sample_rate >= SIGNAL_INTERVAL_SECS {
+            rack.drain_signal_levels(|output, level| emit(AudioEvent::SignalLevel(output, level)));
+            self.samples = 0;
+        }
+    }
+}
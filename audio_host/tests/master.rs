@@ -0,0 +1,99 @@
+use std::{
+    f32::consts::PI,
+    sync::{atomic::Ordering, Arc},
+};
+
+use audio_host::{MasterBus, MasterSettings};
+use eurorack::AUDIO_VOLTS;
+
+const SAMPLE_RATE: usize = 48_000;
+/// The limiter's lookahead of 2ms, in samples.
+const LOOKAHEAD: usize = 96;
+const CEILING: f32 = 0.89;
+
+/// A master bus with only the limiter enabled, which has settled at full volume.
+fn master_bus() -> MasterBus {
+    let settings = Arc::new(MasterSettings::default());
+    settings.dc_blocker.store(false, Ordering::Relaxed);
+    let mut bus = MasterBus::new(settings, SAMPLE_RATE);
+    for _ in 0..SAMPLE_RATE {
+        bus.process(0.0);
+    }
+    bus
+}
+
+/// A 1kHz sine, at `amplitude` times full scale.
+fn sine(amplitude: f32, samples: usize) -> Vec<f32> {
+    (0..samples)
+        .map(|i| amplitude * (2.0 * PI * 1000.0 * i as f32 / SAMPLE_RATE as f32).sin())
+        .collect()
+}
+
+/// Runs samples through the bus, returning its output with the lookahead delay removed.
+fn process(bus: &mut MasterBus, input: &[f32]) -> Vec<f32> {
+    let padded = input.iter().chain(&[0.0; LOOKAHEAD]);
+    let output: Vec<f32> = padded.map(|x| bus.process(x * AUDIO_VOLTS)).collect();
+    output[LOOKAHEAD..].to_vec()
+}
+
+#[test]
+fn passes_quiet_signals_unchanged() {
+    let input = sine(0.8, SAMPLE_RATE / 10);
+    let output = process(&mut master_bus(), &input);
+    for (x, y) in input.iter().zip(&output) {
+        assert!((x - y).abs() < 1e-5, "{} became {}", x, y);
+    }
+}
+
+#[test]
+fn limits_peaks_without_sudden_gain_changes() {
+    let mut input = sine(0.5, SAMPLE_RATE / 10);
+    input.extend(sine(2.0, SAMPLE_RATE / 10));
+    let output = process(&mut master_bus(), &input);
+
+    let peak = output.iter().fold(0.0f32, |peak, y| peak.max(y.abs()));
+    assert!(peak <= CEILING + 1e-4, "peak of {}", peak);
+
+    // The gain ramps down over the lookahead, rather than dropping at the first loud sample.
+    let gains: Vec<(usize, f32)> = input
+        .iter()
+        .zip(&output)
+        .enumerate()
+        .filter(|(_, (x, _))| x.abs() > 0.1)
+        .map(|(i, (x, y))| (i, y / x))
+        .collect();
+    for pair in gains.windows(2) {
+        let [(i, a), (j, b)] = [pair[0], pair[1]];
+        let step = (b - a).abs() / (j - i) as f32;
+        assert!(step < 0.01, "gain changed by {} per sample at {}", step, i);
+    }
+    // The ramp starts ahead of the burst.
+    let burst = SAMPLE_RATE / 10;
+    assert!(gains.iter().any(|&(i, gain)| i < burst && gain < 0.9));
+}
+
+#[test]
+fn recovers_after_peaks() {
+    let mut bus = master_bus();
+    process(&mut bus, &sine(2.0, SAMPLE_RATE / 10));
+    process(&mut bus, &vec![0.0; SAMPLE_RATE]);
+    let input = sine(0.8, SAMPLE_RATE / 10);
+    let output = process(&mut bus, &input);
+    for (x, y) in input.iter().zip(&output) {
+        assert!((x - y).abs() < 1e-4, "{} became {}", x, y);
+    }
+}
+
+#[test]
+fn only_clips_when_disabled() {
+    let settings = Arc::new(MasterSettings::default());
+    settings.dc_blocker.store(false, Ordering::Relaxed);
+    settings.limiter.store(false, Ordering::Relaxed);
+    let mut bus = MasterBus::new(settings, SAMPLE_RATE);
+    for _ in 0..SAMPLE_RATE {
+        bus.process(0.0);
+    }
+    let output = process(&mut bus, &sine(2.0, SAMPLE_RATE / 10));
+    let peak = output.iter().fold(0.0f32, |peak, y| peak.max(y.abs()));
+    assert_eq!(peak, 1.0);
+}
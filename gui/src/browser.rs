@@ -0,0 +1,180 @@
+use eframe::egui;
+use module::{
+    registry::{Category, ModuleManifest, ModuleRegistry},
+    Module, ModuleHandle, Panel,
+};
+
+use crate::panels;
+
+/// A searchable dialog listing every registered module by category, with a preview of the
+/// selected module's panel.
+#[derive(Default)]
+pub(crate) struct ModuleBrowser {
+    open: bool,
+    /// Set when the browser opens, to focus the search box.
+    just_opened: bool,
+    query: String,
+    selected: Option<String>,
+    preview: Option<Preview>,
+}
+
+/// A module created only to draw its panel. Its audio unit is never created.
+struct Preview {
+    id: String,
+    handle: ModuleHandle,
+    _module: Box<dyn Module>,
+    panel: Box<dyn Panel>,
+}
+
+impl ModuleBrowser {
+    pub(crate) fn open(&mut self) {
+        self.open = true;
+        self.just_opened = true;
+        self.query.clear();
+    }
+
+    /// Shows the browser if it's open, returning the id of a module to add to the patch.
+    pub(crate) fn show(
+        &mut self,
+        ctx: &egui::Context,
+        registry: &mut ModuleRegistry,
+    ) -> Option<String> {
+        if !self.open {
+            return None;
+        }
+
+        let mut modules: Vec<ModuleManifest> = registry
+            .all_modules()
+            .into_iter()
+            .filter(|m| m.matches(&self.query))
+            .collect();
+        modules.sort_by(|a, b| (a.category, &a.name).cmp(&(b.category, &b.name)));
+        // Keep something selected while searching, so that enter always adds a module.
+        if !modules
+            .iter()
+            .any(|m| Some(&m.id) == self.selected.as_ref())
+        {
+            self.selected = modules.first().map(|m| m.id.clone());
+        }
+
+        let mut added = None;
+        let mut open = self.open;
+        egui::Window::new("Add module")
+            .open(&mut open)
+            .collapsible(false)
+            .default_size([560.0, 560.0])
+            .show(ctx, |ui| {
+                let search = ui.add(
+                    egui::TextEdit::singleline(&mut self.query)
+                        .hint_text("Search modules")
+                        .desired_width(f32::INFINITY),
+                );
+                if self.just_opened {
+                    search.request_focus();
+                    self.just_opened = false;
+                }
+                if search.lost_focus() && ui.input().key_pressed(egui::Key::Enter) {
+                    added = self.selected.clone();
+                }
+                ui.separator();
+
+                ui.horizontal_top(|ui| {
+                    egui::ScrollArea::vertical()
+                        .id_source("module_list")
+                        .max_width(160.0)
+                        .show(ui, |ui| {
+                            for category in Category::ALL {
+                                let mut in_category =
+                                    modules.iter().filter(|m| m.category == category).peekable();
+                                if in_category.peek().is_none() {
+                                    continue;
+                                }
+                                ui.label(egui::RichText::new(category.name()).strong());
+                                for manifest in in_category {
+                                    let selected = Some(&manifest.id) == self.selected.as_ref();
+                                    let response = ui.selectable_label(selected, &manifest.name);
+                                    if response.clicked() {
+                                        self.selected = Some(manifest.id.clone());
+                                    }
+                                    if response.double_clicked() {
+                                        added = Some(manifest.id.clone());
+                                    }
+                                }
+                                ui.add_space(6.0);
+                            }
+                            if modules.is_empty() {
+                                ui.weak("No modules found");
+                            }
+                        });
+                    ui.separator();
+
+                    let manifest = self
+                        .selected
+                        .as_ref()
+                        .and_then(|id| modules.iter().find(|m| &m.id == id));
+                    if let Some(manifest) = manifest {
+                        ui.vertical(|ui| {
+                            if self.details(ui, registry, manifest) {
+                                added = Some(manifest.id.clone());
+                            }
+                        });
+                    }
+                });
+            });
+
+        if ctx.input().key_pressed(egui::Key::Escape) {
+            open = false;
+        }
+        self.open = open && added.is_none();
+        if !self.open {
+            self.preview = None;
+        }
+        added
+    }
+
+    /// Describes a module and previews its panel. Returns true if the module should be added.
+    fn details(
+        &mut self,
+        ui: &mut egui::Ui,
+        registry: &mut ModuleRegistry,
+        manifest: &ModuleManifest,
+    ) -> bool {
+        ui.heading(&manifest.name);
+        let mut details = format!("{} · {} HP", manifest.category.name(), manifest.hp);
+        if !manifest.author.is_empty() {
+            details.push_str(&format!(" · by {}", manifest.author));
+        }
+        ui.weak(details);
+        if !manifest.description.is_empty() {
+            ui.label(&manifest.description);
+        }
+        if !manifest.tags.is_empty() {
+            ui.weak(format!("Tags: {}", manifest.tags.join(", ")));
+        }
+        let add = ui.button("Add to patch").clicked();
+        ui.add_space(8.0);
+
+        if self.preview.as_ref().map(|p| &p.id) != Some(&manifest.id) {
+            self.preview = registry
+                .create_module(&manifest.id)
+                .ok()
+                .map(|(handle, module)| Preview {
+                    id: manifest.id.clone(),
+                    handle,
+                    panel: module.create_panel(),
+                    _module: module,
+                });
+        }
+        if let Some(preview) = &mut self.preview {
+            // The preview has no audio unit, so it mustn't be patched or played with.
+            ui.add_enabled_ui(false, |ui| {
+                ui.add(panels::panel_to_widget(
+                    preview.handle,
+                    preview.panel.as_mut(),
+                    None,
+                ));
+            });
+        }
+        add
+    }
+}
@@ -0,0 +1,174 @@
+use ::widgets::jack;
+use eframe::egui::*;
+use eframe::epaint::QuadraticBezierShape;
+use eframe::epi;
+use module::SignalLevel;
+
+/// Colours given to new cables, in order of preference.
+pub(crate) const PALETTE: [Color32; 8] = [
+    Color32::from_rgb(230, 80, 70),
+    Color32::from_rgb(240, 170, 50),
+    Color32::from_rgb(230, 220, 80),
+    Color32::from_rgb(90, 200, 100),
+    Color32::from_rgb(70, 190, 210),
+    Color32::from_rgb(80, 120, 230),
+    Color32::from_rgb(170, 100, 220),
+    Color32::from_rgb(230, 110, 180),
+];
+
+/// How close the pointer must be to a cable to hover it, in points.
+const HOVER_DISTANCE: f32 = 6.0;
+/// The number of straight segments a cable is approximated by for hit testing.
+const HIT_SEGMENTS: usize = 24;
+
+const OPACITY_KEY: &str = "cable_opacity";
+const SAG_KEY: &str = "cable_sag";
+
+/// The palette colour used by the fewest of the given cables, so that new cables stand out.
+pub(crate) fn auto_color(existing: impl Iterator<Item = Color32>) -> Color32 {
+    let mut counts = [0; PALETTE.len()];
+    for color in existing {
+        if let Some(i) = PALETTE.iter().position(|&c| c == color) {
+            counts[i] += 1;
+        }
+    }
+    let i = (0..PALETTE.len()).min_by_key(|&i| counts[i]).unwrap();
+    PALETTE[i]
+}
+
+/// Shifts a cable's colour towards the polarity of the signal it carries, more so the louder it
+/// is.
+pub(crate) fn tint(color: Color32, level: Option<&SignalLevel>) -> Color32 {
+    let level = match level {
+        Some(level) => level,
+        None => return color,
+    };
+    let signal = jack::signal_color(level);
+    let t = 0.5 * jack::signal_intensity(level);
+    let mix = |a: u8, b: u8| (a as f32 + t * (b as f32 - a as f32)) as u8;
+    Color32::from_rgb(
+        mix(color.r(), signal.r()),
+        mix(color.g(), signal.g()),
+        mix(color.b(), signal.b()),
+    )
+}
+
+/// How all cables are drawn.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct CableSettings {
+    pub(crate) opacity: f32,
+    /// How far cables droop, relative to the default.
+    pub(crate) sag: f32,
+}
+
+impl Default for CableSettings {
+    fn default() -> Self {
+        CableSettings {
+            opacity: 0.75,
+            sag: 1.0,
+        }
+    }
+}
+
+impl CableSettings {
+    pub(crate) fn load(storage: &dyn epi::Storage) -> Self {
+        let defaults = CableSettings::default();
+        let get = |key, default| {
+            storage
+                .get_string(key)
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(default)
+        };
+        CableSettings {
+            opacity: get(OPACITY_KEY, defaults.opacity),
+            sag: get(SAG_KEY, defaults.sag),
+        }
+    }
+
+    pub(crate) fn save(&self, storage: &mut dyn epi::Storage) {
+        storage.set_string(OPACITY_KEY, self.opacity.to_string());
+        storage.set_string(SAG_KEY, self.sag.to_string());
+    }
+
+    pub(crate) fn ui(&mut self, ui: &mut Ui) {
+        ui.add(Slider::new(&mut self.opacity, 0.1..=1.0).text("Cable opacity"));
+        ui.add(Slider::new(&mut self.sag, 0.0..=3.0).text("Cable sag"));
+    }
+}
+
+/// A cable hanging between two points.
+pub(crate) struct Cable {
+    points: [Pos2; 3],
+}
+
+impl Cable {
+    pub(crate) fn new(src: Pos2, dst: Pos2, settings: &CableSettings, zoom: f32) -> Self {
+        let dy = (src.y - dst.y).abs();
+        let sag = vec2(0.0, settings.sag * (0.5 * dy + 30.0 * zoom));
+        let midpoint = src + 0.5 * (dst - src) + sag;
+        Cable {
+            points: [src, midpoint, dst],
+        }
+    }
+
+    pub(crate) fn start(&self) -> Pos2 {
+        self.points[0]
+    }
+
+    pub(crate) fn end(&self) -> Pos2 {
+        self.points[2]
+    }
+
+    /// The lowest point of the cable.
+    pub(crate) fn middle(&self) -> Pos2 {
+        self.point(0.5)
+    }
+
+    fn point(&self, t: f32) -> Pos2 {
+        let [a, b, c] = self.points;
+        let u = 1.0 - t;
+        (u * u * a.to_vec2() + 2.0 * u * t * b.to_vec2() + t * t * c.to_vec2()).to_pos2()
+    }
+
+    /// Whether a position is close enough to the cable to count as hovering it, and if so, how
+    /// far away it is.
+    pub(crate) fn hit(&self, pos: Pos2, zoom: f32) -> Option<f32> {
+        let points: Vec<Pos2> = (0..=HIT_SEGMENTS)
+            .map(|i| self.point(i as f32 / HIT_SEGMENTS as f32))
+            .collect();
+        let distance = points
+            .windows(2)
+            .map(|segment| distance_to_segment(pos, segment[0], segment[1]))
+            .fold(f32::INFINITY, f32::min);
+        Some(distance).filter(|&d| d <= HOVER_DISTANCE * zoom.max(1.0))
+    }
+
+    pub(crate) fn draw(&self, ui: &Ui, color: Color32, width: f32, opacity: f32) {
+        let stroke = Stroke::new(
+            width,
+            Color32::from_rgba_unmultiplied(
+                color.r(),
+                color.g(),
+                color.b(),
+                (255.0 * opacity) as u8,
+            ),
+        );
+        ui.painter()
+            .add(Shape::from(QuadraticBezierShape::from_points_stroke(
+                self.points,
+                false,
+                Color32::default(),
+                stroke,
+            )));
+    }
+}
+
+fn distance_to_segment(pos: Pos2, a: Pos2, b: Pos2) -> f32 {
+    let (ab, ap) = (b - a, pos - a);
+    let t = if ab.length_sq() > 0.0 {
+        ((ap.x * ab.x + ap.y * ab.y) / ab.length_sq()).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    pos.distance(a + t * ab)
+}
@@ -0,0 +1,128 @@
+use eframe::egui::{self, vec2, Pos2, Rect, Vec2};
+use patch_file::RackPosition;
+
+use crate::panels::{HP_PIXELS, PANEL_HEIGHT};
+
+/// The vertical distance between the tops of consecutive rows, in unzoomed pixels.
+const ROW_PITCH: f32 = (PANEL_HEIGHT + HP_PIXELS) as f32;
+const MIN_ZOOM: f32 = 0.25;
+const MAX_ZOOM: f32 = 2.0;
+
+/// The height of the strip along the top of each panel that it can be dragged by.
+pub(crate) const HEADER_HEIGHT: f32 = 24.0;
+
+/// How the rack is panned and zoomed within the window.
+pub(crate) struct RackView {
+    /// The screen offset of the rack's origin from the top left of the canvas.
+    offset: Vec2,
+    zoom: f32,
+}
+
+impl Default for RackView {
+    fn default() -> Self {
+        RackView {
+            // Leave room for the audio output panel, which sits left of the first HP.
+            offset: vec2(6.0 * HP_PIXELS as f32, 10.0),
+            zoom: 1.0,
+        }
+    }
+}
+
+impl RackView {
+    pub(crate) fn zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    /// Pans with the scroll wheel or by dragging the background, and zooms around the pointer
+    /// with ctrl and the scroll wheel, or by pinching.
+    pub(crate) fn navigate(&mut self, ui: &egui::Ui, canvas: Rect, background: &egui::Response) {
+        if background.dragged() {
+            self.offset += background.drag_delta();
+        }
+        let input = ui.input();
+        let hover = match input.pointer.hover_pos() {
+            Some(pos) if canvas.contains(pos) => pos,
+            _ => return,
+        };
+        let zoom_delta = input.zoom_delta();
+        if zoom_delta != 1.0 {
+            let zoom = (self.zoom * zoom_delta).clamp(MIN_ZOOM, MAX_ZOOM);
+            // Keep the point under the pointer in place.
+            let anchor = hover - canvas.min;
+            self.offset = anchor - (anchor - self.offset) * (zoom / self.zoom);
+            self.zoom = zoom;
+        } else {
+            self.offset += input.scroll_delta;
+        }
+    }
+
+    /// The screen rect of a panel `hp` wide at the given position. The HP may be negative, to
+    /// place panels before the start of the rack.
+    pub(crate) fn panel_rect(&self, canvas: Rect, row: usize, hp: isize, width: usize) -> Rect {
+        let min = canvas.min
+            + self.offset
+            + self.zoom * vec2(hp as f32 * HP_PIXELS as f32, row as f32 * ROW_PITCH);
+        Rect::from_min_size(
+            min,
+            self.zoom * vec2((width * HP_PIXELS) as f32, PANEL_HEIGHT as f32),
+        )
+    }
+
+    /// The nearest rack position to a screen position, for the top left corner of a panel.
+    pub(crate) fn snap(&self, canvas: Rect, pos: Pos2) -> RackPosition {
+        let rack = (pos - canvas.min - self.offset) / self.zoom;
+        RackPosition {
+            row: (rack.y / ROW_PITCH).round().max(0.0) as usize,
+            hp: (rack.x / HP_PIXELS as f32).round().max(0.0) as usize,
+        }
+    }
+}
+
+/// Scales the sizes in a style, so that panels drawn with it match the zoom.
+pub(crate) fn zoom_style(style: &mut egui::Style, zoom: f32) {
+    for font in style.text_styles.values_mut() {
+        font.size *= zoom;
+    }
+    let spacing = &mut style.spacing;
+    spacing.item_spacing *= zoom;
+    spacing.button_padding *= zoom;
+    spacing.interact_size *= zoom;
+    spacing.indent *= zoom;
+    spacing.icon_width *= zoom;
+    spacing.icon_spacing *= zoom;
+}
+
+/// Moves a panel, pushing any panels it lands on, and those they then land on, to the right.
+/// Panels entirely to the left of its new position stay where they are.
+pub(crate) fn place(
+    positions: &mut [RackPosition],
+    widths: &[usize],
+    index: usize,
+    target: RackPosition,
+) {
+    positions[index] = target;
+    let mut neighbours: Vec<usize> = (0..positions.len())
+        .filter(|&i| {
+            i != index && positions[i].row == target.row && positions[i].hp + widths[i] > target.hp
+        })
+        .collect();
+    neighbours.sort_by_key(|&i| positions[i].hp);
+
+    let mut end = target.hp + widths[index];
+    for i in neighbours {
+        positions[i].hp = positions[i].hp.max(end);
+        end = positions[i].hp + widths[i];
+    }
+}
+
+/// The first position after every panel in a row.
+pub(crate) fn end_of_row(positions: &[RackPosition], widths: &[usize], row: usize) -> RackPosition {
+    let hp = positions
+        .iter()
+        .zip(widths)
+        .filter(|(p, _)| p.row == row)
+        .map(|(p, w)| p.hp + w)
+        .max()
+        .unwrap_or(0);
+    RackPosition { row, hp }
+}
@@ -7,6 +7,10 @@ pub(crate) fn configure_fonts(ctx: &egui::Context) {
         "SpaceMonoBoldItalic".to_owned(),
         FontData::from_static(include_bytes!("../../assets/SpaceMono-BoldItalic.ttf")),
     );
+    fonts.font_data.insert(
+        "SpaceMono".to_owned(),
+        FontData::from_static(include_bytes!("../../assets/SpaceMono-Regular.ttf")),
+    );
     fonts.font_data.insert(
         "SpaceGrotesk".to_owned(),
         FontData::from_static(include_bytes!("../../assets/SpaceGrotesk-Medium.ttf")),
@@ -21,6 +25,12 @@ pub(crate) fn configure_fonts(ctx: &egui::Context) {
     fonts
         .families
         .insert(regular.clone(), vec!["SpaceGrotesk".to_owned()]);
+    // Readouts such as `widgets::display::Display` use the monospace family.
+    fonts
+        .families
+        .entry(FontFamily::Monospace)
+        .or_default()
+        .insert(0, "SpaceMono".to_owned());
     ctx.set_fonts(fonts);
 
     // Configure default fonts:
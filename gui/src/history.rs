@@ -0,0 +1,380 @@
+use std::collections::HashMap;
+
+use module::{ModuleState, Oversampling, SerializedParameter};
+use patch_file::{RackPosition, SerializedConnection, SerializedModule};
+
+/// A reversible change to a patch.
+///
+/// Modules are referred to by index, and connections use the same indices as patch files. These
+/// stay valid because edits are always undone and redone in order, so the patch is in the same
+/// shape each time an edit is replayed.
+#[derive(Clone, Debug)]
+pub(crate) enum Edit {
+    AddModule {
+        index: usize,
+        module: SerializedModule,
+    },
+    RemoveModule {
+        index: usize,
+        module: SerializedModule,
+        /// Every connection to or from the module, from before it was removed.
+        connections: Vec<SerializedConnection>,
+    },
+    Connect {
+        connection: SerializedConnection,
+        /// The connection that previously went to the same input, if any.
+        replaced: Option<SerializedConnection>,
+    },
+    Disconnect {
+        connections: Vec<SerializedConnection>,
+    },
+    SetParams {
+        index: usize,
+        before: HashMap<String, SerializedParameter>,
+        after: HashMap<String, SerializedParameter>,
+    },
+    SetState {
+        index: usize,
+        before: ModuleState,
+        after: ModuleState,
+    },
+    SetOversampling {
+        index: usize,
+        before: Oversampling,
+        after: Oversampling,
+    },
+    SetCableColor {
+        connection: SerializedConnection,
+        before: [u8; 3],
+        after: [u8; 3],
+    },
+    /// Moves panels in the rack. Every panel's position is kept, as moving one may push others.
+    MoveModules {
+        before: Vec<RackPosition>,
+        after: Vec<RackPosition>,
+    },
+}
+
+/// The undo and redo stacks for a patch.
+pub(crate) struct History {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+    /// A change that is still in progress, such as a knob being dragged.
+    pending: Option<Edit>,
+    /// The depth of the undo stack when the patch was last saved, if that state can still be
+    /// reached.
+    saved: Option<usize>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            pending: None,
+            saved: Some(0),
+        }
+    }
+}
+
+impl History {
+    /// Records an edit that has just been made.
+    pub(crate) fn push(&mut self, edit: Edit) {
+        self.commit();
+        self.record(edit);
+    }
+
+    /// Records a change to a module's parameters or a cable's colour. Consecutive changes to the
+    /// same thing are merged until [`History::commit`] is called, so that a whole drag is undone
+    /// in one step.
+    pub(crate) fn change(&mut self, edit: Edit) {
+        match (&mut self.pending, edit) {
+            (
+                Some(Edit::SetParams {
+                    index: pending_index,
+                    after: pending_after,
+                    ..
+                }),
+                Edit::SetParams { index, after, .. },
+            ) if *pending_index == index => *pending_after = after,
+            (
+                Some(Edit::SetCableColor {
+                    connection: pending_connection,
+                    after: pending_after,
+                    ..
+                }),
+                Edit::SetCableColor {
+                    connection, after, ..
+                },
+            ) if same_cable(pending_connection, &connection) => *pending_after = after,
+            (_, edit) => {
+                self.commit();
+                self.pending = Some(edit);
+            }
+        }
+    }
+
+    /// Finishes any change in progress, making it a single undo step.
+    pub(crate) fn commit(&mut self) {
+        if let Some(edit) = self.pending.take() {
+            self.record(edit);
+        }
+    }
+
+    fn record(&mut self, edit: Edit) {
+        // A saved state on the redo stack can no longer be returned to.
+        if self.saved.is_some_and(|depth| depth > self.undo.len()) {
+            self.saved = None;
+        }
+        self.undo.push(edit);
+        self.redo.clear();
+    }
+
+    /// Remembers the current state as the one on disk.
+    pub(crate) fn mark_saved(&mut self) {
+        self.commit();
+        self.saved = Some(self.undo.len());
+    }
+
+    /// Whether there are changes since the patch was last saved or loaded.
+    pub(crate) fn is_dirty(&self) -> bool {
+        self.pending.is_some() || self.saved != Some(self.undo.len())
+    }
+
+    /// Moves the latest edit onto the redo stack, returning it so that it can be reverted.
+    pub(crate) fn undo(&mut self) -> Option<Edit> {
+        self.commit();
+        let edit = self.undo.pop()?;
+        self.redo.push(edit.clone());
+        Some(edit)
+    }
+
+    /// Moves the latest undone edit back onto the undo stack, returning it so that it can be
+    /// replayed.
+    pub(crate) fn redo(&mut self) -> Option<Edit> {
+        self.commit();
+        let edit = self.redo.pop()?;
+        self.undo.push(edit.clone());
+        Some(edit)
+    }
+
+    pub(crate) fn can_undo(&self) -> bool {
+        !self.undo.is_empty() || self.pending.is_some()
+    }
+
+    pub(crate) fn can_redo(&self) -> bool {
+        // A change in progress clears the redo stack as soon as it's committed.
+        self.pending.is_none() && !self.redo.is_empty()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        *self = History::default();
+    }
+}
+
+/// Whether two connections join the same ports, whatever their colours.
+fn same_cable(a: &SerializedConnection, b: &SerializedConnection) -> bool {
+    (a.src_index, a.src_channel, a.dst_index, a.dst_channel)
+        == (b.src_index, b.src_channel, b.dst_index, b.dst_channel)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set_param(index: usize, before: f32, after: f32) -> Edit {
+        let param = |value| HashMap::from([("p".to_owned(), SerializedParameter::Num(value))]);
+        Edit::SetParams {
+            index,
+            before: param(before),
+            after: param(after),
+        }
+    }
+
+    fn set_color(src_index: usize, before: u8, after: u8) -> Edit {
+        Edit::SetCableColor {
+            connection: SerializedConnection {
+                src_index,
+                src_channel: 0,
+                dst_index: 1,
+                dst_channel: 0,
+                color: Some([before; 3]),
+            },
+            before: [before; 3],
+            after: [after; 3],
+        }
+    }
+
+    fn bypass(index: usize) -> Edit {
+        Edit::SetState {
+            index,
+            before: ModuleState::Active,
+            after: ModuleState::Bypassed,
+        }
+    }
+
+    fn param_change(edit: Option<Edit>) -> (usize, f32, f32) {
+        let value = |params: &HashMap<String, SerializedParameter>| match params["p"] {
+            SerializedParameter::Num(value) => value,
+            SerializedParameter::List(_) => panic!(),
+        };
+        match edit {
+            Some(Edit::SetParams {
+                index,
+                before,
+                after,
+            }) => (index, value(&before), value(&after)),
+            edit => panic!("{:?}", edit),
+        }
+    }
+
+    #[test]
+    fn coalesces_changes_to_the_same_thing() {
+        let mut history = History::default();
+        history.change(set_param(0, 0.0, 0.1));
+        history.change(set_param(0, 0.1, 0.2));
+        history.change(set_param(0, 0.2, 0.3));
+        assert!(history.can_undo());
+        assert_eq!(param_change(history.undo()), (0, 0.0, 0.3));
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn separates_changes_to_different_things() {
+        let mut history = History::default();
+        history.change(set_param(0, 0.0, 0.1));
+        history.change(set_param(1, 0.0, 0.5));
+        history.change(set_param(0, 0.1, 0.2));
+        assert_eq!(param_change(history.undo()), (0, 0.1, 0.2));
+        assert_eq!(param_change(history.undo()), (1, 0.0, 0.5));
+        assert_eq!(param_change(history.undo()), (0, 0.0, 0.1));
+
+        history.change(set_color(0, 1, 2));
+        history.change(set_color(0, 2, 3));
+        history.change(set_color(2, 1, 2));
+        assert!(matches!(
+            history.undo(),
+            Some(Edit::SetCableColor {
+                after: [2, 2, 2],
+                ..
+            })
+        ));
+        assert!(matches!(
+            history.undo(),
+            Some(Edit::SetCableColor {
+                before: [1, 1, 1],
+                after: [3, 3, 3],
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn commit_ends_a_change() {
+        let mut history = History::default();
+        history.change(set_param(0, 0.0, 0.1));
+        history.commit();
+        history.change(set_param(0, 0.1, 0.2));
+        assert_eq!(param_change(history.undo()), (0, 0.1, 0.2));
+        assert_eq!(param_change(history.undo()), (0, 0.0, 0.1));
+
+        // Pushing an edit commits the change in progress before it.
+        history.change(set_param(0, 0.0, 0.1));
+        history.push(bypass(0));
+        assert!(matches!(history.undo(), Some(Edit::SetState { .. })));
+        assert_eq!(param_change(history.undo()), (0, 0.0, 0.1));
+    }
+
+    #[test]
+    fn undoes_and_redoes_in_order() {
+        let mut history = History::default();
+        history.push(bypass(0));
+        history.push(bypass(1));
+        assert!(matches!(
+            history.undo(),
+            Some(Edit::SetState { index: 1, .. })
+        ));
+        assert!(matches!(
+            history.undo(),
+            Some(Edit::SetState { index: 0, .. })
+        ));
+        assert!(history.undo().is_none());
+        assert!(history.can_redo());
+        assert!(matches!(
+            history.redo(),
+            Some(Edit::SetState { index: 0, .. })
+        ));
+        assert!(matches!(
+            history.redo(),
+            Some(Edit::SetState { index: 1, .. })
+        ));
+        assert!(history.redo().is_none());
+    }
+
+    #[test]
+    fn new_edits_invalidate_redo() {
+        let mut history = History::default();
+        history.push(bypass(0));
+        history.push(bypass(1));
+        history.undo();
+        history.push(bypass(2));
+        assert!(!history.can_redo());
+        assert!(history.redo().is_none());
+
+        // So do changes, once they're in progress.
+        history.undo();
+        assert!(history.can_redo());
+        history.change(set_param(0, 0.0, 0.1));
+        assert!(!history.can_redo());
+        assert_eq!(param_change(history.undo()), (0, 0.0, 0.1));
+        assert!(matches!(
+            history.undo(),
+            Some(Edit::SetState { index: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn tracks_the_saved_depth() {
+        let mut history = History::default();
+        assert!(!history.is_dirty());
+        history.push(bypass(0));
+        assert!(history.is_dirty());
+        history.mark_saved();
+        assert!(!history.is_dirty());
+
+        // Undoing past the save and redoing back to it.
+        history.undo();
+        assert!(history.is_dirty());
+        history.redo();
+        assert!(!history.is_dirty());
+
+        // A change in progress is unsaved, and saving commits it.
+        history.change(set_param(0, 0.0, 0.1));
+        assert!(history.is_dirty());
+        history.mark_saved();
+        assert!(!history.is_dirty());
+        assert_eq!(param_change(history.undo()), (0, 0.0, 0.1));
+        assert!(history.is_dirty());
+    }
+
+    #[test]
+    fn saved_state_on_the_redo_stack_is_lost_by_new_edits() {
+        let mut history = History::default();
+        history.push(bypass(0));
+        history.push(bypass(1));
+        history.mark_saved();
+        history.undo();
+        history.undo();
+        // Back to the depth of the save, but on a different branch.
+        history.push(bypass(2));
+        history.push(bypass(3));
+        assert!(history.is_dirty());
+        history.undo();
+        history.undo();
+        assert!(history.is_dirty());
+
+        history.clear();
+        assert!(!history.is_dirty());
+        assert!(!history.can_undo());
+    }
+}
@@ -1,18 +1,58 @@
-use audio_host::AudioHost;
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::Ordering,
+};
+
+use audio_host::{AudioEvent, AudioHost, AudioMessage};
 use eframe::{egui, epi};
-use module::registry::ModuleRegistry;
+use module::registry::{Category, ModuleRegistry};
 use native_dialog::FileDialog;
 
+mod browser;
+mod cables;
+mod canvas;
 mod fonts;
+mod history;
+mod metering;
 mod panels;
 mod patch;
+mod recent;
+mod watch;
 
-use crate::patch::Patch;
+use crate::{
+    browser::ModuleBrowser, cables::CableSettings, metering::DspLoad, patch::Patch,
+    recent::RecentPatches, watch::PatchWatcher,
+};
 
 pub struct ModularSynth {
     registry: ModuleRegistry,
     audio_host: AudioHost,
     patch: Patch,
+    browser: ModuleBrowser,
+    dsp_load: DspLoad,
+    show_module_load: bool,
+    cable_settings: CableSettings,
+    watcher: Option<PatchWatcher>,
+    /// The file the patch was last loaded from or saved to.
+    path: Option<PathBuf>,
+    recent: RecentPatches,
+    /// An action waiting on the user to decide what to do with unsaved changes.
+    unsaved_prompt: Option<PendingAction>,
+    /// Set once the user has agreed to quit, so that quitting isn't prompted for again.
+    exit_confirmed: bool,
+    title: String,
+    /// The last error from loading or saving a patch, shown until the next success.
+    patch_error: Option<String>,
+}
+
+/// Actions that replace the current patch, and so may need to wait for unsaved changes to be
+/// dealt with first.
+enum PendingAction {
+    Load(PathBuf),
+    Watch(PathBuf),
+    /// Loads the watched patch again, after it changed on disk.
+    Reload,
+    Quit,
 }
 
 impl ModularSynth {
@@ -21,6 +61,17 @@ impl ModularSynth {
             registry,
             audio_host,
             patch: Patch::new(),
+            browser: ModuleBrowser::default(),
+            dsp_load: DspLoad::default(),
+            show_module_load: false,
+            cable_settings: CableSettings::default(),
+            watcher: None,
+            path: None,
+            recent: RecentPatches::default(),
+            unsaved_prompt: None,
+            exit_confirmed: false,
+            title: String::new(),
+            patch_error: None,
         }
     }
 
@@ -29,23 +80,196 @@ impl ModularSynth {
             .add_module(&mut self.registry, &self.audio_host, id);
     }
 
-    fn save_patch(&mut self) {
-        if let Ok(Some(path)) = FileDialog::new()
-            .add_filter("JSON", &["json"])
+    fn set_show_module_load(&mut self, show: bool) {
+        self.show_module_load = show;
+        self.audio_host
+            .send_message(AudioMessage::SetProfiling(show));
+        if !show {
+            self.patch.clear_module_loads();
+        }
+    }
+
+    fn poll_audio_events(&mut self) {
+        while let Some(event) = self.audio_host.poll_event() {
+            match event {
+                AudioEvent::DspLoad { load, peak, xruns } => {
+                    self.dsp_load = DspLoad { load, peak, xruns };
+                }
+                AudioEvent::ModuleLoad(handle, load) => {
+                    if self.show_module_load {
+                        self.patch.set_module_load(handle, load);
+                    }
+                }
+                AudioEvent::ModuleFault(handle, fault) => {
+                    self.patch.set_module_fault(handle, fault);
+                }
+                AudioEvent::SignalLevel(output, level) => {
+                    self.patch.set_signal_level(output, level);
+                }
+            }
+        }
+    }
+
+    /// Saves to the current file, or asks for one if there isn't one yet. Returns whether the
+    /// patch was saved.
+    fn save_patch(&mut self) -> bool {
+        match self.path.clone() {
+            Some(path) => self.save_patch_to(path),
+            None => self.save_patch_as(),
+        }
+    }
+
+    fn save_patch_as(&mut self) -> bool {
+        match FileDialog::new()
+            .add_filter("Patch", &["json", patch_file::dsl::EXTENSION])
             .set_location("./patches")
             .show_save_single_file()
         {
-            self.patch.save(path);
+            Ok(Some(path)) => self.save_patch_to(path),
+            _ => false,
+        }
+    }
+
+    fn save_patch_to(&mut self, path: PathBuf) -> bool {
+        if let Err(e) = self.patch.save(&mut self.registry, &path) {
+            self.patch_error = Some(e.to_string());
+            return false;
+        }
+        // Our own save shouldn't look like an outside change to a watched file.
+        if let Some(watcher) = &mut self.watcher {
+            if watcher.path() == path {
+                watcher.sync();
+            }
         }
+        self.recent.add(&path);
+        self.path = Some(path);
+        self.patch_error = None;
+        true
     }
 
-    fn load_patch(&mut self) {
-        if let Ok(Some(path)) = FileDialog::new()
-            .add_filter("JSON", &["json"])
+    fn choose_patch() -> Option<PathBuf> {
+        FileDialog::new()
+            .add_filter("Patch", &["json", patch_file::dsl::EXTENSION])
             .set_location("./patches")
             .show_open_single_file()
-        {
-            self.patch.load(&mut self.registry, &self.audio_host, path);
+            .ok()
+            .flatten()
+    }
+
+    fn load_patch(&mut self, path: &Path) {
+        match self.patch.load(&mut self.registry, &self.audio_host, path) {
+            Ok(()) => {
+                self.recent.add(path);
+                self.path = Some(path.to_owned());
+                self.patch_error = None;
+            }
+            Err(e) => self.patch_error = Some(e.to_string()),
+        }
+    }
+
+    /// Loads a patch, then reloads it whenever the file changes. Only the differences are applied,
+    /// so the audio carries on uninterrupted.
+    fn watch_patch(&mut self, path: PathBuf) {
+        self.load_patch(&path);
+        self.watcher = Some(PatchWatcher::new(path));
+    }
+
+    /// Reloads the watched patch if it has changed. Unsaved edits made in the rack since the
+    /// last reload are asked about first, rather than being lost. If that's cancelled, the patch
+    /// is marked as changed on disk until it's reloaded by hand.
+    fn poll_watched_patch(&mut self, frame: &epi::Frame) {
+        // A change that arrives while the prompt is showing is picked up once it's answered.
+        if self.unsaved_prompt.is_some() {
+            return;
+        }
+        if self.watcher.as_mut().is_some_and(|watcher| watcher.poll()) {
+            self.request(PendingAction::Reload, frame);
+        }
+    }
+
+    fn reload_watched_patch(&mut self) {
+        if let Some(watcher) = &mut self.watcher {
+            let path = watcher.path().to_owned();
+            watcher.sync();
+            self.load_patch(&path);
+        }
+    }
+
+    /// Runs an action that replaces the current patch, first asking what to do with any unsaved
+    /// changes.
+    fn request(&mut self, action: PendingAction, frame: &epi::Frame) {
+        if self.patch.is_dirty() {
+            self.unsaved_prompt = Some(action);
+        } else {
+            self.run(action, frame);
+        }
+    }
+
+    fn run(&mut self, action: PendingAction, frame: &epi::Frame) {
+        match action {
+            PendingAction::Load(path) => self.load_patch(&path),
+            PendingAction::Watch(path) => self.watch_patch(path),
+            PendingAction::Reload => self.reload_watched_patch(),
+            PendingAction::Quit => {
+                self.exit_confirmed = true;
+                frame.quit();
+            }
+        }
+    }
+
+    fn show_unsaved_prompt(&mut self, ctx: &egui::Context, frame: &epi::Frame) {
+        if self.unsaved_prompt.is_none() {
+            return;
+        }
+        let mut save = None;
+        let mut cancel = false;
+        egui::Window::new("Unsaved changes")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label(format!("Save changes to {} first?", self.patch_name()));
+                ui.horizontal(|ui| {
+                    if ui.button("Save").clicked() {
+                        save = Some(true);
+                    }
+                    if ui.button("Don't save").clicked() {
+                        save = Some(false);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        cancel = true;
+                    }
+                });
+            });
+
+        if cancel {
+            self.unsaved_prompt = None;
+        } else if let Some(save) = save {
+            // Keep asking if the save didn't happen, e.g. if the dialog was closed.
+            if save && !self.save_patch() {
+                return;
+            }
+            let action = self.unsaved_prompt.take().unwrap();
+            self.run(action, frame);
+        }
+    }
+
+    fn patch_name(&self) -> String {
+        self.path
+            .as_ref()
+            .and_then(|path| path.file_name())
+            .map_or("Untitled".to_owned(), |name| {
+                name.to_string_lossy().into_owned()
+            })
+    }
+
+    /// Shows the patch name in the title bar, marking it while there are unsaved changes.
+    fn update_title(&mut self, frame: &epi::Frame) {
+        let dirty = if self.patch.is_dirty() { "*" } else { "" };
+        let title = format!("{}{} - {}", dirty, self.patch_name(), epi::App::name(self));
+        if title != self.title {
+            frame.set_window_title(&title);
+            self.title = title;
         }
     }
 }
@@ -59,51 +283,219 @@ impl epi::App for ModularSynth {
         &mut self,
         ctx: &egui::Context,
         _frame: &epi::Frame,
-        _storage: Option<&dyn epi::Storage>,
+        storage: Option<&dyn epi::Storage>,
     ) {
         fonts::configure_fonts(ctx);
+        if let Some(storage) = storage {
+            self.recent = RecentPatches::load(storage);
+            self.cable_settings = CableSettings::load(storage);
+        }
+    }
+
+    fn save(&mut self, storage: &mut dyn epi::Storage) {
+        self.recent.save(storage);
+        self.cable_settings.save(storage);
+    }
+
+    fn on_exit_event(&mut self) -> bool {
+        if self.patch.is_dirty() && !self.exit_confirmed {
+            self.unsaved_prompt = Some(PendingAction::Quit);
+            false
+        } else {
+            true
+        }
     }
 
-    fn update(&mut self, ctx: &egui::Context, _frame: &epi::Frame) {
+    fn update(&mut self, ctx: &egui::Context, frame: &epi::Frame) {
         use egui::{Key, Modifiers};
-        if ctx.input_mut().consume_key(Modifiers::COMMAND, Key::S) {
+
+        // Audio status changes continuously, so we must keep redrawing to display it.
+        self.poll_audio_events();
+        ctx.request_repaint();
+
+        if ctx
+            .input_mut()
+            .consume_key(Modifiers::COMMAND | Modifiers::SHIFT, Key::S)
+        {
+            self.save_patch_as();
+        } else if ctx.input_mut().consume_key(Modifiers::COMMAND, Key::S) {
             self.save_patch();
         } else if ctx.input_mut().consume_key(Modifiers::COMMAND, Key::O) {
-            self.load_patch();
+            if let Some(path) = Self::choose_patch() {
+                self.request(PendingAction::Load(path), frame);
+            }
+        }
+        if ctx.input_mut().consume_key(Modifiers::COMMAND, Key::M) {
+            self.browser.open();
+        }
+        // While typing, e.g. into the module browser's search box, undo belongs to the text.
+        if !ctx.wants_keyboard_input() {
+            if ctx
+                .input_mut()
+                .consume_key(Modifiers::COMMAND | Modifiers::SHIFT, Key::Z)
+            {
+                self.patch.redo(&mut self.registry, &self.audio_host);
+            } else if ctx.input_mut().consume_key(Modifiers::COMMAND, Key::Z) {
+                self.patch.undo(&mut self.registry, &self.audio_host);
+            }
         }
+        self.poll_watched_patch(frame);
 
         egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
             egui::menu::bar(ui, |ui| {
+                ui.menu_button("Edit", |ui| {
+                    let undo = egui::Button::new("Undo (Ctrl+Z)");
+                    if ui.add_enabled(self.patch.can_undo(), undo).clicked() {
+                        self.patch.undo(&mut self.registry, &self.audio_host);
+                        ui.close_menu();
+                    }
+                    let redo = egui::Button::new("Redo (Ctrl+Shift+Z)");
+                    if ui.add_enabled(self.patch.can_redo(), redo).clicked() {
+                        self.patch.redo(&mut self.registry, &self.audio_host);
+                        ui.close_menu();
+                    }
+                });
                 ui.menu_button("Modules", |ui| {
+                    if ui.button("Browse... (Ctrl+M)").clicked() {
+                        self.browser.open();
+                        ui.close_menu();
+                    }
+                    ui.separator();
                     let mut modules = self.registry.all_modules();
                     modules.sort_by_key(|m| m.name.clone());
-                    for manifest in modules {
-                        if ui.button(manifest.name).clicked() {
-                            self.add_module(manifest.id);
+                    for category in Category::ALL {
+                        let in_category: Vec<_> =
+                            modules.iter().filter(|m| m.category == category).collect();
+                        if in_category.is_empty() {
+                            continue;
                         }
+                        ui.menu_button(category.name(), |ui| {
+                            for manifest in in_category {
+                                if ui.button(&manifest.name).clicked() {
+                                    self.add_module(manifest.id.clone());
+                                    ui.close_menu();
+                                }
+                            }
+                        });
                     }
                 });
                 ui.menu_button("Patches", |ui| {
-                    if ui.button("Save patch...").clicked() {
+                    if ui.button("Save (Ctrl+S)").clicked() {
                         self.save_patch();
                         ui.close_menu();
                     }
-                    if ui.button("Load patch...").clicked() {
-                        self.load_patch();
+                    if ui.button("Save as... (Ctrl+Shift+S)").clicked() {
+                        self.save_patch_as();
+                        ui.close_menu();
+                    }
+                    if ui.button("Load patch... (Ctrl+O)").clicked() {
+                        if let Some(path) = Self::choose_patch() {
+                            self.request(PendingAction::Load(path), frame);
+                        }
+                        ui.close_menu();
+                    }
+                    ui.menu_button("Recent patches", |ui| {
+                        let mut chosen = None;
+                        for path in self.recent.iter() {
+                            if ui.button(path.display().to_string()).clicked() {
+                                chosen = Some(path.to_owned());
+                            }
+                        }
+                        if let Some(path) = chosen {
+                            self.request(PendingAction::Load(path), frame);
+                            ui.close_menu();
+                        }
+                    });
+                    ui.separator();
+                    if self.watcher.is_some() {
+                        if ui.button("Stop watching").clicked() {
+                            self.watcher = None;
+                            ui.close_menu();
+                        }
+                    } else if ui.button("Watch patch...").clicked() {
+                        if let Some(path) = Self::choose_patch() {
+                            self.request(PendingAction::Watch(path), frame);
+                        }
                         ui.close_menu();
                     }
                 });
+                ui.menu_button("View", |ui| {
+                    self.cable_settings.ui(ui);
+                });
+                ui.menu_button("Output", |ui| {
+                    let master = self.audio_host.master();
+                    let mut dc_blocker = master.dc_blocker.load(Ordering::Relaxed);
+                    if ui.checkbox(&mut dc_blocker, "DC blocker").changed() {
+                        master.dc_blocker.store(dc_blocker, Ordering::Relaxed);
+                    }
+                    let mut limiter = master.limiter.load(Ordering::Relaxed);
+                    if ui.checkbox(&mut limiter, "Limiter").changed() {
+                        master.limiter.store(limiter, Ordering::Relaxed);
+                    }
+                });
                 ui.menu_button("Debug", |ui| {
                     if ui.button("Toggle layout on hover").clicked() {
                         ctx.set_debug_on_hover(!ctx.debug_on_hover());
                         ui.close_menu();
                     }
+                    let mut show_module_load = self.show_module_load;
+                    if ui
+                        .checkbox(&mut show_module_load, "Show module CPU usage")
+                        .changed()
+                    {
+                        self.set_show_module_load(show_module_load);
+                    }
+                });
+                ui.with_layout(egui::Layout::right_to_left(), |ui| {
+                    // The panic button silences all output until it is clicked again.
+                    let muted = &self.audio_host.master().muted;
+                    let button = if muted.load(Ordering::Relaxed) {
+                        egui::Button::new("Unmute").fill(egui::Color32::from_rgb(200, 40, 40))
+                    } else {
+                        egui::Button::new("Panic")
+                    };
+                    if ui.add(button).clicked() {
+                        muted.fetch_xor(true, Ordering::Relaxed);
+                    }
+                    ui.add(self.dsp_load);
+                    if let Some(error) = &self.patch_error {
+                        ui.colored_label(egui::Color32::from_rgb(200, 40, 40), "Patch error")
+                            .on_hover_text(egui::RichText::new(error).monospace());
+                    }
+                    if let Some(watcher) = &self.watcher {
+                        let changed = watcher.is_changed();
+                        ui.label(format!("Watching {}", watcher.path().display()));
+                        // A reload that was cancelled at the unsaved changes prompt.
+                        if changed {
+                            ui.colored_label(
+                                egui::Color32::from_rgb(230, 160, 40),
+                                "Changed on disk",
+                            );
+                            if ui
+                                .button("Reload")
+                                .on_hover_text("Replace the rack with the patch on disk.")
+                                .clicked()
+                            {
+                                self.request(PendingAction::Reload, frame);
+                            }
+                        }
+                    }
                 });
             });
         });
 
         egui::CentralPanel::default().show(ctx, |ui| {
-            self.patch.update(&self.audio_host, ui);
+            self.patch.update(
+                &mut self.registry,
+                &self.audio_host,
+                &self.cable_settings,
+                ui,
+            );
         });
+        if let Some(id) = self.browser.show(ctx, &mut self.registry) {
+            self.add_module(id);
+        }
+        self.show_unsaved_prompt(ctx, frame);
+        self.update_title(frame);
     }
 }
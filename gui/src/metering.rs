@@ -0,0 +1,36 @@
+use eframe::egui;
+
+/// The most recent DSP load reported by the audio thread.
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct DspLoad {
+    pub(crate) load: f32,
+    pub(crate) peak: f32,
+    pub(crate) xruns: usize,
+}
+
+impl egui::Widget for DspLoad {
+    fn ui(self, ui: &mut egui::Ui) -> egui::Response {
+        ui.horizontal(|ui| {
+            if self.xruns > 0 {
+                ui.colored_label(egui::Color32::YELLOW, format!("{} xruns", self.xruns));
+            }
+            ui.add(
+                egui::ProgressBar::new(self.load.min(1.0))
+                    .desired_width(100.0)
+                    .text(format!("DSP {:.0}%", 100.0 * self.load)),
+            )
+            .on_hover_text(format!(
+                "Average load: {:.1}%\nPeak load: {:.1}%\nXruns: {}",
+                100.0 * self.load,
+                100.0 * self.peak,
+                self.xruns
+            ));
+        })
+        .response
+    }
+}
+
+/// Formats a module's share of the buffer duration as a short badge label.
+pub(crate) fn format_module_load(load: f32) -> String {
+    format!("{:.1}%", 100.0 * load)
+}
@@ -1,47 +1,153 @@
+use std::sync::atomic::Ordering;
+
+use audio_host::MasterSettings;
 use eframe::egui;
-use module::{ModuleHandle, Panel};
+use module::{ModuleHandle, ModuleState, Panel};
+use rack::ModuleFault;
+
+use widgets::{
+    jack::{self, Jack},
+    knob::Knob,
+};
 
-use widgets::jack::{self, Jack};
+use crate::metering;
 
-const HP_PIXELS: usize = 20;
-const PANEL_HEIGHT: usize = 25 * HP_PIXELS;
+pub(crate) const HP_PIXELS: usize = 20;
+pub(crate) const PANEL_HEIGHT: usize = 25 * HP_PIXELS;
 
 pub(crate) fn panel_to_widget(
     handle: ModuleHandle,
     panel: &mut dyn Panel,
+    cpu_load: Option<f32>,
 ) -> impl egui::Widget + '_ {
     move |ui: &mut egui::Ui| {
         let width = HP_PIXELS * panel.width();
         let desired_size = egui::vec2(width as f32, PANEL_HEIGHT as f32);
-        let (rect, response) = ui.allocate_exact_size(desired_size, egui::Sense::hover());
-
-        if ui.is_rect_visible(rect) {
-            ui.painter().rect(
-                rect,
-                10.0,
-                ui.visuals().faint_bg_color,
-                ui.visuals().noninteractive().bg_stroke,
-            );
-            let mut panel_ui = ui.child_ui(
-                rect.shrink(10.0),
-                egui::Layout::top_down(egui::Align::Center),
-            );
-            panel.update(&handle, &mut panel_ui);
-        }
+        let (rect, _) = ui.allocate_exact_size(desired_size, egui::Sense::hover());
+        draw_panel(ui, rect, 1.0, handle, panel, cpu_load)
+    }
+}
+
+/// Draws a panel filling the given rect, with its decorations scaled by `zoom`. The UI's style
+/// should already be scaled to match.
+pub(crate) fn draw_panel(
+    ui: &mut egui::Ui,
+    rect: egui::Rect,
+    zoom: f32,
+    handle: ModuleHandle,
+    panel: &mut dyn Panel,
+    cpu_load: Option<f32>,
+) -> egui::Response {
+    // Panels are clickable to support context menus.
+    let response = ui.interact(rect, ui.id().with(handle), egui::Sense::click());
+
+    ui.painter().rect(
+        rect,
+        10.0 * zoom,
+        ui.visuals().faint_bg_color,
+        ui.visuals().noninteractive().bg_stroke,
+    );
+    let mut panel_ui = ui.child_ui(
+        rect.shrink(10.0 * zoom),
+        egui::Layout::top_down(egui::Align::Center),
+    );
+    panel.update(&handle, &mut panel_ui);
 
-        response
+    if let Some(load) = cpu_load {
+        ui.painter().text(
+            rect.right_top() + zoom * egui::vec2(-6.0, 4.0),
+            egui::Align2::RIGHT_TOP,
+            metering::format_module_load(load),
+            egui::TextStyle::Small.resolve(ui.style()),
+            ui.visuals().weak_text_color(),
+        );
     }
+
+    response
+}
+
+/// Outlines a faulted module's panel and shows a warning badge, which resets the module when
+/// clicked. Returns true if a reset was requested.
+pub(crate) fn fault_indicator(
+    ui: &mut egui::Ui,
+    rect: egui::Rect,
+    zoom: f32,
+    fault: ModuleFault,
+) -> bool {
+    let color = egui::Color32::from_rgb(200, 40, 40);
+    ui.painter()
+        .rect_stroke(rect, 10.0 * zoom, egui::Stroke::new(2.0, color));
+
+    let (label, description) = match fault {
+        ModuleFault::Panicked => ("CRASHED", "This module crashed, and has been muted."),
+        ModuleFault::NonFinite => (
+            "NaN",
+            "This module produced invalid voltages, which were replaced with silence.",
+        ),
+    };
+    let badge = egui::Rect::from_min_size(
+        rect.left_top() + zoom * egui::vec2(8.0, 6.0),
+        zoom * egui::vec2(70.0, 18.0),
+    );
+    ui.put(
+        badge,
+        egui::Button::new(
+            egui::RichText::new(label)
+                .small()
+                .color(egui::Color32::WHITE),
+        )
+        .fill(color),
+    )
+    .on_hover_text(format!("{}\nClick to reset the module.", description))
+    .clicked()
 }
 
-pub(crate) struct AudioOutputPanel;
+/// Dims a bypassed or muted module's panel and shows a badge, which reactivates the module when
+/// clicked. Returns true if the module should be reactivated.
+pub(crate) fn state_indicator(
+    ui: &mut egui::Ui,
+    rect: egui::Rect,
+    zoom: f32,
+    state: ModuleState,
+) -> bool {
+    let label = match state {
+        ModuleState::Active => return false,
+        ModuleState::Bypassed => "BYPASS",
+        ModuleState::Muted => "MUTED",
+    };
+    ui.painter()
+        .rect_filled(rect, 10.0 * zoom, egui::Color32::from_black_alpha(96));
 
-impl Panel for AudioOutputPanel {
+    let badge = egui::Rect::from_min_size(
+        rect.left_top() + zoom * egui::vec2(8.0, 28.0),
+        zoom * egui::vec2(70.0, 18.0),
+    );
+    ui.put(badge, egui::Button::new(egui::RichText::new(label).small()))
+        .on_hover_text("Click to reactivate the module.")
+        .clicked()
+}
+
+pub(crate) struct AudioOutputPanel<'a>(pub(crate) &'a MasterSettings);
+
+impl<'a> Panel for AudioOutputPanel<'a> {
     fn width(&self) -> usize {
         4
     }
 
     fn update(&mut self, handle: &ModuleHandle, ui: &mut egui::Ui) {
         ui.heading("Audio");
+        ui.add_space(20.0);
+        ui.add(
+            Knob::new(&self.0.volume)
+                .decibels(-60.0..=0.0)
+                .default_value(1.0),
+        );
+        ui.label("Volume");
+        ui.add_space(10.0);
+        let muted = self.0.muted.load(Ordering::Relaxed);
+        if ui.selectable_label(muted, "Mute").clicked() {
+            self.0.muted.store(!muted, Ordering::Relaxed);
+        }
         ui.with_layout(egui::Layout::bottom_up(egui::Align::Center), |ui| {
             jack::inputs(ui, |ui| {
                 ui.add(Jack::input(handle.input(0)));
@@ -1,19 +1,35 @@
-use std::{collections::HashMap, fs::File, hash::Hash, path::Path};
+use std::{collections::HashMap, hash::Hash, path::Path};
 
-use ::widgets::jack::JackInteraction;
+use ::widgets::jack::{self, JackInteraction};
 use audio_host::{AudioHost, AudioMessage};
 use eframe::egui::*;
-use eframe::epaint::QuadraticBezierShape;
 use module::{
-    registry::ModuleRegistry, Module, ModuleHandle, ModuleInput, ModuleOutput, Panel,
-    SerializedParameter,
+    registry::ModuleRegistry, Module, ModuleHandle, ModuleInput, ModuleOutput, ModuleState,
+    Oversampling, Panel, SerializedParameter, SignalLevel,
 };
+use patch_file::{
+    PatchError, RackPosition, SerializedConnection, SerializedModule, SerializedPatch,
+};
+use rack::ModuleFault;
 
-use crate::panels;
+use crate::{
+    cables::{self, Cable, CableSettings},
+    canvas::{self, RackView, HEADER_HEIGHT},
+    history::{Edit, History},
+    panels,
+};
 
 pub(crate) struct Patch {
     modules: Vec<ModuleInstance>,
     connections: Vec<Connection>,
+    history: History,
+    view: RackView,
+    /// Where the pointer grabbed the panel being dragged, relative to its top left corner.
+    drag_grab: Option<Vec2>,
+    /// The cable clicked on last, which can be recoloured or deleted.
+    selected_cable: Option<Connection>,
+    /// The latest signal reported on each module output.
+    signals: HashMap<ModuleOutput, SignalLevel>,
 }
 
 impl Patch {
@@ -21,6 +37,11 @@ impl Patch {
         Patch {
             modules: Vec::new(),
             connections: Vec::new(),
+            history: History::default(),
+            view: RackView::default(),
+            drag_grab: None,
+            selected_cable: None,
+            signals: HashMap::new(),
         }
     }
 
@@ -29,54 +50,267 @@ impl Patch {
         registry: &mut ModuleRegistry,
         audio_host: &AudioHost,
         id: String,
-    ) -> ModuleHandle {
-        let (handle, module) = registry.create_module(&id).unwrap();
-        audio_host.send_message(AudioMessage::AddModule(
-            handle,
-            module.inputs(),
-            module.outputs(),
-            module.create_audio_unit(),
-        ));
-        self.modules.push(ModuleInstance {
-            id,
-            handle,
-            panel: module.create_panel(),
-            module,
+    ) {
+        let index = self.modules.len();
+        self.modules.push(create_module(registry, audio_host, id));
+        self.auto_place(index);
+        self.history.push(Edit::AddModule {
+            index,
+            module: self.serialize_module(index),
         });
-        handle
     }
 
-    pub(crate) fn save<P: AsRef<Path>>(&self, path: P) {
-        let mut handle_indices: HashMap<ModuleHandle, usize> = self
-            .modules
+    /// Creates a module from its serialized form at the given position.
+    fn insert_module(
+        &mut self,
+        registry: &mut ModuleRegistry,
+        audio_host: &AudioHost,
+        index: usize,
+        serialized: &SerializedModule,
+    ) {
+        let instance = create_module(registry, audio_host, serialized.id.clone());
+        self.modules.insert(index, instance);
+        if serialized.position.is_none() {
+            self.auto_place(index);
+        }
+        self.configure_module(index, serialized, audio_host);
+    }
+
+    /// Puts a module at the end of the first row.
+    fn auto_place(&mut self, index: usize) {
+        let (mut positions, widths) = self.layout();
+        // Ignore the module's own position.
+        positions[index].row = usize::MAX;
+        self.modules[index].position = canvas::end_of_row(&positions, &widths, 0);
+    }
+
+    /// The position and width of every panel.
+    fn layout(&self) -> (Vec<RackPosition>, Vec<usize>) {
+        self.modules
             .iter()
-            .enumerate()
-            .map(|(i, inst)| (inst.handle, i))
-            .collect();
-        handle_indices.insert(rack::AUDIO_OUTPUT_HANDLE, handle_indices.len());
+            .map(|m| (m.position, m.panel.width()))
+            .unzip()
+    }
 
-        let mut serialized = SerializedPatch::default();
+    fn set_layout(&mut self, positions: &[RackPosition]) {
+        for (module, position) in self.modules.iter_mut().zip(positions) {
+            module.position = *position;
+        }
+    }
+
+    /// Removes a module and any cables connected to it.
+    fn remove_module(&mut self, index: usize, audio_host: &AudioHost) {
+        let module = self.modules.remove(index);
+        // The rack drops the cables itself.
+        self.connections
+            .retain(|c| c.output.module != module.handle && c.input.module != module.handle);
+        self.signals
+            .retain(|output, _| output.module != module.handle);
+        audio_host.send_message(AudioMessage::RemoveModule(module.handle));
+    }
+
+    /// Brings a module's parameters, state, oversampling and position in line with its serialized
+    /// form.
+    fn configure_module(
+        &mut self,
+        index: usize,
+        serialized: &SerializedModule,
+        audio_host: &AudioHost,
+    ) {
+        let module = &self.modules[index].module;
+        let oversampling = serialized
+            .oversampling
+            .unwrap_or_else(|| module.default_oversampling());
+        if serialize_params(module.as_ref()) != serialized.params {
+            self.set_module_params(index, &serialized.params);
+        }
+        if let Some(position) = serialized.position {
+            self.modules[index].position = position;
+        }
+        self.set_module_state(index, serialized.state, audio_host);
+        self.set_module_oversampling(index, oversampling, audio_host);
+    }
+
+    fn set_module_params(&mut self, index: usize, params: &HashMap<String, SerializedParameter>) {
+        let module = &mut self.modules[index];
+        if let Some(p) = module.module.params() {
+            p.deserialize(params);
+        }
+        module.params = params.clone();
+    }
+
+    /// Changes the rate a module runs at, which replaces its audio unit.
+    fn set_module_oversampling(
+        &mut self,
+        index: usize,
+        oversampling: Oversampling,
+        audio_host: &AudioHost,
+    ) {
+        let module = &mut self.modules[index];
+        if module.oversampling != oversampling {
+            module.oversampling = oversampling;
+            audio_host.send_message(AudioMessage::ReplaceAudioUnit(
+                module.handle,
+                oversampling.wrap(
+                    module.module.create_audio_unit(),
+                    module.module.inputs(),
+                    module.module.outputs(),
+                ),
+            ));
+        }
+    }
+
+    fn set_module_state(&mut self, index: usize, state: ModuleState, audio_host: &AudioHost) {
+        let module = &mut self.modules[index];
+        if module.state != state {
+            module.state = state;
+            audio_host.send_message(AudioMessage::SetModuleState(module.handle, state));
+        }
+    }
+
+    fn connect(&mut self, connection: Connection, audio_host: &AudioHost) {
+        audio_host.send_message(AudioMessage::ConnectModules(
+            connection.output,
+            connection.input,
+        ));
+        self.connections.push(connection);
+    }
+
+    fn disconnect(&mut self, connection: Connection, audio_host: &AudioHost) {
+        if let Some(i) = self.connections.iter().position(|c| *c == connection) {
+            audio_host.send_message(AudioMessage::DisconnectModules(
+                connection.output,
+                connection.input,
+            ));
+            self.connections.swap_remove(i);
+        }
+    }
+
+    /// Records the latest CPU load reported for a module, to be shown on its panel.
+    pub(crate) fn set_module_load(&mut self, handle: ModuleHandle, load: f32) {
+        if let Some(module) = self.modules.iter_mut().find(|m| m.handle == handle) {
+            module.cpu_load = Some(load);
+        }
+    }
+
+    /// Records that a module has been isolated by the rack, to be shown on its panel.
+    pub(crate) fn set_module_fault(&mut self, handle: ModuleHandle, fault: ModuleFault) {
+        if let Some(module) = self.modules.iter_mut().find(|m| m.handle == handle) {
+            module.fault = Some(fault);
+        }
+    }
+
+    /// Records the latest signal on a module output, to be shown on its jack and cables.
+    pub(crate) fn set_signal_level(&mut self, output: ModuleOutput, level: SignalLevel) {
+        self.signals.insert(output, level);
+    }
+
+    /// Passes the signal on every jack to the jack widgets. Inputs show the signal of the output
+    /// they're patched to.
+    fn publish_signals(&self, ui: &Ui) {
         for module in &self.modules {
-            let params = match module.module.params() {
-                Some(params) => params.serialize(),
-                _ => HashMap::new(),
-            };
-            serialized.modules.push(SerializedModule {
-                id: module.id.clone(),
-                params,
-            });
+            for channel in 0..module.module.outputs() {
+                let output = module.handle.output(channel);
+                jack::set_signal(ui, output, self.signals.get(&output).copied());
+            }
+            for channel in 0..module.module.inputs() {
+                jack::set_signal(ui, module.handle.input(channel), None);
+            }
+        }
+        jack::set_signal(ui, rack::Rack::audio_output(), None);
+        for c in &self.connections {
+            jack::set_signal(ui, c.input, self.signals.get(&c.output).copied());
+        }
+    }
+
+    /// Hides the CPU load on all panels.
+    pub(crate) fn clear_module_loads(&mut self) {
+        for module in &mut self.modules {
+            module.cpu_load = None;
+        }
+    }
+
+    fn serialize_module(&self, index: usize) -> SerializedModule {
+        let module = &self.modules[index];
+        SerializedModule {
+            id: module.id.clone(),
+            state: module.state,
+            oversampling: Some(module.oversampling)
+                .filter(|&o| o != module.module.default_oversampling()),
+            position: Some(module.position),
+            params: serialize_params(module.module.as_ref()),
+        }
+    }
+
+    /// Converts a connection to refer to modules by index, as in patch files.
+    fn serialize_connection(&self, connection: Connection) -> SerializedConnection {
+        SerializedConnection {
+            src_index: self.module_index(connection.output.module),
+            src_channel: connection.output.channel,
+            dst_index: self.module_index(connection.input.module),
+            dst_channel: connection.input.channel,
+            color: Some([
+                connection.color.r(),
+                connection.color.g(),
+                connection.color.b(),
+            ]),
+        }
+    }
+
+    /// Converts a connection from a patch file, colouring it automatically if it has no colour.
+    fn resolve_connection(&self, connection: &SerializedConnection) -> Connection {
+        let color = match connection.color {
+            Some([r, g, b]) => Color32::from_rgb(r, g, b),
+            None => cables::auto_color(self.connections.iter().map(|c| c.color)),
+        };
+        Connection {
+            output: ModuleOutput {
+                module: self.module_handle(connection.src_index),
+                channel: connection.src_channel,
+            },
+            input: ModuleInput {
+                module: self.module_handle(connection.dst_index),
+                channel: connection.dst_channel,
+            },
+            color,
+        }
+    }
+
+    /// The index of a module, where the audio output comes after all modules.
+    fn module_index(&self, handle: ModuleHandle) -> usize {
+        self.modules
+            .iter()
+            .position(|m| m.handle == handle)
+            .unwrap_or(self.modules.len())
+    }
+
+    fn module_handle(&self, index: usize) -> ModuleHandle {
+        self.modules
+            .get(index)
+            .map_or(rack::AUDIO_OUTPUT_HANDLE, |m| m.handle)
+    }
+
+    pub(crate) fn serialize(&self) -> SerializedPatch {
+        let mut serialized = SerializedPatch::default();
+        for i in 0..self.modules.len() {
+            serialized.modules.push(self.serialize_module(i));
         }
         for connection in &self.connections {
-            serialized.connections.push(SerializedConnection {
-                src_index: handle_indices[&connection.output.module],
-                src_channel: connection.output.channel,
-                dst_index: handle_indices[&connection.input.module],
-                dst_channel: connection.input.channel,
-            });
+            serialized
+                .connections
+                .push(self.serialize_connection(*connection));
         }
+        serialized
+    }
 
-        let file = File::create(path).unwrap();
-        serde_json::to_writer_pretty(file, &serialized).unwrap();
+    pub(crate) fn save<P: AsRef<Path>>(
+        &mut self,
+        registry: &mut ModuleRegistry,
+        path: P,
+    ) -> Result<(), PatchError> {
+        self.serialize().write(path, registry)?;
+        self.history.mark_saved();
+        Ok(())
     }
 
     pub(crate) fn load<P: AsRef<Path>>(
@@ -84,54 +318,387 @@ impl Patch {
         registry: &mut ModuleRegistry,
         audio_host: &AudioHost,
         path: P,
+    ) -> Result<(), PatchError> {
+        let serialized = SerializedPatch::read(path, registry)?;
+        if let Some(error) = serialized.validate(registry).into_iter().next() {
+            return Err(PatchError::Invalid(error));
+        }
+        self.apply(registry, audio_host, &serialized);
+        // Earlier edits may refer to modules that no longer exist.
+        self.history.clear();
+        Ok(())
+    }
+
+    /// Whether there are changes that haven't been saved.
+    pub(crate) fn is_dirty(&self) -> bool {
+        self.history.is_dirty()
+    }
+
+    /// Brings the running patch in line with a valid serialized one, sending only the messages
+    /// needed to get there. Modules are matched up by id in order, so modules that are kept carry
+    /// on running without being reset, unless their oversampling changes.
+    fn apply(
+        &mut self,
+        registry: &mut ModuleRegistry,
+        audio_host: &AudioHost,
+        serialized: &SerializedPatch,
     ) {
-        // This is meant to load from scratch, but I haven't implemented any module removal yet, so
-        // we must assert that its empty before loading.
-        assert!(self.modules.is_empty());
-
-        let file = File::open(path).unwrap();
-        let serialized: SerializedPatch = serde_json::from_reader(file).unwrap();
-
-        let mut handles = Vec::new();
-        for module in &serialized.modules {
-            handles.push(self.add_module(registry, audio_host, module.id.clone()));
-            if let Some(params) = self.modules.last().unwrap().module.params() {
-                params.deserialize(&module.params);
+        let old_ids: Vec<&str> = self.modules.iter().map(|m| m.id.as_str()).collect();
+        let new_ids: Vec<&str> = serialized.modules.iter().map(|m| m.id.as_str()).collect();
+        let matches = match_modules(&old_ids, &new_ids);
+
+        // Remove unmatched modules, from the back so that earlier indices stay valid.
+        let mut kept = vec![false; self.modules.len()];
+        for old_index in matches.iter().flatten() {
+            kept[*old_index] = true;
+        }
+        for old_index in (0..self.modules.len()).rev() {
+            if !kept[old_index] {
+                self.remove_module(old_index, audio_host);
             }
         }
-        handles.push(rack::AUDIO_OUTPUT_HANDLE);
 
-        for connection in &serialized.connections {
-            let output = ModuleOutput {
-                module: handles[connection.src_index],
-                channel: connection.src_channel,
+        // The kept modules are already in their new order, so slot the new ones in between.
+        let mut kept_modules = std::mem::take(&mut self.modules).into_iter();
+        for (module, old_index) in serialized.modules.iter().zip(&matches) {
+            let instance = match old_index {
+                Some(_) => kept_modules.next().unwrap(),
+                None => create_module(registry, audio_host, module.id.clone()),
             };
-            let input = ModuleInput {
-                module: handles[connection.dst_index],
-                channel: connection.dst_channel,
-            };
-            audio_host.send_message(AudioMessage::ConnectModules(output, input));
-            self.connections.push(Connection { output, input });
+            self.modules.push(instance);
+        }
+        for (index, module) in serialized.modules.iter().enumerate() {
+            self.configure_module(index, module, audio_host);
+        }
+        // Lay out new modules without positions after everything else, in order.
+        for (index, old_index) in matches.iter().enumerate() {
+            if old_index.is_none() && serialized.modules[index].position.is_none() {
+                self.auto_place(index);
+            }
+        }
+
+        // Cables that are kept and have no colour of their own keep the colour they had.
+        let old_connections = std::mem::take(&mut self.connections);
+        for c in &serialized.connections {
+            let mut connection = self.resolve_connection(c);
+            if let (None, Some(old)) = (
+                c.color,
+                old_connections.iter().find(|&old| *old == connection),
+            ) {
+                connection.color = old.color;
+            }
+            self.connections.push(connection);
+        }
+
+        // Disconnect first, so that an input being moved to another output is free again.
+        for c in &old_connections {
+            if !self.connections.contains(c) {
+                audio_host.send_message(AudioMessage::DisconnectModules(c.output, c.input));
+            }
+        }
+        for c in &self.connections {
+            if !old_connections.contains(c) {
+                audio_host.send_message(AudioMessage::ConnectModules(c.output, c.input));
+            }
+        }
+        self.selected_cable = None;
+    }
+
+    /// Makes an edit and records it, so that it can be undone.
+    fn perform(&mut self, edit: Edit, registry: &mut ModuleRegistry, audio_host: &AudioHost) {
+        self.replay(edit.clone(), true, registry, audio_host);
+        self.history.push(edit);
+    }
+
+    /// Makes an edit, or reverts it if `forward` is false.
+    fn replay(
+        &mut self,
+        edit: Edit,
+        forward: bool,
+        registry: &mut ModuleRegistry,
+        audio_host: &AudioHost,
+    ) {
+        match edit {
+            Edit::AddModule { index, module } => {
+                if forward {
+                    self.insert_module(registry, audio_host, index, &module);
+                } else {
+                    self.remove_module(index, audio_host);
+                }
+            }
+            Edit::RemoveModule {
+                index,
+                module,
+                connections,
+            } => {
+                if forward {
+                    self.remove_module(index, audio_host);
+                } else {
+                    self.insert_module(registry, audio_host, index, &module);
+                    for c in &connections {
+                        self.connect(self.resolve_connection(c), audio_host);
+                    }
+                }
+            }
+            Edit::Connect {
+                connection,
+                replaced,
+            } => {
+                let connection = Some(self.resolve_connection(&connection));
+                let replaced = replaced.map(|c| self.resolve_connection(&c));
+                let (old, new) = if forward {
+                    (replaced, connection)
+                } else {
+                    (connection, replaced)
+                };
+                if let Some(c) = old {
+                    self.disconnect(c, audio_host);
+                }
+                if let Some(c) = new {
+                    self.connect(c, audio_host);
+                }
+            }
+            Edit::Disconnect { connections } => {
+                for c in &connections {
+                    let c = self.resolve_connection(c);
+                    if forward {
+                        self.disconnect(c, audio_host);
+                    } else {
+                        self.connect(c, audio_host);
+                    }
+                }
+            }
+            Edit::SetParams {
+                index,
+                before,
+                after,
+            } => {
+                self.set_module_params(index, if forward { &after } else { &before });
+            }
+            Edit::SetState {
+                index,
+                before,
+                after,
+            } => {
+                self.set_module_state(index, if forward { after } else { before }, audio_host);
+            }
+            Edit::SetOversampling {
+                index,
+                before,
+                after,
+            } => {
+                let oversampling = if forward { after } else { before };
+                self.set_module_oversampling(index, oversampling, audio_host);
+            }
+            Edit::SetCableColor {
+                connection,
+                before,
+                after,
+            } => {
+                let [r, g, b] = if forward { after } else { before };
+                let connection = self.resolve_connection(&connection);
+                if let Some(c) = self.connections.iter_mut().find(|c| **c == connection) {
+                    c.color = Color32::from_rgb(r, g, b);
+                }
+            }
+            Edit::MoveModules { before, after } => {
+                self.set_layout(if forward { &after } else { &before });
+            }
+        }
+    }
+
+    pub(crate) fn undo(&mut self, registry: &mut ModuleRegistry, audio_host: &AudioHost) {
+        if let Some(edit) = self.history.undo() {
+            self.replay(edit, false, registry, audio_host);
         }
     }
 
-    pub(crate) fn update(&mut self, host: &AudioHost, ui: &mut Ui) {
+    pub(crate) fn redo(&mut self, registry: &mut ModuleRegistry, audio_host: &AudioHost) {
+        if let Some(edit) = self.history.redo() {
+            self.replay(edit, true, registry, audio_host);
+        }
+    }
+
+    pub(crate) fn can_undo(&self) -> bool {
+        self.history.can_undo()
+    }
+
+    pub(crate) fn can_redo(&self) -> bool {
+        self.history.can_redo()
+    }
+
+    pub(crate) fn update(
+        &mut self,
+        registry: &mut ModuleRegistry,
+        host: &AudioHost,
+        cable_settings: &CableSettings,
+        ui: &mut Ui,
+    ) {
+        // Changes from the context menus are applied once all panels are drawn.
+        let mut edits = Vec::new();
+        let mut removed = None;
+
+        // The rack fills the rest of the window, and is drawn with a style to match its zoom.
+        let canvas = ui.available_rect_before_wrap();
+        ui.allocate_rect(canvas, Sense::hover());
+        let mut canvas_ui = ui.child_ui(canvas, *ui.layout());
+        canvas_ui.set_clip_rect(canvas.intersect(ui.clip_rect()));
+        let ui = &mut canvas_ui;
+        let zoom = self.view.zoom();
+        canvas::zoom_style(ui.style_mut(), zoom);
+
         // Draw panels.
-        ScrollArea::horizontal().show(ui, |ui| {
-            ui.horizontal(|ui| {
-                for module in &mut self.modules {
-                    ui.add(panels::panel_to_widget(
-                        module.handle,
-                        module.panel.as_mut(),
-                    ));
+        self.publish_signals(ui);
+        let mut moved = None;
+        for i in 0..self.modules.len() {
+            let module = &mut self.modules[i];
+            let position = module.position;
+            let width = module.panel.width();
+            let rect = self
+                .view
+                .panel_rect(canvas, position.row, position.hp as isize, width);
+            let response = panels::draw_panel(
+                ui,
+                rect,
+                zoom,
+                module.handle,
+                module.panel.as_mut(),
+                module.cpu_load,
+            );
+            if let Some(fault) = module.fault {
+                if panels::fault_indicator(ui, rect, zoom, fault) {
+                    host.send_message(AudioMessage::ResetModule(module.handle));
+                    module.fault = None;
+                }
+            }
+
+            // Panels are moved by dragging along their top edge.
+            let header = Rect::from_min_size(rect.min, vec2(rect.width(), HEADER_HEIGHT * zoom));
+            let header = ui
+                .interact(
+                    header,
+                    ui.id().with(("header", module.handle)),
+                    Sense::drag(),
+                )
+                .on_hover_cursor(CursorIcon::Grab);
+            if header.drag_started() {
+                self.drag_grab = header.interact_pointer_pos().map(|pos| pos - rect.min);
+            }
+            if let (true, Some(grab), Some(pos)) = (
+                header.dragged() || header.drag_released(),
+                self.drag_grab,
+                header.interact_pointer_pos(),
+            ) {
+                let target = self.view.snap(canvas, pos - grab);
+                let target_rect =
+                    self.view
+                        .panel_rect(canvas, target.row, target.hp as isize, width);
+                ui.painter()
+                    .rect_stroke(target_rect, 10.0 * zoom, ui.visuals().selection.stroke);
+                if header.drag_released() {
+                    moved = Some((i, target));
+                    self.drag_grab = None;
+                }
+            }
+
+            let mut oversampling = module.oversampling;
+            let mut state = module.state;
+            let mut remove = false;
+            if !state.is_active() && panels::state_indicator(ui, rect, zoom, state) {
+                state = ModuleState::Active;
+            }
+            response.context_menu(|ui| {
+                ui.radio_value(&mut state, ModuleState::Active, "Active");
+                ui.radio_value(&mut state, ModuleState::Bypassed, "Bypass");
+                ui.radio_value(&mut state, ModuleState::Muted, "Mute");
+                ui.separator();
+                ui.menu_button("Oversampling", |ui| {
+                    for o in Oversampling::ALL {
+                        let label = match o {
+                            Oversampling::None => "None".to_owned(),
+                            _ => format!("{}x", o.factor()),
+                        };
+                        ui.radio_value(&mut oversampling, o, label);
+                    }
+                });
+                ui.separator();
+                if ui.button("Remove").clicked() {
+                    remove = true;
+                    ui.close_menu();
                 }
-                // Always add audio output as the last panel.
-                ui.add(panels::panel_to_widget(
-                    rack::AUDIO_OUTPUT_HANDLE,
-                    &mut panels::AudioOutputPanel,
-                ));
             });
-        });
+            if state != module.state {
+                edits.push(Edit::SetState {
+                    index: i,
+                    before: module.state,
+                    after: state,
+                });
+            }
+            if oversampling != module.oversampling {
+                edits.push(Edit::SetOversampling {
+                    index: i,
+                    before: module.oversampling,
+                    after: oversampling,
+                });
+            }
+            if remove {
+                removed = Some(i);
+            }
+        }
+        // The audio output sits just before the start of the first row.
+        let audio_output = &mut panels::AudioOutputPanel(host.master());
+        let rect = self.view.panel_rect(
+            canvas,
+            0,
+            -(audio_output.width() as isize) - 1,
+            audio_output.width(),
+        );
+        panels::draw_panel(
+            ui,
+            rect,
+            zoom,
+            rack::AUDIO_OUTPUT_HANDLE,
+            audio_output,
+            None,
+        );
+
+        // The background is interacted with last, so that panels get first pick of any drags.
+        let background = ui.interact(canvas, ui.id().with("rack_background"), Sense::drag());
+        self.view.navigate(ui, canvas, &background);
+
+        if let Some((i, target)) = moved {
+            let (before, widths) = self.layout();
+            let mut after = before.clone();
+            canvas::place(&mut after, &widths, i, target);
+            if after != before {
+                edits.push(Edit::MoveModules { before, after });
+            }
+        }
+
+        // Panels write parameters directly, so look for any that changed this frame. Changes are
+        // merged into one edit until the pointer is released, so that a knob drag is one step.
+        for (i, module) in self.modules.iter_mut().enumerate() {
+            let params = serialize_params(module.module.as_ref());
+            if params != module.params {
+                let before = std::mem::replace(&mut module.params, params.clone());
+                self.history.change(Edit::SetParams {
+                    index: i,
+                    before,
+                    after: params,
+                });
+            }
+        }
+        if !ui.input().pointer.any_down() {
+            self.history.commit();
+        }
+
+        for edit in edits {
+            self.perform(edit, registry, host);
+        }
+        // Removal comes last, so that the other edits' indices are still valid.
+        if let Some(i) = removed {
+            self.perform(self.removal(i), registry, host);
+        }
 
         // Handle any interactions from Jack widgets:
         let mut pending_source = None;
@@ -140,25 +707,90 @@ impl Patch {
                 JackInteraction::PendingInput(input) => pending_source = locate(ui, input),
                 JackInteraction::PendingOutput(output) => pending_source = locate(ui, output),
                 JackInteraction::CreateConnection(output, input) => {
-                    self.maybe_clear_input(input, host);
-                    host.send_message(AudioMessage::ConnectModules(output, input));
-                    self.connections.push(Connection { output, input });
+                    let replaced = self.connections.iter().find(|c| c.input == input);
+                    let color = cables::auto_color(self.connections.iter().map(|c| c.color));
+                    let edit = Edit::Connect {
+                        connection: self.serialize_connection(Connection {
+                            output,
+                            input,
+                            color,
+                        }),
+                        replaced: replaced.map(|c| self.serialize_connection(*c)),
+                    };
+                    self.perform(edit, registry, host);
                     JackInteraction::clear(ui);
                 }
                 JackInteraction::ClearInput(input) => {
-                    self.maybe_clear_input(input, host);
+                    self.disconnect_where(|c| c.input == input, registry, host);
                     JackInteraction::clear(ui);
                 }
                 JackInteraction::ClearOutput(output) => {
-                    self.clear_all_outputs(output, host);
+                    self.disconnect_where(|c| c.output == output, registry, host);
                     JackInteraction::clear(ui);
                 }
             }
         }
 
+        self.update_cables(pending_source, cable_settings, registry, host, ui);
+    }
+
+    /// Draws the cables, letting them be hovered, selected, recoloured and deleted, along with
+    /// any cable being patched from `pending_source`.
+    fn update_cables(
+        &mut self,
+        pending_source: Option<Pos2>,
+        settings: &CableSettings,
+        registry: &mut ModuleRegistry,
+        host: &AudioHost,
+        ui: &mut Ui,
+    ) {
+        let zoom = self.view.zoom();
+        let cables: Vec<Cable> = self
+            .connections
+            .iter()
+            .map(|c| {
+                let src = locate(ui, c.output).unwrap();
+                let dst = locate(ui, c.input).unwrap();
+                Cable::new(src, dst, settings, zoom)
+            })
+            .collect();
+
+        // Cables can only be picked out from the rack itself, and not while patching or dragging.
+        let pointer = ui.input().pointer.hover_pos().filter(|&pos| {
+            ui.ctx().layer_id_at(pos) == Some(ui.layer_id())
+                && ui.clip_rect().contains(pos)
+                && pending_source.is_none()
+                && !ui.memory().is_anything_being_dragged()
+        });
+        let hovered = pointer.and_then(|pos| {
+            cables
+                .iter()
+                .enumerate()
+                .filter_map(|(i, cable)| cable.hit(pos, zoom).map(|d| (i, d)))
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .map(|(i, _)| i)
+        });
+        if pointer.is_some() && primary_clicked(ui) {
+            self.selected_cable = hovered.map(|i| self.connections[i]);
+        }
+        // Forget a selected cable that has since been unpatched.
+        self.selected_cable = self
+            .selected_cable
+            .and_then(|s| self.connections.iter().find(|&&c| c == s).copied());
+
         // Draw existing connections:
-        for c in &self.connections {
-            Cable::new(locate(ui, c.output).unwrap(), locate(ui, c.input).unwrap()).draw(ui);
+        for (i, (c, cable)) in self.connections.iter().zip(&cables).enumerate() {
+            let color = cables::tint(c.color, self.signals.get(&c.output));
+            if hovered == Some(i) || self.selected_cable == Some(*c) {
+                cable.draw(ui, color, 7.0 * zoom, 1.0);
+                let radius = 0.75 * ui.spacing().interact_size.y + 3.0 * zoom;
+                let stroke = Stroke::new(2.0 * zoom, color);
+                for end in [cable.start(), cable.end()] {
+                    ui.painter().circle_stroke(end, radius, stroke);
+                }
+            } else {
+                cable.draw(ui, color, 5.0 * zoom, settings.opacity);
+            }
         }
 
         // Handle ongoing new connection:
@@ -171,61 +803,114 @@ impl Patch {
             } else {
                 let hover_pos = ui.ctx().input().pointer.hover_pos();
                 if let Some(pos) = hover_pos {
-                    Cable::new(src_pos, pos).draw(ui);
+                    let color = cables::auto_color(self.connections.iter().map(|c| c.color));
+                    Cable::new(src_pos, pos, settings, zoom).draw(
+                        ui,
+                        color,
+                        5.0 * zoom,
+                        settings.opacity,
+                    );
                 }
             }
         }
-    }
 
-    fn maybe_clear_input(&mut self, input: ModuleInput, host: &AudioHost) {
-        if let Some(i) = self.connections.iter().position(|c| c.input == input) {
-            host.send_message(AudioMessage::DisconnectModules(
-                self.connections[i].output,
-                input,
-            ));
-            self.connections.swap_remove(i);
+        if let Some(selected) = self.selected_cable {
+            let i = self
+                .connections
+                .iter()
+                .position(|&c| c == selected)
+                .unwrap();
+            self.cable_popup(i, cables[i].middle(), registry, host, ui);
         }
     }
 
-    fn clear_all_outputs(&mut self, output: ModuleOutput, host: &AudioHost) {
-        while let Some(i) = self.connections.iter().position(|c| c.output == output) {
-            host.send_message(AudioMessage::DisconnectModules(
-                output,
-                self.connections[i].input,
-            ));
-            self.connections.swap_remove(i);
+    /// Shows the colour and delete controls for the selected cable, which is at `index`.
+    fn cable_popup(
+        &mut self,
+        index: usize,
+        pos: Pos2,
+        registry: &mut ModuleRegistry,
+        host: &AudioHost,
+        ui: &Ui,
+    ) {
+        let connection = self.connections[index];
+        let mut color = connection.color;
+        let mut delete = ui.memory().focus().is_none()
+            && (ui.input().key_pressed(Key::Delete) || ui.input().key_pressed(Key::Backspace));
+        Area::new("selected_cable")
+            .order(Order::Foreground)
+            .fixed_pos(pos)
+            .show(ui.ctx(), |ui| {
+                Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        for swatch in cables::PALETTE {
+                            let (rect, response) =
+                                ui.allocate_exact_size(vec2(16.0, 16.0), Sense::click());
+                            ui.painter().rect_filled(rect, 2.0, swatch);
+                            if swatch == color {
+                                ui.painter().rect_stroke(
+                                    rect.expand(2.0),
+                                    2.0,
+                                    ui.visuals().selection.stroke,
+                                );
+                            }
+                            if response.clicked() {
+                                color = swatch;
+                            }
+                        }
+                        ui.color_edit_button_srgba(&mut color);
+                        delete |= ui.button("Delete").clicked();
+                    });
+                });
+            });
+
+        if delete {
+            let connections = vec![self.serialize_connection(connection)];
+            self.perform(Edit::Disconnect { connections }, registry, host);
+            self.selected_cable = None;
+        } else if color != connection.color {
+            // Colours don't change the sound, so they're set directly rather than replayed.
+            let color = Color32::from_rgb(color.r(), color.g(), color.b());
+            self.connections[index].color = color;
+            let serialized = self.serialize_connection(self.connections[index]);
+            self.history.change(Edit::SetCableColor {
+                before: self.serialize_connection(connection).color.unwrap(),
+                after: serialized.color.unwrap(),
+                connection: serialized,
+            });
         }
     }
-}
-
-struct Cable {
-    src: Pos2,
-    dst: Pos2,
-}
 
-impl Cable {
-    fn new(src: Pos2, dst: Pos2) -> Self {
-        Cable { src, dst }
+    /// The edit that removes a module, remembering its connections so that they can be restored.
+    fn removal(&self, index: usize) -> Edit {
+        let handle = self.modules[index].handle;
+        Edit::RemoveModule {
+            index,
+            module: self.serialize_module(index),
+            connections: self
+                .connections
+                .iter()
+                .filter(|c| c.output.module == handle || c.input.module == handle)
+                .map(|c| self.serialize_connection(*c))
+                .collect(),
+        }
     }
 
-    fn draw(self, ui: &mut Ui) {
-        let stroke = if ui.visuals().dark_mode {
-            Stroke::new(5.0, Color32::from_white_alpha(64))
-        } else {
-            Stroke::new(5.0, Color32::from_black_alpha(128))
-        };
-
-        let dy = (self.src.y - self.dst.y).abs();
-        let sag = vec2(0.0, 0.5 * dy + 30.0);
-        let midpoint = self.src + 0.5 * (self.dst - self.src) + sag;
-
-        ui.painter()
-            .add(Shape::from(QuadraticBezierShape::from_points_stroke(
-                [self.src, midpoint, self.dst],
-                false,
-                Color32::default(),
-                stroke,
-            )));
+    fn disconnect_where(
+        &mut self,
+        f: impl Fn(&Connection) -> bool,
+        registry: &mut ModuleRegistry,
+        host: &AudioHost,
+    ) {
+        let connections: Vec<_> = self
+            .connections
+            .iter()
+            .filter(|c| f(c))
+            .map(|c| self.serialize_connection(*c))
+            .collect();
+        if !connections.is_empty() {
+            self.perform(Edit::Disconnect { connections }, registry, host);
+        }
     }
 }
 
@@ -234,33 +919,98 @@ struct ModuleInstance {
     handle: ModuleHandle,
     module: Box<dyn Module>,
     panel: Box<dyn Panel>,
+    state: ModuleState,
+    oversampling: Oversampling,
+    position: RackPosition,
+    /// The parameters as of the last frame, to notice when a panel changes them.
+    params: HashMap<String, SerializedParameter>,
+    cpu_load: Option<f32>,
+    fault: Option<ModuleFault>,
 }
 
 #[derive(Copy, Clone, Debug)]
 struct Connection {
     output: ModuleOutput,
     input: ModuleInput,
+    color: Color32,
 }
 
-#[derive(Default, Debug, serde::Serialize, serde::Deserialize)]
-struct SerializedPatch {
-    modules: Vec<SerializedModule>,
-    connections: Vec<SerializedConnection>,
+/// Cables are identified by their ends, whatever their colour.
+impl PartialEq for Connection {
+    fn eq(&self, other: &Self) -> bool {
+        self.output == other.output && self.input == other.input
+    }
 }
 
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
-struct SerializedModule {
+fn create_module(
+    registry: &mut ModuleRegistry,
+    audio_host: &AudioHost,
     id: String,
-    #[serde(flatten)]
-    params: HashMap<String, SerializedParameter>,
+) -> ModuleInstance {
+    let (handle, module) = registry.create_module(&id).unwrap();
+    let oversampling = module.default_oversampling();
+    audio_host.send_message(AudioMessage::AddModule(
+        handle,
+        module.inputs(),
+        module.outputs(),
+        module.bypass_routes(),
+        oversampling.wrap(
+            module.create_audio_unit(),
+            module.inputs(),
+            module.outputs(),
+        ),
+    ));
+    ModuleInstance {
+        id,
+        handle,
+        panel: module.create_panel(),
+        params: serialize_params(module.as_ref()),
+        module,
+        state: ModuleState::Active,
+        oversampling,
+        // Set by the caller, once it knows where the module goes.
+        position: RackPosition { row: 0, hp: 0 },
+        cpu_load: None,
+        fault: None,
+    }
 }
 
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
-struct SerializedConnection {
-    src_index: usize,
-    src_channel: usize,
-    dst_index: usize,
-    dst_channel: usize,
+fn serialize_params(module: &dyn Module) -> HashMap<String, SerializedParameter> {
+    module
+        .params()
+        .map(|params| params.serialize())
+        .unwrap_or_default()
+}
+
+/// Pairs up modules between an old and new list of ids, keeping as many as possible in order.
+/// Returns the matching old index, if any, for each new module.
+fn match_modules(old: &[&str], new: &[&str]) -> Vec<Option<usize>> {
+    // Longest common subsequence, where lengths[i][j] covers old[i..] and new[j..].
+    let mut lengths = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i] == new[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut matches = vec![None; new.len()];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            matches[j] = Some(i);
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    matches
 }
 
 fn locate<T>(ui: &Ui, io: T) -> Option<Pos2>
@@ -270,3 +1020,257 @@ where
     let id = Id::new(io);
     ui.memory().data.get_temp(id)
 }
+
+/// Whether the primary pointer button was clicked this frame, wherever the pointer is.
+fn primary_clicked(ui: &Ui) -> bool {
+    let input = ui.input();
+    input.pointer.any_click()
+        && input.events.iter().any(|e| {
+            matches!(
+                e,
+                Event::PointerButton {
+                    button: PointerButton::Primary,
+                    pressed: false,
+                    ..
+                }
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::Receiver;
+
+    use patch_file::dsl;
+
+    use super::*;
+
+    #[test]
+    fn matches_modules_in_order() {
+        assert_eq!(match_modules(&["a", "b"], &["a", "b"]), [Some(0), Some(1)]);
+        assert_eq!(
+            match_modules(&["a", "b"], &["a", "c", "b"]),
+            [Some(0), None, Some(1)]
+        );
+        assert_eq!(
+            match_modules(&["a", "b", "c"], &["a", "c"]),
+            [Some(0), Some(2)]
+        );
+        // Only one of two swapped modules can be kept.
+        assert_eq!(match_modules(&["a", "b"], &["b", "a"]), [Some(1), None]);
+        assert_eq!(
+            match_modules(&["a", "a", "b"], &["a", "b", "a"]),
+            [Some(0), Some(2), None]
+        );
+        assert_eq!(match_modules(&[], &["a"]), [None]);
+        assert!(match_modules(&["a"], &[]).is_empty());
+    }
+
+    /// Applies patches written as text, keeping track of what's sent to the audio thread.
+    struct Fixture {
+        registry: ModuleRegistry,
+        audio_host: AudioHost,
+        messages: Receiver<AudioMessage>,
+        patch: Patch,
+    }
+
+    impl Fixture {
+        fn new(source: &str) -> Self {
+            let (audio_host, messages) = AudioHost::detached();
+            let mut fixture = Fixture {
+                registry: modules::builtin_modules(),
+                audio_host,
+                messages,
+                patch: Patch::new(),
+            };
+            fixture.apply(source);
+            fixture
+        }
+
+        /// Applies a patch, returning the messages sent for it.
+        fn apply(&mut self, source: &str) -> Vec<String> {
+            let serialized = dsl::parse(source, &mut self.registry).unwrap();
+            self.patch
+                .apply(&mut self.registry, &self.audio_host, &serialized);
+            self.messages.try_iter().map(|m| describe(&m)).collect()
+        }
+
+        fn handles(&self) -> Vec<usize> {
+            self.patch.modules.iter().map(|m| m.handle.0).collect()
+        }
+    }
+
+    fn describe(message: &AudioMessage) -> String {
+        match message {
+            AudioMessage::AddModule(handle, ..) => format!("add {}", handle.0),
+            AudioMessage::RemoveModule(handle) => format!("remove {}", handle.0),
+            AudioMessage::ConnectModules(output, input) => format!(
+                "connect {}.{} -> {}.{}",
+                output.module.0, output.channel, input.module.0, input.channel
+            ),
+            AudioMessage::DisconnectModules(output, input) => format!(
+                "disconnect {}.{} -> {}.{}",
+                output.module.0, output.channel, input.module.0, input.channel
+            ),
+            AudioMessage::ReplaceAudioUnit(handle, _) => format!("replace {}", handle.0),
+            AudioMessage::SetModuleState(handle, state) => {
+                format!("state {} {:?}", handle.0, state)
+            }
+            AudioMessage::ResetModule(handle) => format!("reset {}", handle.0),
+            AudioMessage::SetProfiling(on) => format!("profiling {}", on),
+        }
+    }
+
+    const CLOCK_AND_VCO: &str = "
+        clock = Clock()
+        vco = Vco()
+        clock.trigger -> vco.v_oct
+    ";
+
+    #[test]
+    fn adds_modules() {
+        let mut fixture = Fixture::new(CLOCK_AND_VCO);
+        let [clock, vco] = fixture.handles()[..] else {
+            panic!()
+        };
+        let messages = fixture.apply(
+            "
+            clock = Clock()
+            lfo = Lfo()
+            vco = Vco()
+            clock.trigger -> vco.v_oct
+            lfo.0 -> out
+            ",
+        );
+        let [_, lfo, _] = fixture.handles()[..] else {
+            panic!()
+        };
+        assert_eq!(fixture.handles(), [clock, lfo, vco]);
+        // The clock's cable is left alone, though its modules have moved.
+        assert_eq!(
+            messages,
+            [
+                format!("add {}", lfo),
+                format!("connect {}.0 -> {}.0", lfo, rack::AUDIO_OUTPUT_HANDLE.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn removes_modules() {
+        let mut fixture = Fixture::new(
+            "
+            clock = Clock()
+            lfo = Lfo()
+            vco = Vco()
+            clock.trigger -> vco.v_oct
+            lfo.0 -> out
+            ",
+        );
+        let [clock, lfo, vco] = fixture.handles()[..] else {
+            panic!()
+        };
+        // The rack drops the removed module's cables itself.
+        assert_eq!(fixture.apply(CLOCK_AND_VCO), [format!("remove {}", lfo)]);
+        assert_eq!(fixture.handles(), [clock, vco]);
+        assert_eq!(fixture.patch.connections.len(), 1);
+    }
+
+    #[test]
+    fn reorders_modules() {
+        let mut fixture = Fixture::new(CLOCK_AND_VCO);
+        let [clock, vco] = fixture.handles()[..] else {
+            panic!()
+        };
+        let messages = fixture.apply(
+            "
+            vco = Vco()
+            clock = Clock()
+            clock.trigger -> vco.v_oct
+            ",
+        );
+        // The VCO carries on, and the clock is replaced.
+        let [kept, new_clock] = fixture.handles()[..] else {
+            panic!()
+        };
+        assert_eq!(kept, vco);
+        assert_ne!(new_clock, clock);
+        assert_eq!(
+            messages,
+            [
+                format!("remove {}", clock),
+                format!("add {}", new_clock),
+                format!("connect {}.0 -> {}.0", new_clock, vco),
+            ]
+        );
+    }
+
+    #[test]
+    fn changes_connections() {
+        let mut fixture = Fixture::new(CLOCK_AND_VCO);
+        let [clock, vco] = fixture.handles()[..] else {
+            panic!()
+        };
+        let messages = fixture.apply(
+            "
+            clock = Clock()
+            vco = Vco()
+            vco.saw -> out
+            ",
+        );
+        assert_eq!(
+            messages,
+            [
+                format!("disconnect {}.0 -> {}.0", clock, vco),
+                format!("connect {}.0 -> {}.0", vco, rack::AUDIO_OUTPUT_HANDLE.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn changes_parameters_without_resetting() {
+        let mut fixture = Fixture::new(CLOCK_AND_VCO);
+        let handles = fixture.handles();
+        let messages = fixture.apply(
+            "
+            clock = Clock(bpm=90)
+            vco = Vco()
+            clock.trigger -> vco.v_oct
+            ",
+        );
+        assert!(messages.is_empty(), "{:?}", messages);
+        assert_eq!(fixture.handles(), handles);
+        let params = &fixture.patch.serialize().modules[0].params;
+        assert_eq!(params["bpm"], SerializedParameter::Num(90.0));
+        assert_eq!(
+            fixture.patch.modules[0]
+                .module
+                .params()
+                .unwrap()
+                .serialize()["bpm"],
+            SerializedParameter::Num(90.0)
+        );
+    }
+
+    #[test]
+    fn changes_state_and_oversampling() {
+        let mut fixture = Fixture::new(CLOCK_AND_VCO);
+        let [clock, vco] = fixture.handles()[..] else {
+            panic!()
+        };
+        let messages = fixture.apply(
+            "
+            clock = Clock(state=bypassed)
+            vco = Vco(oversampling=8)
+            clock.trigger -> vco.v_oct
+            ",
+        );
+        assert_eq!(
+            messages,
+            [
+                format!("state {} Bypassed", clock),
+                format!("replace {}", vco)
+            ]
+        );
+    }
+}
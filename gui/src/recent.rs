@@ -0,0 +1,33 @@
+use std::path::{Path, PathBuf};
+
+use eframe::epi;
+
+const STORAGE_KEY: &str = "recent_patches";
+const MAX_RECENT: usize = 10;
+
+/// The patches most recently opened or saved, newest first.
+#[derive(Default)]
+pub(crate) struct RecentPatches(Vec<PathBuf>);
+
+impl RecentPatches {
+    pub(crate) fn load(storage: &dyn epi::Storage) -> Self {
+        let paths = storage.get_string(STORAGE_KEY).unwrap_or_default();
+        RecentPatches(paths.lines().map(PathBuf::from).collect())
+    }
+
+    pub(crate) fn save(&self, storage: &mut dyn epi::Storage) {
+        let paths: Vec<_> = self.0.iter().map(|p| p.display().to_string()).collect();
+        storage.set_string(STORAGE_KEY, paths.join("\n"));
+    }
+
+    /// Moves a patch to the top of the list.
+    pub(crate) fn add(&mut self, path: &Path) {
+        self.0.retain(|p| p != path);
+        self.0.insert(0, path.to_owned());
+        self.0.truncate(MAX_RECENT);
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Path> {
+        self.0.iter().map(PathBuf::as_path)
+    }
+}
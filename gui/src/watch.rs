@@ -0,0 +1,67 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+/// How often the watched file is checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Watches a patch file for changes made by other programs, e.g. a text editor, by polling its
+/// modification time.
+pub(crate) struct PatchWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    last_poll: Instant,
+    /// Whether the file has changed since it was last loaded or saved.
+    changed: bool,
+}
+
+impl PatchWatcher {
+    pub(crate) fn new(path: PathBuf) -> Self {
+        PatchWatcher {
+            modified: modified_time(&path),
+            path,
+            last_poll: Instant::now(),
+            changed: false,
+        }
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Takes the file as it is now as unchanged, e.g. after loading it or writing to it ourselves.
+    pub(crate) fn sync(&mut self) {
+        self.modified = modified_time(&self.path);
+        self.changed = false;
+    }
+
+    /// Whether the file has changed in a way that hasn't been loaded yet, e.g. because reloading
+    /// it was cancelled.
+    pub(crate) fn is_changed(&self) -> bool {
+        self.changed
+    }
+
+    /// Whether the file has changed since the last call. The file is only checked once per poll
+    /// interval, so this is cheap to call every frame.
+    pub(crate) fn poll(&mut self) -> bool {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = Instant::now();
+        let modified = modified_time(&self.path);
+        // A missing file is not a change, as editors may briefly remove it while saving.
+        if modified.is_some() && modified != self.modified {
+            self.modified = modified;
+            self.changed = true;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
@@ -0,0 +1,272 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{bail, Context};
+use audio_host::{MasterBus, MasterSettings};
+use clap::Subcommand;
+use module::{registry::ModuleRegistry, SerializedParameter};
+use patch_file::{dsl, SerializedPatch, CURRENT_VERSION};
+
+#[derive(Subcommand)]
+pub(crate) enum Command {
+    /// Checks that patches load against the builtin and plugin modules.
+    Validate {
+        #[arg(required = true)]
+        patches: Vec<PathBuf>,
+    },
+    /// Lists a patch's modules, parameters and connections.
+    Info { patch: PathBuf },
+    /// Writes a patch as a Graphviz DOT graph.
+    Graph {
+        patch: PathBuf,
+        /// Where to write the graph, instead of stdout.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Renders a patch's audio output to a WAV file, without an audio device.
+    Render {
+        patch: PathBuf,
+        #[arg(short, long)]
+        output: PathBuf,
+        #[arg(short, long, default_value_t = 10.0)]
+        seconds: f32,
+        #[arg(long, default_value_t = 48_000)]
+        sample_rate: u32,
+    },
+    /// Converts a patch between JSON and the text format, depending on its extension.
+    Convert {
+        patch: PathBuf,
+        /// Where to write the converted patch, instead of stdout.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Rewrites patches from older versions of the file format.
+    Upgrade {
+        #[arg(required = true)]
+        patches: Vec<PathBuf>,
+        /// Only list patches that need upgrading, failing if there are any.
+        #[arg(long)]
+        check: bool,
+    },
+}
+
+impl Command {
+    pub(crate) fn run(self, mut registry: ModuleRegistry) -> anyhow::Result<()> {
+        match self {
+            Command::Validate { patches } => validate(&mut registry, &patches),
+            Command::Info { patch } => info(&mut registry, &patch),
+            Command::Graph { patch, output } => match output {
+                Some(path) => graph(&mut registry, &patch, File::create(path)?),
+                None => graph(&mut registry, &patch, io::stdout().lock()),
+            },
+            Command::Render {
+                patch,
+                output,
+                seconds,
+                sample_rate,
+            } => render(&mut registry, &patch, &output, seconds, sample_rate),
+            Command::Convert { patch, output } => convert(&mut registry, &patch, output),
+            Command::Upgrade { patches, check } => upgrade(&mut registry, &patches, check),
+        }
+    }
+}
+
+fn load(registry: &mut ModuleRegistry, path: &Path) -> anyhow::Result<SerializedPatch> {
+    SerializedPatch::read(path, registry)
+        .with_context(|| format!("couldn't load {}", path.display()))
+}
+
+fn validate(registry: &mut ModuleRegistry, paths: &[PathBuf]) -> anyhow::Result<()> {
+    let mut failures = 0;
+    for path in paths {
+        let errors: Vec<String> = match SerializedPatch::read(path, registry) {
+            Ok(patch) => patch
+                .validate(registry)
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            Err(e) => vec![e.to_string()],
+        };
+        if errors.is_empty() {
+            println!("{}: ok", path.display());
+        } else {
+            failures += 1;
+            for error in errors {
+                println!("{}: {}", path.display(), error);
+            }
+        }
+    }
+    if failures > 0 {
+        bail!("{} of {} patches are invalid", failures, paths.len());
+    }
+    Ok(())
+}
+
+fn info(registry: &mut ModuleRegistry, path: &Path) -> anyhow::Result<()> {
+    let patch = load(registry, path)?;
+    let names = module_names(registry, &patch);
+    println!("{} (version {})", path.display(), patch.version);
+
+    println!("Modules:");
+    for (i, module) in patch.modules.iter().enumerate() {
+        print!("  {} ({})", names[i], module.id);
+        if !module.state.is_active() {
+            print!(" [{:?}]", module.state);
+        }
+        if let Some(oversampling) = module.oversampling {
+            print!(" [{}x oversampling]", oversampling.factor());
+        }
+        println!();
+        let mut params: Vec<_> = module.params.iter().collect();
+        params.sort_by_key(|(name, _)| name.as_str());
+        for (name, value) in params {
+            println!("        {} = {}", name, format_param(value));
+        }
+    }
+
+    println!("Connections:");
+    let name = |i: usize| names.get(i).map_or("?", String::as_str);
+    for c in &patch.connections {
+        println!(
+            "  {}.out{} -> {}.in{}",
+            name(c.src_index),
+            c.src_channel,
+            name(c.dst_index),
+            c.dst_channel
+        );
+    }
+    Ok(())
+}
+
+fn graph(registry: &mut ModuleRegistry, path: &Path, mut out: impl Write) -> anyhow::Result<()> {
+    let patch = load(registry, path)?;
+    let names = module_names(registry, &patch);
+    writeln!(out, "digraph patch {{")?;
+    writeln!(out, "    rankdir=LR;")?;
+    writeln!(out, "    node [shape=box];")?;
+    for (i, name) in names.iter().enumerate() {
+        writeln!(out, "    m{} [label={:?}];", i, name)?;
+    }
+    for c in &patch.connections {
+        writeln!(
+            out,
+            "    m{} -> m{} [taillabel=\"{}\", headlabel=\"{}\"];",
+            c.src_index, c.dst_index, c.src_channel, c.dst_channel
+        )?;
+    }
+    writeln!(out, "}}")?;
+    Ok(())
+}
+
+fn render(
+    registry: &mut ModuleRegistry,
+    path: &Path,
+    output: &Path,
+    seconds: f32,
+    sample_rate: u32,
+) -> anyhow::Result<()> {
+    let patch = load(registry, path)?;
+    let (mut rack, _modules) = patch.build_rack(registry)?;
+    rack.reset(sample_rate as usize);
+    // Render through the same output stage as the audio device, so files sound the same.
+    let mut master = MasterBus::new(Arc::new(MasterSettings::default()), sample_rate as usize);
+
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let mut writer = hound::WavWriter::create(output, spec)?;
+    for _ in 0..(seconds * sample_rate as f32) as usize {
+        writer.write_sample(master.process(rack.tick()))?;
+    }
+    writer.finalize()?;
+    Ok(())
+}
+
+fn convert(
+    registry: &mut ModuleRegistry,
+    path: &Path,
+    output: Option<PathBuf>,
+) -> anyhow::Result<()> {
+    let patch = load(registry, path)?;
+    if path.extension().is_some_and(|e| e == dsl::EXTENSION) {
+        match output {
+            Some(output) => patch.save(output)?,
+            None => patch.to_writer(io::stdout().lock())?,
+        }
+    } else {
+        if let Some(error) = patch.validate(registry).into_iter().next() {
+            bail!("can't convert invalid patch: {}", error);
+        }
+        let text = dsl::print(&patch, registry)?;
+        match output {
+            Some(output) => std::fs::write(output, text)?,
+            None => print!("{}", text),
+        }
+    }
+    Ok(())
+}
+
+fn upgrade(registry: &mut ModuleRegistry, paths: &[PathBuf], check: bool) -> anyhow::Result<()> {
+    let mut outdated = 0;
+    for path in paths {
+        let patch = load(registry, path)?;
+        if patch.version < CURRENT_VERSION {
+            outdated += 1;
+            if check {
+                println!("{}: version {} is outdated", path.display(), patch.version);
+            } else {
+                patch.save(path)?;
+                println!(
+                    "{}: upgraded from version {}",
+                    path.display(),
+                    patch.version
+                );
+            }
+        }
+    }
+    if check && outdated > 0 {
+        bail!(
+            "{} patches need upgrading to version {}",
+            outdated,
+            CURRENT_VERSION
+        );
+    }
+    Ok(())
+}
+
+/// Labels each module index in the patch, including the audio output.
+fn module_names(registry: &ModuleRegistry, patch: &SerializedPatch) -> Vec<String> {
+    let manifests: HashMap<_, _> = registry
+        .all_modules()
+        .into_iter()
+        .map(|manifest| (manifest.id, manifest.name))
+        .collect();
+    patch
+        .modules
+        .iter()
+        .enumerate()
+        .map(|(i, module)| {
+            let name = manifests.get(&module.id).unwrap_or(&module.id);
+            format!("{}:{}", i, name)
+        })
+        .chain(Some("Audio Output".to_owned()))
+        .collect()
+}
+
+fn format_param(param: &SerializedParameter) -> String {
+    match param {
+        SerializedParameter::Num(value) => value.to_string(),
+        SerializedParameter::List(values) => {
+            let values: Vec<_> = values.iter().map(format_param).collect();
+            format!("[{}]", values.join(", "))
+        }
+    }
+}
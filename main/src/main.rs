@@ -1,10 +1,52 @@
+use std::path::PathBuf;
+
 use audio_host::AudioHost;
+use clap::Parser;
 use eframe::egui::vec2;
 use gui::ModularSynth;
+use module::registry::ModuleRegistry;
 use modules::builtin_modules;
 use rack::Rack;
 
+mod cli;
+
+/// A small software modular synthesizer. Without a command, opens the rack.
+#[derive(Parser)]
+#[command(name = "oxcable", version)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<cli::Command>,
+    /// Where to load plugins and WebAssembly modules from, instead of the `plugins` directory next
+    /// to the executable.
+    #[arg(long, global = true)]
+    plugins: Option<PathBuf>,
+}
+
 fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    let registry = load_modules(args.plugins);
+    match args.command {
+        Some(command) => command.run(registry),
+        None => run_gui(registry),
+    }
+}
+
+/// The builtin modules, along with those from any plugins and WebAssembly modules. Those that fail
+/// to load are reported and skipped.
+fn load_modules(plugins: Option<PathBuf>) -> ModuleRegistry {
+    let mut registry = builtin_modules();
+    if let Some(dir) = plugins.or_else(plugin_host::default_dir) {
+        for (path, error) in plugin_host::load_dir(&mut registry, &dir) {
+            eprintln!("Skipping plugin {}: {}", path.display(), error);
+        }
+        for (path, error) in wasm_host::load_dir(&mut registry, &dir) {
+            eprintln!("Skipping WebAssembly module {}: {}", path.display(), error);
+        }
+    }
+    registry
+}
+
+fn run_gui(registry: ModuleRegistry) -> anyhow::Result<()> {
     let window_options = eframe::NativeOptions {
         initial_window_size: Some(vec2(875.0, 540.0)),
         ..Default::default()
@@ -12,6 +54,6 @@ fn main() -> anyhow::Result<()> {
 
     let mut audio_host = AudioHost::default();
     audio_host.start(Rack::new())?;
-    let app = ModularSynth::new(builtin_modules(), audio_host);
+    let app = ModularSynth::new(registry, audio_host);
     eframe::run_native(Box::new(app), window_options);
 }
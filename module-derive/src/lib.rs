@@ -1,8 +1,12 @@
 use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
 use quote::quote;
-use syn::{parse_macro_input, Data, DataStruct, DeriveInput, Fields, Type};
+use syn::{
+    parse_macro_input, Attribute, Data, DataStruct, DeriveInput, Field, Fields, Lit, Meta,
+    NestedMeta, Type,
+};
 
-#[proc_macro_derive(Parameters)]
+#[proc_macro_derive(Parameters, attributes(param))]
 pub fn derive_parameter_set(input: TokenStream) -> TokenStream {
     // Parse the input token stream.
     let input = parse_macro_input!(input as DeriveInput);
@@ -45,6 +49,7 @@ pub fn derive_parameter_set(input: TokenStream) -> TokenStream {
             quote! { self.#field_name.deserialize(&params[stringify!(#field_name)]); }
         }
     });
+    let infos = fields.iter().map(parameter_info);
 
     // Generate the serialize/deserialize impls.
     TokenStream::from(quote! {
@@ -58,6 +63,147 @@ pub fn derive_parameter_set(input: TokenStream) -> TokenStream {
             fn deserialize(&self, params: &std::collections::HashMap<String, SerializedParameter>) {
                 #(#deserializers)*
             }
+            fn info(&self) -> Vec<module::ParameterInfo<'_>> {
+                let mut info = Vec::new();
+                #(#infos)*
+                info
+            }
         }
     })
 }
+
+/// Metadata given in a field's `#[param(...)]` attribute.
+#[derive(Default)]
+struct FieldMetadata {
+    label: Option<String>,
+    min: Option<f32>,
+    max: Option<f32>,
+    default: Option<f32>,
+    logarithmic: bool,
+    unit: Option<String>,
+    options: Vec<String>,
+}
+
+impl FieldMetadata {
+    fn parse(attrs: &[Attribute]) -> Self {
+        let mut metadata = FieldMetadata::default();
+        for attr in attrs.iter().filter(|a| a.path.is_ident("param")) {
+            let list = match attr.parse_meta() {
+                Ok(Meta::List(list)) => list,
+                _ => panic!("expected #[param(...)]"),
+            };
+            for nested in list.nested {
+                match nested {
+                    NestedMeta::Meta(Meta::Path(path)) if path.is_ident("log") => {
+                        metadata.logarithmic = true;
+                    }
+                    NestedMeta::Meta(Meta::NameValue(pair)) => {
+                        let key = pair.path.get_ident().map(ToString::to_string);
+                        match (key.as_deref(), &pair.lit) {
+                            (Some("label"), Lit::Str(s)) => metadata.label = Some(s.value()),
+                            (Some("unit"), Lit::Str(s)) => metadata.unit = Some(s.value()),
+                            (Some("min"), lit) => metadata.min = Some(number(lit)),
+                            (Some("max"), lit) => metadata.max = Some(number(lit)),
+                            (Some("default"), lit) => metadata.default = Some(number(lit)),
+                            _ => panic!("unknown parameter attribute"),
+                        }
+                    }
+                    NestedMeta::Meta(Meta::List(list)) if list.path.is_ident("options") => {
+                        for option in list.nested {
+                            match option {
+                                NestedMeta::Lit(Lit::Str(s)) => metadata.options.push(s.value()),
+                                _ => panic!("options must be strings"),
+                            }
+                        }
+                    }
+                    _ => panic!("unknown parameter attribute"),
+                }
+            }
+        }
+        metadata
+    }
+}
+
+fn number(lit: &Lit) -> f32 {
+    match lit {
+        Lit::Float(f) => f.base10_parse().unwrap(),
+        Lit::Int(i) => i.base10_parse().unwrap(),
+        _ => panic!("expected a number"),
+    }
+}
+
+/// Quotes a number. Literals can't be negative, so the sign is quoted separately.
+fn float(value: f32) -> TokenStream2 {
+    let magnitude = value.abs();
+    if value < 0.0 {
+        quote! { -#magnitude }
+    } else {
+        quote! { #magnitude }
+    }
+}
+
+/// Generates code pushing a field's `ParameterInfo` onto `info`, or one for each element of an
+/// array.
+fn parameter_info(field: &Field) -> TokenStream2 {
+    let field_name = &field.ident;
+    let metadata = FieldMetadata::parse(&field.attrs);
+    let label = metadata.label.unwrap_or_else(|| {
+        let name = field_name.as_ref().unwrap().to_string().replace('_', " ");
+        let mut chars = name.chars();
+        chars
+            .next()
+            .map(|c| c.to_uppercase().chain(chars).collect())
+            .unwrap_or_default()
+    });
+    let range = match (metadata.min, metadata.max) {
+        (Some(min), Some(max)) => {
+            let (min, max) = (float(min), float(max));
+            quote! { Some(#min..=#max) }
+        }
+        (None, None) => quote! { None },
+        _ => panic!("a parameter's range needs both a min and a max"),
+    };
+    let default = match metadata.default.map(float) {
+        Some(default) => quote! { Some(#default) },
+        None => quote! { None },
+    };
+    let logarithmic = metadata.logarithmic;
+    let unit = match metadata.unit {
+        Some(unit) => quote! { Some(#unit) },
+        None => quote! { None },
+    };
+    let options = metadata.options;
+    let info = |name: TokenStream2, label: TokenStream2, param: TokenStream2| {
+        quote! {
+            module::ParameterInfo {
+                name: #name,
+                label: #label,
+                param: module::parameters::AsParameterRef::as_parameter_ref(#param),
+                range: #range,
+                default: #default,
+                logarithmic: #logarithmic,
+                unit: #unit,
+                options: &[#(#options),*],
+            }
+        }
+    };
+    if let Type::Array(_) = &field.ty {
+        let info = info(
+            quote! { format!("{}[{}]", stringify!(#field_name), i) },
+            quote! { format!("{} {}", #label, i + 1) },
+            quote! { param },
+        );
+        quote! {
+            for (i, param) in self.#field_name.iter().enumerate() {
+                info.push(#info);
+            }
+        }
+    } else {
+        let info = info(
+            quote! { stringify!(#field_name).to_owned() },
+            quote! { #label.to_owned() },
+            quote! { &self.#field_name },
+        );
+        quote! { info.push(#info); }
+    }
+}
@@ -0,0 +1,393 @@
+//! Support for testing modules without an audio device.
+//!
+//! A [`Harness`] runs a single module's audio unit, feeding its inputs from scripted [`Input`]s and
+//! capturing its outputs. Captured [`Signal`]s can then be measured, compared with expectations, or
+//! pinned down against golden files.
+
+use std::{collections::HashMap, fs, path::Path};
+
+use eurorack::Voltage;
+use module::{
+    registry::{ModuleRegistry, RegistryError},
+    AudioUnit, Module, SerializedParameter,
+};
+
+/// The environment variable which causes golden files to be rewritten, rather than compared.
+pub const UPDATE_GOLDEN_VAR: &str = "UPDATE_GOLDEN";
+
+/// Drives a single module's audio unit.
+pub struct Harness {
+    module: Box<dyn Module>,
+    audio_unit: Box<dyn AudioUnit>,
+    sample_rate: usize,
+    elapsed: usize,
+    scripts: Vec<Input>,
+    inputs: Vec<Option<Voltage>>,
+    outputs: Vec<Voltage>,
+}
+
+impl Harness {
+    /// Creates a harness for `module`, running at `sample_rate` with its default oversampling.
+    pub fn new(module: Box<dyn Module>, sample_rate: usize) -> Self {
+        let mut audio_unit = module.default_oversampling().wrap(
+            module.create_audio_unit(),
+            module.inputs(),
+            module.outputs(),
+        );
+        audio_unit.reset(sample_rate);
+        Harness {
+            scripts: (0..module.inputs()).map(|_| Input::Disconnected).collect(),
+            inputs: vec![None; module.inputs()],
+            outputs: vec![0.0; module.outputs()],
+            module,
+            audio_unit,
+            sample_rate,
+            elapsed: 0,
+        }
+    }
+
+    /// Creates a harness for a module from the registry.
+    pub fn from_registry(
+        registry: &mut ModuleRegistry,
+        id: &str,
+        sample_rate: usize,
+    ) -> Result<Self, RegistryError> {
+        let (_, module) = registry.create_module(id)?;
+        Ok(Harness::new(module, sample_rate))
+    }
+
+    pub fn module(&self) -> &dyn Module {
+        self.module.as_ref()
+    }
+
+    /// Sets a single numeric parameter, by its serialized name.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the module has no parameter with this name.
+    pub fn set_param(&mut self, name: &str, value: f32) -> &mut Self {
+        self.set_serialized_param(name, SerializedParameter::Num(value))
+    }
+
+    /// Sets a single parameter, by its serialized name.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the module has no parameter with this name.
+    pub fn set_serialized_param(&mut self, name: &str, value: SerializedParameter) -> &mut Self {
+        let params = self.module.params().expect("module has no parameters");
+        let mut serialized: HashMap<String, SerializedParameter> = params.serialize();
+        assert!(
+            serialized.contains_key(name),
+            "module has no parameter named '{}'",
+            name
+        );
+        serialized.insert(name.to_owned(), value);
+        params.deserialize(&serialized);
+        self
+    }
+
+    /// Scripts the voltage on an input channel.
+    pub fn set_input(&mut self, channel: usize, input: Input) -> &mut Self {
+        self.scripts[channel] = input;
+        self
+    }
+
+    /// Resets the audio unit, restarting time from zero.
+    pub fn reset(&mut self) -> &mut Self {
+        self.audio_unit.reset(self.sample_rate);
+        self.elapsed = 0;
+        self
+    }
+
+    /// Runs the module for `seconds`, capturing its outputs.
+    pub fn run(&mut self, seconds: f32) -> Capture {
+        self.run_samples((seconds * self.sample_rate as f32).round() as usize)
+    }
+
+    /// Runs the module for a number of samples, capturing its outputs.
+    pub fn run_samples(&mut self, samples: usize) -> Capture {
+        let mut outputs = vec![Vec::with_capacity(samples); self.outputs.len()];
+        for _ in 0..samples {
+            let t = self.elapsed as f32 / self.sample_rate as f32;
+            for (input, script) in self.inputs.iter_mut().zip(self.scripts.iter_mut()) {
+                *input = script.sample(t);
+            }
+            self.audio_unit.tick(&self.inputs, &mut self.outputs);
+            for (capture, v) in outputs.iter_mut().zip(&self.outputs) {
+                capture.push(*v);
+            }
+            self.elapsed += 1;
+        }
+        Capture {
+            sample_rate: self.sample_rate,
+            outputs,
+        }
+    }
+}
+
+/// A scripted input voltage.
+pub enum Input {
+    Disconnected,
+    Constant(Voltage),
+    /// A voltage computed from the time since the harness was reset, in seconds.
+    Function(Box<dyn FnMut(f32) -> Voltage>),
+}
+
+impl Input {
+    pub fn function<F>(f: F) -> Self
+    where
+        F: 'static + FnMut(f32) -> Voltage,
+    {
+        Input::Function(Box::new(f))
+    }
+
+    /// A gate that is high from `start` for `length` seconds.
+    pub fn gate(start: f32, length: f32, volts: Voltage) -> Self {
+        Input::function(move |t| {
+            if t >= start && t < start + length {
+                volts
+            } else {
+                0.0
+            }
+        })
+    }
+
+    /// A train of short triggers, every `period` seconds starting from zero.
+    pub fn triggers(period: f32, volts: Voltage) -> Self {
+        Input::function(move |t| if t % period < 0.001 { volts } else { 0.0 })
+    }
+
+    fn sample(&mut self, t: f32) -> Option<Voltage> {
+        match self {
+            Input::Disconnected => None,
+            Input::Constant(v) => Some(*v),
+            Input::Function(f) => Some(f(t)),
+        }
+    }
+}
+
+/// The outputs captured from a run of the harness.
+pub struct Capture {
+    sample_rate: usize,
+    outputs: Vec<Vec<Voltage>>,
+}
+
+impl Capture {
+    pub fn output(&self, channel: usize) -> Signal<'_> {
+        Signal {
+            samples: &self.outputs[channel],
+            sample_rate: self.sample_rate,
+        }
+    }
+}
+
+/// A captured signal, with utilities for measuring it.
+#[derive(Copy, Clone, Debug)]
+pub struct Signal<'a> {
+    samples: &'a [Voltage],
+    sample_rate: usize,
+}
+
+impl<'a> Signal<'a> {
+    pub fn new(samples: &'a [Voltage], sample_rate: usize) -> Self {
+        Signal {
+            samples,
+            sample_rate,
+        }
+    }
+
+    pub fn samples(&self) -> &'a [Voltage] {
+        self.samples
+    }
+
+    pub fn sample_rate(&self) -> usize {
+        self.sample_rate
+    }
+
+    /// Drops the first `seconds` of the signal, e.g. to skip over transients.
+    pub fn skip(&self, seconds: f32) -> Self {
+        let n = ((seconds * self.sample_rate as f32) as usize).min(self.samples.len());
+        Signal::new(&self.samples[n..], self.sample_rate)
+    }
+
+    /// Keeps only the first `seconds` of the signal.
+    pub fn take(&self, seconds: f32) -> Self {
+        let n = ((seconds * self.sample_rate as f32) as usize).min(self.samples.len());
+        Signal::new(&self.samples[..n], self.sample_rate)
+    }
+
+    /// The value at a given time, in seconds.
+    pub fn at(&self, seconds: f32) -> Voltage {
+        self.samples[(seconds * self.sample_rate as f32) as usize]
+    }
+
+    pub fn dc_offset(&self) -> Voltage {
+        self.samples.iter().sum::<f32>() / self.samples.len() as f32
+    }
+
+    pub fn rms(&self) -> Voltage {
+        (self.samples.iter().map(|v| v * v).sum::<f32>() / self.samples.len() as f32).sqrt()
+    }
+
+    pub fn peak(&self) -> Voltage {
+        self.samples.iter().fold(0.0, |peak, v| v.abs().max(peak))
+    }
+
+    /// Estimates the fundamental frequency from the rising zero crossings around the DC offset.
+    pub fn frequency(&self) -> Option<f32> {
+        let offset = self.dc_offset();
+        let crossings = self.rising_edges(offset);
+        if crossings.len() < 2 {
+            return None;
+        }
+        let elapsed = crossings[crossings.len() - 1] - crossings[0];
+        Some((crossings.len() - 1) as f32 / elapsed)
+    }
+
+    /// The times, in seconds, at which the signal rises through `threshold`. Times are linearly
+    /// interpolated between samples.
+    pub fn rising_edges(&self, threshold: Voltage) -> Vec<f32> {
+        self.samples
+            .windows(2)
+            .enumerate()
+            .filter(|(_, w)| w[0] < threshold && w[1] >= threshold)
+            .map(|(i, w)| {
+                let fraction = (threshold - w[0]) / (w[1] - w[0]);
+                (i as f32 + fraction) / self.sample_rate as f32
+            })
+            .collect()
+    }
+
+    /// The time, in seconds, at which the signal first reaches `threshold` from below.
+    pub fn time_to_reach(&self, threshold: Voltage) -> Option<f32> {
+        self.samples
+            .iter()
+            .position(|v| *v >= threshold)
+            .map(|i| i as f32 / self.sample_rate as f32)
+    }
+
+    /// The time, in seconds, at which the signal first falls to `threshold` from above.
+    pub fn time_to_fall(&self, threshold: Voltage) -> Option<f32> {
+        self.samples
+            .iter()
+            .position(|v| *v <= threshold)
+            .map(|i| i as f32 / self.sample_rate as f32)
+    }
+}
+
+/// Asserts that `actual` is within `tolerance` of `expected`.
+#[track_caller]
+pub fn assert_near(what: &str, actual: f32, expected: f32, tolerance: f32) {
+    assert!(
+        (actual - expected).abs() <= tolerance,
+        "{}: expected {} ± {}, got {}",
+        what,
+        expected,
+        tolerance,
+        actual
+    );
+}
+
+/// Asserts that the signal's fundamental is within `cents` of `expected` Hz.
+#[track_caller]
+pub fn assert_frequency(signal: &Signal, expected: f32, cents: f32) {
+    let actual = signal.frequency().expect("signal has no zero crossings");
+    let error = 1200.0 * (actual / expected).log2();
+    assert!(
+        error.abs() <= cents,
+        "frequency: expected {} Hz ± {} cents, got {} Hz ({:+.2} cents)",
+        expected,
+        cents,
+        actual,
+        error
+    );
+}
+
+#[track_caller]
+pub fn assert_rms(signal: &Signal, expected: Voltage, tolerance: Voltage) {
+    assert_near("rms", signal.rms(), expected, tolerance);
+}
+
+#[track_caller]
+pub fn assert_dc_offset(signal: &Signal, expected: Voltage, tolerance: Voltage) {
+    assert_near("dc offset", signal.dc_offset(), expected, tolerance);
+}
+
+#[track_caller]
+pub fn assert_peak(signal: &Signal, expected: Voltage, tolerance: Voltage) {
+    assert_near("peak", signal.peak(), expected, tolerance);
+}
+
+/// Asserts that the signal triggers (rises through `threshold`) at the `expected` times, in
+/// seconds, each within `tolerance`.
+#[track_caller]
+pub fn assert_trigger_times(signal: &Signal, threshold: Voltage, expected: &[f32], tolerance: f32) {
+    let actual = signal.rising_edges(threshold);
+    assert_eq!(
+        actual.len(),
+        expected.len(),
+        "expected triggers at {:?}, got {:?}",
+        expected,
+        actual
+    );
+    for (a, e) in actual.iter().zip(expected) {
+        assert!(
+            (a - e).abs() <= tolerance,
+            "expected triggers at {:?} (± {}), got {:?}",
+            expected,
+            tolerance,
+            actual
+        );
+    }
+}
+
+/// Compares a signal with a golden file, holding one sample per line, within `tolerance`.
+///
+/// If the `UPDATE_GOLDEN` environment variable is set, the golden file is (re)written from the
+/// signal instead.
+#[track_caller]
+pub fn assert_golden<P: AsRef<Path>>(path: P, signal: &Signal, tolerance: Voltage) {
+    let path = path.as_ref();
+    if std::env::var_os(UPDATE_GOLDEN_VAR).is_some() {
+        let contents: String = signal
+            .samples
+            .iter()
+            .map(|v| format!("{:.6}\n", v))
+            .collect();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).unwrap();
+        }
+        fs::write(path, contents).unwrap();
+        return;
+    }
+
+    let contents = fs::read_to_string(path).unwrap_or_else(|e| {
+        panic!(
+            "couldn't read golden file {} ({}); run with {}=1 to create it",
+            path.display(),
+            e,
+            UPDATE_GOLDEN_VAR
+        )
+    });
+    let golden: Vec<f32> = contents
+        .lines()
+        .map(|line| line.trim().parse().unwrap())
+        .collect();
+    assert_eq!(
+        golden.len(),
+        signal.samples.len(),
+        "golden file {} has a different length",
+        path.display()
+    );
+    for (i, (actual, expected)) in signal.samples.iter().zip(&golden).enumerate() {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "sample {} differs from golden file {}: expected {}, got {}",
+            i,
+            path.display(),
+            expected,
+            actual
+        );
+    }
+}
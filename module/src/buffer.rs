@@ -0,0 +1,166 @@
+//! Real-time safe buffers for passing audio and state from an [`AudioUnit`](crate::AudioUnit) to
+//! its [`Panel`](crate::Panel).
+//!
+//! These are shared between the unit and the panel through an `Arc`, in the same way as
+//! parameters, but flow the other way.
+
+use std::sync::{
+    atomic::{fence, AtomicUsize, Ordering},
+    Mutex, PoisonError,
+};
+
+use eurorack::Voltage;
+use portable_atomic::AtomicF32;
+
+/// A fixed-size ring of multi-channel frames, written by the audio thread and read by the UI.
+///
+/// The writer never waits: once the ring is full, the oldest frames are overwritten. Readers ask
+/// for frames by their position in the stream of every frame ever pushed, and are told if those
+/// frames have already been overwritten.
+pub struct SampleRing {
+    channels: usize,
+    capacity: usize,
+    /// Room for `capacity` frames, plus one more for the writer to fill without disturbing any
+    /// of them.
+    samples: Box<[AtomicF32]>,
+    /// The number of frames pushed so far.
+    written: AtomicUsize,
+}
+
+impl SampleRing {
+    /// Creates a ring holding `capacity` frames of `channels` samples each.
+    pub fn new(channels: usize, capacity: usize) -> Self {
+        SampleRing {
+            channels,
+            capacity,
+            samples: (0..channels * (capacity + 1))
+                .map(|_| AtomicF32::new(0.0))
+                .collect(),
+            written: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// The number of frames kept before they are overwritten.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The number of frames pushed so far, which is also the position of the next frame.
+    pub fn written(&self) -> usize {
+        self.written.load(Ordering::Acquire)
+    }
+
+    /// Appends a frame, overwriting the oldest one if the ring is full. Missing channels are
+    /// written as silence.
+    ///
+    /// Only one thread may push to a ring.
+    pub fn push(&self, frame: &[Voltage]) {
+        let position = self.written.load(Ordering::Relaxed);
+        let start = (position % (self.capacity + 1)) * self.channels;
+        // Readers that see any of these samples must also see every frame counted before them,
+        // so that they know which frame is being overwritten.
+        fence(Ordering::Release);
+        for channel in 0..self.channels {
+            let v = frame.get(channel).copied().unwrap_or(0.0);
+            self.samples[start + channel].store(v, Ordering::Relaxed);
+        }
+        self.written.store(position + 1, Ordering::Release);
+    }
+
+    /// Copies out `frames` frames starting at position `start`, interleaved by channel. Returns
+    /// `None` if any of them haven't been pushed yet, or have already been overwritten.
+    pub fn read(&self, start: usize, frames: usize) -> Option<Vec<Voltage>> {
+        let written = self.written();
+        if frames > self.capacity || start + frames > written || start + self.capacity < written {
+            return None;
+        }
+        let mut out = Vec::with_capacity(frames * self.channels);
+        for position in start..start + frames {
+            let offset = (position % (self.capacity + 1)) * self.channels;
+            out.extend(
+                self.samples[offset..offset + self.channels]
+                    .iter()
+                    .map(|s| s.load(Ordering::Relaxed)),
+            );
+        }
+        // The writer may have lapped us while we were copying. It overwrites a frame before
+        // counting it as written, which is why it has a spare slot: the frame it's overwriting is
+        // never one of the last `capacity` it has counted. The fence keeps the copy from being
+        // reordered after this check.
+        fence(Ordering::Acquire);
+        if start + self.capacity < self.written.load(Ordering::Relaxed) {
+            return None;
+        }
+        Some(out)
+    }
+}
+
+/// Set in [`TripleBuffer::latest`] when the reader hasn't yet seen the slot it names.
+const FRESH: usize = 4;
+
+/// Holds the latest value published by an audio unit, such as the step a sequencer is playing,
+/// for its panel to read.
+///
+/// Values are passed through three slots: one being written, one being read, and the latest
+/// complete value between them. The writer and reader never use the same slot, so publishing
+/// never waits on the reader, and the reader always sees a whole value.
+pub struct TripleBuffer<T> {
+    slots: [Mutex<T>; 3],
+    /// The slot holding the latest value, along with [`FRESH`] if it hasn't been read.
+    latest: AtomicUsize,
+    /// The slot the writer will use next.
+    writing: AtomicUsize,
+    /// The slot the reader last took.
+    reading: AtomicUsize,
+}
+
+impl<T: Clone> TripleBuffer<T> {
+    pub fn new(initial: T) -> Self {
+        TripleBuffer {
+            slots: [
+                Mutex::new(initial.clone()),
+                Mutex::new(initial.clone()),
+                Mutex::new(initial),
+            ],
+            latest: AtomicUsize::new(0),
+            writing: AtomicUsize::new(1),
+            reading: AtomicUsize::new(2),
+        }
+    }
+
+    /// Makes a value the latest one. Only one thread may publish to a buffer, and values should
+    /// be cheap to drop, as the audio thread drops the value they replace.
+    pub fn publish(&self, value: T) {
+        let writing = self.writing.load(Ordering::Relaxed);
+        // The slot is never shared with the reader, so this only fails if two threads publish at
+        // once, in which case the value is dropped rather than waiting.
+        if let Ok(mut slot) = self.slots[writing].try_lock() {
+            *slot = value;
+        } else {
+            return;
+        }
+        let previous = self.latest.swap(writing | FRESH, Ordering::AcqRel);
+        self.writing.store(previous & !FRESH, Ordering::Relaxed);
+    }
+
+    /// The latest published value. Only one thread may read from a buffer.
+    pub fn read(&self) -> T {
+        if self.latest.load(Ordering::Relaxed) & FRESH != 0 {
+            let reading = self.reading.load(Ordering::Relaxed);
+            let latest = self.latest.swap(reading, Ordering::AcqRel);
+            self.reading.store(latest & !FRESH, Ordering::Relaxed);
+        }
+        let slot = &self.slots[self.reading.load(Ordering::Relaxed)];
+        slot.lock().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+}
+
+impl<T: Clone + Default> Default for TripleBuffer<T> {
+    fn default() -> Self {
+        TripleBuffer::new(T::default())
+    }
+}
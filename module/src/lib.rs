@@ -9,15 +9,25 @@ use std::{
 
 use eurorack::Voltage;
 
+pub mod buffer;
+pub mod oversampling;
 pub mod parameters;
 pub mod registry;
 
 pub use module_derive::Parameters;
-pub use parameters::{Parameter, SerializedParameter};
+pub use oversampling::Oversampling;
+pub use parameters::{Parameter, ParameterInfo, ParameterRef, SerializedParameter};
 
 pub trait AudioUnit: Send {
     fn reset(&mut self, sample_rate: usize);
     fn tick(&mut self, inputs: &[Option<Voltage>], outputs: &mut [Voltage]);
+
+    /// Reports a fault that the unit caught itself during the last reset or tick, such as a panic
+    /// that couldn't unwind out of a plugin. The rack checks this after each one, and treats the
+    /// fault as if the unit had panicked there.
+    fn take_fault(&mut self) -> Option<ModuleFault> {
+        None
+    }
 }
 
 pub trait Panel {
@@ -28,6 +38,11 @@ pub trait Panel {
 pub trait Parameters {
     fn serialize(&self) -> HashMap<String, SerializedParameter>;
     fn deserialize(&self, params: &HashMap<String, SerializedParameter>);
+
+    /// Describes each parameter, for panels that are laid out automatically.
+    fn info(&self) -> Vec<ParameterInfo<'_>> {
+        Vec::new()
+    }
 }
 
 pub trait Module {
@@ -36,10 +51,71 @@ pub trait Module {
 
     fn params(&self) -> Option<&dyn Parameters>;
 
+    /// Names for each input channel, used to refer to them in text patches. Channels without a
+    /// name can only be referred to by number.
+    fn input_names(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Names for each output channel, as for [`Module::input_names`].
+    fn output_names(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Pairs of (input, output) channels that are connected directly while the module is bypassed.
+    /// Any other outputs are silenced.
+    fn bypass_routes(&self) -> Vec<(usize, usize)> {
+        Vec::new()
+    }
+
+    /// The oversampling used for new instances of this module, unless the user picks another.
+    fn default_oversampling(&self) -> Oversampling {
+        Oversampling::None
+    }
+
     fn create_audio_unit(&self) -> Box<dyn AudioUnit>;
     fn create_panel(&self) -> Box<dyn Panel>;
 }
 
+/// Controls whether a module's audio unit is processed.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModuleState {
+    #[default]
+    Active,
+    /// The unit is not ticked, and its bypass routes pass inputs straight through.
+    Bypassed,
+    /// The unit is not ticked, and all outputs are silenced.
+    Muted,
+}
+
+impl ModuleState {
+    pub fn is_active(&self) -> bool {
+        *self == ModuleState::Active
+    }
+}
+
+/// Describes why a module was isolated from the rest of the rack.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ModuleFault {
+    /// The module panicked, and has been muted until it is reset.
+    Panicked,
+    /// The module produced NaN or infinite voltages, which were replaced with silence.
+    NonFinite,
+}
+
+/// A summary of the signal on a jack over a short window, for display.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct SignalLevel {
+    /// The most recent voltage.
+    pub value: Voltage,
+    /// The largest absolute voltage.
+    pub peak: Voltage,
+    pub rms: Voltage,
+    /// The average voltage, which shows whether a signal is unipolar.
+    pub mean: Voltage,
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct ModuleHandle(pub usize);
 
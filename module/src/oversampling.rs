@@ -0,0 +1,274 @@
+use std::{convert::TryFrom, f32::consts::PI};
+
+use eurorack::Voltage;
+
+use crate::{AudioUnit, ModuleFault};
+
+/// The number of taps in each halfband filter. Halfband filters must have `4k + 3` taps, so that
+/// the outermost taps are non-zero.
+const HALFBAND_TAPS: usize = 63;
+
+/// The number of non-zero taps away from the center of each halfband filter.
+const POLYPHASE_TAPS: usize = HALFBAND_TAPS.div_ceil(2);
+
+/// The delay of the pure-delay polyphase branch, in low-rate samples.
+const CENTER_DELAY: usize = (HALFBAND_TAPS - 3) / 4;
+
+/// How many times faster than the host sample rate an audio unit is run.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "usize", into = "usize")]
+pub enum Oversampling {
+    #[default]
+    None,
+    X2,
+    X4,
+    X8,
+}
+
+impl Oversampling {
+    pub const ALL: [Oversampling; 4] = [
+        Oversampling::None,
+        Oversampling::X2,
+        Oversampling::X4,
+        Oversampling::X8,
+    ];
+
+    pub fn factor(&self) -> usize {
+        1 << self.stages()
+    }
+
+    pub fn is_none(&self) -> bool {
+        *self == Oversampling::None
+    }
+
+    /// Wraps an audio unit so that it runs at this oversampling rate. The unit is returned as is
+    /// if no oversampling is needed.
+    pub fn wrap(
+        &self,
+        audio_unit: Box<dyn AudioUnit>,
+        inputs: usize,
+        outputs: usize,
+    ) -> Box<dyn AudioUnit> {
+        if self.is_none() {
+            audio_unit
+        } else {
+            Box::new(Oversampler::new(audio_unit, *self, inputs, outputs))
+        }
+    }
+
+    fn stages(&self) -> usize {
+        match self {
+            Oversampling::None => 0,
+            Oversampling::X2 => 1,
+            Oversampling::X4 => 2,
+            Oversampling::X8 => 3,
+        }
+    }
+}
+
+impl From<Oversampling> for usize {
+    fn from(oversampling: Oversampling) -> usize {
+        oversampling.factor()
+    }
+}
+
+impl TryFrom<usize> for Oversampling {
+    type Error = String;
+
+    fn try_from(factor: usize) -> Result<Self, Self::Error> {
+        Oversampling::ALL
+            .into_iter()
+            .find(|o| o.factor() == factor)
+            .ok_or_else(|| format!("unsupported oversampling factor {}", factor))
+    }
+}
+
+/// Runs an audio unit at a multiple of the host sample rate.
+///
+/// Inputs are upsampled, and outputs downsampled, through a cascade of 2x halfband filters. This
+/// adds latency, which grows with each stage: 31 samples at 2x, up to about 54 at 8x.
+pub struct Oversampler {
+    audio_unit: Box<dyn AudioUnit>,
+    factor: usize,
+    // Indexed by [channel][stage]:
+    interpolators: Vec<Vec<HalfbandFilter>>,
+    decimators: Vec<Vec<HalfbandFilter>>,
+    // Indexed by [channel][sample]:
+    input_buffers: Vec<Vec<Voltage>>,
+    output_buffers: Vec<Vec<Voltage>>,
+    scratch: Vec<Voltage>,
+    inputs: Vec<Option<Voltage>>,
+    outputs: Vec<Voltage>,
+}
+
+impl Oversampler {
+    pub fn new(
+        audio_unit: Box<dyn AudioUnit>,
+        oversampling: Oversampling,
+        inputs: usize,
+        outputs: usize,
+    ) -> Self {
+        let taps = halfband_taps();
+        let filters = |channels| {
+            (0..channels)
+                .map(|_| {
+                    (0..oversampling.stages())
+                        .map(|_| HalfbandFilter::new(&taps))
+                        .collect()
+                })
+                .collect()
+        };
+        let factor = oversampling.factor();
+        Oversampler {
+            audio_unit,
+            factor,
+            interpolators: filters(inputs),
+            decimators: filters(outputs),
+            input_buffers: vec![vec![0.0; factor]; inputs],
+            output_buffers: vec![vec![0.0; factor]; outputs],
+            scratch: vec![0.0; factor],
+            inputs: vec![None; inputs],
+            outputs: vec![0.0; outputs],
+        }
+    }
+}
+
+impl AudioUnit for Oversampler {
+    fn reset(&mut self, sample_rate: usize) {
+        for filter in self
+            .interpolators
+            .iter_mut()
+            .chain(self.decimators.iter_mut())
+            .flatten()
+        {
+            filter.clear();
+        }
+        self.audio_unit.reset(self.factor * sample_rate);
+    }
+
+    fn take_fault(&mut self) -> Option<ModuleFault> {
+        self.audio_unit.take_fault()
+    }
+
+    fn tick(&mut self, inputs: &[Option<Voltage>], outputs: &mut [Voltage]) {
+        // Upsample each connected input, doubling the number of samples in its buffer at each
+        // stage.
+        for (channel, input) in inputs.iter().enumerate() {
+            if let Some(v) = input {
+                let buffer = &mut self.input_buffers[channel];
+                buffer[0] = *v;
+                for (stage, filter) in self.interpolators[channel].iter_mut().enumerate() {
+                    let len = 1 << stage;
+                    self.scratch[..len].copy_from_slice(&buffer[..len]);
+                    for i in 0..len {
+                        let (even, odd) = filter.interpolate(self.scratch[i]);
+                        buffer[2 * i] = even;
+                        buffer[2 * i + 1] = odd;
+                    }
+                }
+            }
+        }
+
+        // Run the audio unit at the higher rate.
+        for i in 0..self.factor {
+            for (channel, input) in inputs.iter().enumerate() {
+                self.inputs[channel] = input.map(|_| self.input_buffers[channel][i]);
+            }
+            self.audio_unit.tick(&self.inputs, &mut self.outputs);
+            for (channel, output) in self.outputs.iter().enumerate() {
+                self.output_buffers[channel][i] = *output;
+            }
+        }
+
+        // Downsample each output back to the host rate, halving the buffer at each stage.
+        for (channel, output) in outputs.iter_mut().enumerate() {
+            let buffer = &mut self.output_buffers[channel];
+            let stages = self.decimators[channel].len();
+            for (stage, filter) in self.decimators[channel].iter_mut().enumerate() {
+                for i in 0..1 << (stages - stage - 1) {
+                    buffer[i] = filter.decimate(buffer[2 * i], buffer[2 * i + 1]);
+                }
+            }
+            *output = buffer[0];
+        }
+    }
+}
+
+/// A polyphase halfband lowpass, which can either interpolate or decimate by 2.
+///
+/// Every other tap of a halfband filter is zero, except the center tap. Each polyphase branch
+/// therefore reduces to either a short FIR or a pure delay.
+struct HalfbandFilter {
+    taps: Vec<f32>,
+    fir_history: Vec<f32>,
+    delay_history: Vec<f32>,
+}
+
+impl HalfbandFilter {
+    fn new(taps: &[f32]) -> Self {
+        HalfbandFilter {
+            taps: taps.to_vec(),
+            fir_history: vec![0.0; POLYPHASE_TAPS],
+            delay_history: vec![0.0; CENTER_DELAY + 1],
+        }
+    }
+
+    fn clear(&mut self) {
+        self.fir_history.fill(0.0);
+        self.delay_history.fill(0.0);
+    }
+
+    /// Consumes one low-rate sample, producing two high-rate samples.
+    fn interpolate(&mut self, x: f32) -> (f32, f32) {
+        push(&mut self.fir_history, x);
+        push(&mut self.delay_history, x);
+        // Zero-stuffing halves the amplitude of the signal, which we make up with a gain of 2.
+        let even = 2.0 * self.fir();
+        let odd = self.delay_history[CENTER_DELAY];
+        (even, odd)
+    }
+
+    /// Consumes two high-rate samples, producing one low-rate sample.
+    fn decimate(&mut self, even: f32, odd: f32) -> f32 {
+        push(&mut self.fir_history, even);
+        let y = self.fir() + 0.5 * self.delay_history[CENTER_DELAY];
+        push(&mut self.delay_history, odd);
+        y
+    }
+
+    fn fir(&self) -> f32 {
+        self.taps
+            .iter()
+            .zip(&self.fir_history)
+            .map(|(h, x)| h * x)
+            .sum()
+    }
+}
+
+/// Pushes a new sample to the front of a history buffer, dropping the oldest.
+fn push(history: &mut [f32], x: f32) {
+    history.copy_within(..history.len() - 1, 1);
+    history[0] = x;
+}
+
+/// Computes the non-zero, off-center taps of a Blackman windowed-sinc halfband filter.
+fn halfband_taps() -> Vec<f32> {
+    let center = (HALFBAND_TAPS - 1) as f32 / 2.0;
+    let mut taps: Vec<f32> = (0..POLYPHASE_TAPS)
+        .map(|j| {
+            let n = (2 * j) as f32;
+            let t = (n - center) / 2.0;
+            let sinc = (PI * t).sin() / (PI * t);
+            let phase = 2.0 * PI * n / (HALFBAND_TAPS - 1) as f32;
+            let window = 0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos();
+            0.5 * sinc * window
+        })
+        .collect();
+
+    // The center tap contributes half of the DC gain, so these must contribute the other half.
+    let sum: f32 = taps.iter().sum();
+    for tap in taps.iter_mut() {
+        *tap *= 0.5 / sum;
+    }
+    taps
+}
@@ -1,8 +1,15 @@
-use std::sync::atomic::{AtomicU8, Ordering};
+use std::{
+    collections::HashMap,
+    ops::RangeInclusive,
+    sync::atomic::{AtomicBool, AtomicU8, Ordering},
+};
 
 use eurorack::utils::Duration;
+use portable_atomic::AtomicF32;
 
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+use crate::Parameters;
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 #[serde(untagged)]
 pub enum SerializedParameter {
     Num(f32),
@@ -42,7 +49,23 @@ impl Parameter for AtomicU8 {
     }
 }
 
-impl Parameter for portable_atomic::AtomicF32 {
+impl Parameter for AtomicBool {
+    type Value = bool;
+    fn read(&self) -> Self::Value {
+        self.load(Ordering::Relaxed)
+    }
+    fn write(&self, value: Self::Value) {
+        self.store(value, Ordering::Relaxed)
+    }
+    fn serialize(&self) -> SerializedParameter {
+        SerializedParameter::Num(self.read() as u8 as f32)
+    }
+    fn deserialize(&self, serialized: &SerializedParameter) {
+        self.write(serialized.as_num() != 0.0);
+    }
+}
+
+impl Parameter for AtomicF32 {
     type Value = f32;
     fn read(&self) -> Self::Value {
         self.load(Ordering::Relaxed)
@@ -73,3 +96,155 @@ impl Parameter for Duration {
         self.write(serialized.as_num());
     }
 }
+
+/// A parameter, typed by the kind of control that suits it.
+#[derive(Copy, Clone)]
+pub enum ParameterRef<'a> {
+    /// A continuous value, for a knob or fader.
+    Float(&'a dyn Parameter<Value = f32>),
+    /// One of a set of options, or a small whole number.
+    Choice(&'a dyn Parameter<Value = u8>),
+    /// An on/off value, for a switch.
+    Switch(&'a dyn Parameter<Value = bool>),
+}
+
+/// Implemented by each type of parameter, so that [`Parameters`](crate::Parameters) can be derived
+/// with metadata.
+pub trait AsParameterRef {
+    fn as_parameter_ref(&self) -> ParameterRef<'_>;
+}
+
+impl AsParameterRef for AtomicF32 {
+    fn as_parameter_ref(&self) -> ParameterRef<'_> {
+        ParameterRef::Float(self)
+    }
+}
+
+impl AsParameterRef for Duration {
+    fn as_parameter_ref(&self) -> ParameterRef<'_> {
+        ParameterRef::Float(self)
+    }
+}
+
+impl AsParameterRef for AtomicU8 {
+    fn as_parameter_ref(&self) -> ParameterRef<'_> {
+        ParameterRef::Choice(self)
+    }
+}
+
+impl AsParameterRef for AtomicBool {
+    fn as_parameter_ref(&self) -> ParameterRef<'_> {
+        ParameterRef::Switch(self)
+    }
+}
+
+/// Describes a parameter for panels that are laid out automatically.
+///
+/// When deriving [`Parameters`](crate::Parameters), these are given with a `#[param(...)]`
+/// attribute on each field, such as
+/// `#[param(label = "Cutoff", min = 20.0, max = 20000.0, default = 1000.0, log, unit = "Hz")]`,
+/// or `#[param(options("Sine", "Square"))]` for a choice.
+#[derive(Clone)]
+pub struct ParameterInfo<'a> {
+    /// The parameter's name. An array is serialized as one list under the field's name, but each
+    /// element is described separately, named `name[i]`.
+    pub name: String,
+    pub label: String,
+    pub param: ParameterRef<'a>,
+    pub range: Option<RangeInclusive<f32>>,
+    pub default: Option<f32>,
+    /// Whether the range is spread logarithmically.
+    pub logarithmic: bool,
+    pub unit: Option<&'static str>,
+    /// Names for each value of a choice, starting from 0.
+    pub options: &'static [&'static str],
+}
+
+/// A parameter of a [`ParameterSet`].
+pub struct NamedParameter {
+    pub name: String,
+    pub label: String,
+    pub range: RangeInclusive<f32>,
+    pub default: f32,
+    pub value: AtomicF32,
+}
+
+impl NamedParameter {
+    pub fn new(name: &str, label: &str, range: RangeInclusive<f32>, default: f32) -> Self {
+        NamedParameter {
+            name: name.to_owned(),
+            label: label.to_owned(),
+            range,
+            default,
+            value: AtomicF32::new(default),
+        }
+    }
+}
+
+/// Continuous parameters that are only known at runtime, for modules that aren't written in
+/// Rust, and so can't derive [`Parameters`].
+///
+/// Unlike derived parameters, values missing from a saved patch are left as they are, so that
+/// a module can gain parameters without breaking older patches.
+#[derive(Default)]
+pub struct ParameterSet {
+    params: Vec<NamedParameter>,
+}
+
+impl ParameterSet {
+    pub fn new(params: Vec<NamedParameter>) -> Self {
+        ParameterSet { params }
+    }
+
+    pub fn len(&self) -> usize {
+        self.params.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &NamedParameter> {
+        self.params.iter()
+    }
+
+    /// Copies every parameter's current value into `values`, which must be as long as the set.
+    pub fn read_into(&self, values: &mut [f32]) {
+        for (value, param) in values.iter_mut().zip(&self.params) {
+            *value = param.value.read();
+        }
+    }
+}
+
+impl Parameters for ParameterSet {
+    fn serialize(&self) -> HashMap<String, SerializedParameter> {
+        self.params
+            .iter()
+            .map(|p| (p.name.clone(), p.value.serialize()))
+            .collect()
+    }
+
+    fn deserialize(&self, params: &HashMap<String, SerializedParameter>) {
+        for param in &self.params {
+            if let Some(SerializedParameter::Num(value)) = params.get(&param.name) {
+                param.value.write(*value);
+            }
+        }
+    }
+
+    fn info(&self) -> Vec<ParameterInfo<'_>> {
+        self.params
+            .iter()
+            .map(|p| ParameterInfo {
+                name: p.name.clone(),
+                label: p.label.clone(),
+                param: ParameterRef::Float(&p.value),
+                range: Some(p.range.clone()),
+                default: Some(p.default),
+                logarithmic: false,
+                unit: None,
+                options: &[],
+            })
+            .collect()
+    }
+}
@@ -6,6 +6,108 @@ use crate::{Module, ModuleHandle};
 pub struct ModuleManifest {
     pub id: String,
     pub name: String,
+    pub category: Category,
+    /// Extra words to find the module by when searching.
+    pub tags: Vec<String>,
+    pub author: String,
+    pub description: String,
+    /// The width of the module's panel, in horizontal pitch units. Modules registered with
+    /// [`ModuleRegistry::register`] take this from their panel.
+    pub hp: usize,
+}
+
+impl ModuleManifest {
+    pub fn new(id: &str, name: &str) -> Self {
+        ModuleManifest {
+            id: id.to_owned(),
+            name: name.to_owned(),
+            category: Category::Utility,
+            tags: Vec::new(),
+            author: String::new(),
+            description: String::new(),
+            hp: 4,
+        }
+    }
+
+    pub fn category(mut self, category: Category) -> Self {
+        self.category = category;
+        self
+    }
+
+    pub fn tags(mut self, tags: &[&str]) -> Self {
+        self.tags = tags.iter().map(|&tag| tag.to_owned()).collect();
+        self
+    }
+
+    pub fn author(mut self, author: &str) -> Self {
+        self.author = author.to_owned();
+        self
+    }
+
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = description.to_owned();
+        self
+    }
+
+    pub fn hp(mut self, hp: usize) -> Self {
+        self.hp = hp;
+        self
+    }
+
+    /// Whether the module matches a search query. Every word of the query must appear in the
+    /// module's name, category, tags or description, ignoring case.
+    pub fn matches(&self, query: &str) -> bool {
+        let text = format!(
+            "{} {} {} {}",
+            self.name,
+            self.category.name(),
+            self.tags.join(" "),
+            self.description
+        )
+        .to_lowercase();
+        query
+            .to_lowercase()
+            .split_whitespace()
+            .all(|word| text.contains(word))
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Category {
+    Oscillator,
+    Filter,
+    Amplifier,
+    Envelope,
+    Modulation,
+    Sequencing,
+    InputOutput,
+    Utility,
+}
+
+impl Category {
+    pub const ALL: [Category; 8] = [
+        Category::Oscillator,
+        Category::Filter,
+        Category::Amplifier,
+        Category::Envelope,
+        Category::Modulation,
+        Category::Sequencing,
+        Category::InputOutput,
+        Category::Utility,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Category::Oscillator => "Oscillator",
+            Category::Filter => "Filter",
+            Category::Amplifier => "Amplifier",
+            Category::Envelope => "Envelope",
+            Category::Modulation => "Modulation",
+            Category::Sequencing => "Sequencing",
+            Category::InputOutput => "Input/Output",
+            Category::Utility => "Utility",
+        }
+    }
 }
 
 #[derive(Default)]
@@ -19,20 +121,45 @@ impl ModuleRegistry {
         self.modules.values().map(|e| e.manifest.clone()).collect()
     }
 
-    pub fn register<M>(&mut self, id: &str, name: &str)
+    /// # Panics
+    ///
+    /// Panics if a module with the same id is already registered.
+    pub fn register<M>(&mut self, mut manifest: ModuleManifest)
     where
         M: 'static + Module + Default,
     {
+        manifest.hp = M::default().create_panel().width();
+        if let Err(e) = self.register_factory(manifest, || Box::new(M::default())) {
+            panic!("{}", e);
+        }
+    }
+
+    /// Registers a module that is created by a function rather than from a Rust type, such as
+    /// one loaded at runtime. Ids can't be registered twice, so that loading a module can never
+    /// change what a saved patch refers to.
+    pub fn register_factory<F>(
+        &mut self,
+        manifest: ModuleManifest,
+        factory: F,
+    ) -> Result<(), RegistryError>
+    where
+        F: 'static + Fn() -> Box<dyn Module>,
+    {
+        if self.modules.contains_key(&manifest.id) {
+            return Err(RegistryError::AlreadyRegistered(manifest.id));
+        }
         self.modules.insert(
-            id.to_owned(),
+            manifest.id.clone(),
             RegisteredModule {
-                manifest: ModuleManifest {
-                    id: id.to_owned(),
-                    name: name.to_owned(),
-                },
-                factory: Box::new(|| Box::new(M::default())),
+                manifest,
+                factory: Box::new(factory),
             },
         );
+        Ok(())
+    }
+
+    pub fn manifest(&self, id: &str) -> Option<&ModuleManifest> {
+        self.modules.get(id).map(|entry| &entry.manifest)
     }
 
     pub fn create_module(
@@ -58,4 +185,6 @@ struct RegisteredModule {
 pub enum RegistryError {
     #[error("no module with id '{0}' exists")]
     NotRegistered(String),
+    #[error("a module with id '{0}' is already registered")]
+    AlreadyRegistered(String),
 }
@@ -0,0 +1,100 @@
+use std::{sync::Arc, thread};
+
+use module::buffer::{SampleRing, TripleBuffer};
+
+#[test]
+fn ring_reads_across_wraparound() {
+    let ring = SampleRing::new(2, 4);
+    for i in 0..6 {
+        ring.push(&[i as f32, -(i as f32)]);
+    }
+    assert_eq!(ring.written(), 6);
+    assert_eq!(ring.read(3, 3), Some(vec![3.0, -3.0, 4.0, -4.0, 5.0, -5.0]));
+}
+
+#[test]
+fn ring_fills_missing_channels_with_silence() {
+    let ring = SampleRing::new(3, 4);
+    ring.push(&[1.0]);
+    assert_eq!(ring.read(0, 1), Some(vec![1.0, 0.0, 0.0]));
+}
+
+#[test]
+fn ring_refuses_overwritten_frames() {
+    let ring = SampleRing::new(1, 4);
+    for i in 0..10 {
+        ring.push(&[i as f32]);
+    }
+    // Only the last four frames are kept.
+    assert_eq!(ring.read(5, 2), None);
+    assert_eq!(ring.read(6, 4), Some(vec![6.0, 7.0, 8.0, 9.0]));
+    assert_eq!(ring.read(0, 1), None);
+}
+
+#[test]
+fn ring_refuses_unwritten_frames() {
+    let ring = SampleRing::new(1, 4);
+    ring.push(&[1.0]);
+    ring.push(&[2.0]);
+    assert_eq!(ring.read(0, 3), None);
+    assert_eq!(ring.read(2, 1), None);
+    assert_eq!(ring.read(0, 5), None);
+    assert_eq!(ring.read(1, 1), Some(vec![2.0]));
+    assert_eq!(ring.read(2, 0), Some(Vec::new()));
+}
+
+#[test]
+fn ring_never_returns_torn_frames_while_being_written() {
+    const FRAMES: usize = 200_000;
+    let ring = Arc::new(SampleRing::new(4, 64));
+    let writer = {
+        let ring = ring.clone();
+        thread::spawn(move || {
+            for i in 0..FRAMES {
+                ring.push(&[i as f32; 4]);
+            }
+        })
+    };
+
+    // Read the latest frames as fast as possible. Every frame that is returned must be whole, and
+    // be the one that was asked for.
+    let mut reads = 0;
+    while !writer.is_finished() || reads == 0 {
+        let written = ring.written();
+        let start = written.saturating_sub(ring.capacity());
+        if let Some(samples) = ring.read(start, written - start) {
+            for (i, frame) in samples.chunks(4).enumerate() {
+                assert_eq!(frame, [(start + i) as f32; 4], "frame {}", start + i);
+            }
+            reads += 1;
+        }
+    }
+    writer.join().unwrap();
+    assert_eq!(ring.written(), FRAMES);
+}
+
+#[test]
+fn triple_buffer_reads_latest_value() {
+    let buffer = TripleBuffer::new(0);
+    assert_eq!(buffer.read(), 0);
+    buffer.publish(1);
+    assert_eq!(buffer.read(), 1);
+    // The value stays until a new one is published.
+    assert_eq!(buffer.read(), 1);
+    buffer.publish(2);
+    buffer.publish(3);
+    assert_eq!(buffer.read(), 3);
+}
+
+#[test]
+fn triple_buffer_keeps_publishing_without_reads() {
+    let buffer = TripleBuffer::new(Vec::new());
+    for i in 0..100 {
+        buffer.publish(vec![i; 3]);
+        if i % 7 == 0 {
+            assert_eq!(buffer.read(), [i; 3]);
+        }
+    }
+    assert_eq!(buffer.read(), [99; 3]);
+    assert_eq!(buffer.read(), [99; 3]);
+}
@@ -0,0 +1,153 @@
+use std::f32::consts::PI;
+
+use eurorack::Voltage;
+use module::*;
+use module_test::{assert_near, Harness, Input};
+
+const SAMPLE_RATE: usize = 48_000;
+
+/// Oversamples an audio unit, which either passes its input through or plays a tone at the
+/// oversampled rate.
+struct Oversampled {
+    oversampling: Oversampling,
+    tone: Option<f32>,
+}
+
+impl Module for Oversampled {
+    fn inputs(&self) -> usize {
+        1
+    }
+
+    fn outputs(&self) -> usize {
+        1
+    }
+
+    fn params(&self) -> Option<&dyn Parameters> {
+        None
+    }
+
+    fn default_oversampling(&self) -> Oversampling {
+        self.oversampling
+    }
+
+    fn create_audio_unit(&self) -> Box<dyn AudioUnit> {
+        Box::new(Unit {
+            tone: self.tone,
+            sample_rate: 0.0,
+            elapsed: 0,
+        })
+    }
+
+    fn create_panel(&self) -> Box<dyn Panel> {
+        Box::new(EmptyPanel)
+    }
+}
+
+/// The tests never draw the module, so it has nothing to show.
+struct EmptyPanel;
+
+impl Panel for EmptyPanel {
+    fn width(&self) -> usize {
+        4
+    }
+
+    fn update(&mut self, _handle: &ModuleHandle, _ui: &mut egui::Ui) {}
+}
+
+struct Unit {
+    tone: Option<f32>,
+    sample_rate: f32,
+    elapsed: usize,
+}
+
+impl AudioUnit for Unit {
+    fn reset(&mut self, sample_rate: usize) {
+        self.sample_rate = sample_rate as f32;
+        self.elapsed = 0;
+    }
+
+    fn tick(&mut self, inputs: &[Option<Voltage>], outputs: &mut [Voltage]) {
+        outputs[0] = match self.tone {
+            Some(frequency) => {
+                (2.0 * PI * frequency * self.elapsed as f32 / self.sample_rate).sin()
+            }
+            None => inputs[0].unwrap_or(0.0),
+        };
+        self.elapsed += 1;
+    }
+}
+
+fn harness(oversampling: Oversampling, tone: Option<f32>) -> Harness {
+    Harness::new(Box::new(Oversampled { oversampling, tone }), SAMPLE_RATE)
+}
+
+fn sine(frequency: f32) -> Input {
+    Input::function(move |t| (2.0 * PI * frequency * t).sin())
+}
+
+const OVERSAMPLED: [Oversampling; 3] = [Oversampling::X2, Oversampling::X4, Oversampling::X8];
+
+/// The gain of a steady tone, which starts once the filters have filled.
+fn gain(mut harness: Harness) -> f32 {
+    2f32.sqrt() * harness.run(0.2).output(0).skip(0.01).rms()
+}
+
+#[test]
+fn passes_the_audible_range() {
+    for oversampling in OVERSAMPLED {
+        for frequency in [100.0, 1000.0, 10_000.0, 15_000.0, 20_000.0] {
+            let mut harness = harness(oversampling, None);
+            harness.set_input(0, sine(frequency));
+            let what = format!("{:?} gain at {}Hz", oversampling, frequency);
+            // Within 0.1dB.
+            assert_near(&what, gain(harness), 1.0, 0.012);
+        }
+    }
+}
+
+#[test]
+fn runs_the_unit_faster() {
+    for oversampling in OVERSAMPLED {
+        let mut harness = harness(oversampling, Some(1000.0));
+        let capture = harness.run(0.2);
+        let what = format!("{:?} frequency", oversampling);
+        let frequency = capture.output(0).skip(0.01).frequency().unwrap();
+        assert_near(&what, frequency, 1000.0, 1.0);
+    }
+}
+
+#[test]
+fn rejects_frequencies_above_nyquist() {
+    for oversampling in OVERSAMPLED {
+        // These would alias back down to 18kHz and 12kHz.
+        for frequency in [30_000.0, 36_000.0] {
+            let what = format!("{:?} gain at {}Hz", oversampling, frequency);
+            let gain = gain(harness(oversampling, Some(frequency)));
+            assert!(gain < 0.002, "{} is {}dB", what, 20.0 * gain.log10());
+        }
+    }
+}
+
+#[test]
+fn delays_by_each_stages_filters() {
+    // Each stage's two filters delay by 62 samples at that stage's rate.
+    for (oversampling, expected) in OVERSAMPLED.into_iter().zip([31.0, 46.5, 54.25]) {
+        let mut harness = harness(oversampling, None);
+        harness.set_input(0, Input::function(|t| if t == 0.0 { 1.0 } else { 0.0 }));
+        let capture = harness.run_samples(200);
+        let response = capture.output(0).samples();
+        // The filters are symmetric, so the delay is the centre of the impulse response.
+        let centre = response
+            .iter()
+            .enumerate()
+            .map(|(i, v)| i as f32 * v)
+            .sum::<f32>()
+            / response.iter().sum::<f32>();
+        assert_near(
+            &format!("{:?} latency", oversampling),
+            centre,
+            expected,
+            0.01,
+        );
+    }
+}
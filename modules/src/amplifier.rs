@@ -35,6 +35,18 @@ impl Module for Vca {
         Some(self.params.as_ref())
     }
 
+    fn input_names(&self) -> &'static [&'static str] {
+        &["audio", "cv"]
+    }
+
+    fn output_names(&self) -> &'static [&'static str] {
+        &["audio"]
+    }
+
+    fn bypass_routes(&self) -> Vec<(usize, usize)> {
+        vec![(Vca::AUDIO_IN, Vca::AUDIO_OUT)]
+    }
+
     fn create_audio_unit(&self) -> Box<dyn AudioUnit> {
         Box::new(VcaUnit(self.params.clone()))
     }
@@ -83,7 +95,7 @@ impl Panel for VcaPanel {
     fn update(&mut self, handle: &module::ModuleHandle, ui: &mut egui::Ui) {
         ui.heading("VCA");
         ui.add_space(20.0);
-        ui.add(Knob::new(&self.0.gain).range(0.0..=1.0));
+        ui.add(Knob::new(&self.0.gain).range(0.0..=1.0).default_value(1.0));
         ui.add(SignalFlow::down_arrow());
         ui.label("Gain");
         ui.add(SignalFlow::up_arrow());
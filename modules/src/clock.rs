@@ -4,6 +4,7 @@ use eurorack::{Voltage, CV_VOLTS};
 use module::*;
 use portable_atomic::AtomicF32;
 use widgets::{
+    display::Display,
     egui::{self, Align, Layout},
     jack::{self, Jack},
     knob::Knob,
@@ -31,6 +32,10 @@ impl Module for Clock {
         Some(self.params.as_ref())
     }
 
+    fn output_names(&self) -> &'static [&'static str] {
+        &["trigger"]
+    }
+
     fn create_audio_unit(&self) -> Box<dyn AudioUnit> {
         Box::new(ClockUnit {
             params: self.params.clone(),
@@ -97,8 +102,11 @@ impl Panel for ClockPanel {
         ui.add(
             Knob::new(&self.0.bpm)
                 .range(40.0..=200.0)
+                .step(1.0)
+                .default_value(120.0)
                 .hover_text(|v| format!("{:.0} bpm", v)),
         );
+        ui.add(Display::new(format!("{:.0}", self.0.bpm.read())).chars(3));
         ui.label("BPM");
         ui.add_space(20.0);
         ui.add(
@@ -4,10 +4,10 @@ use eurorack::{
     utils::{Duration, SchmittTrigger},
     Voltage, CV_VOLTS, GATE_THRESHOLD_VOLTS,
 };
-use module::*;
+use module::{buffer::TripleBuffer, *};
 use portable_atomic::AtomicF32;
 use widgets::{
-    egui::{self, Align, Layout},
+    egui::{self, vec2, Align, Layout, Sense, Stroke},
     jack::{self, Jack},
     knob::Knob,
     signal::SignalFlow,
@@ -16,6 +16,8 @@ use widgets::{
 #[derive(Default)]
 pub struct Adsr {
     params: Arc<AdsrParams>,
+    /// The envelope's stage and level, from 0 to 1.
+    display: Arc<TripleBuffer<(State, f32)>>,
 }
 
 impl Adsr {
@@ -36,9 +38,18 @@ impl Module for Adsr {
         Some(self.params.as_ref())
     }
 
+    fn input_names(&self) -> &'static [&'static str] {
+        &["gate"]
+    }
+
+    fn output_names(&self) -> &'static [&'static str] {
+        &["cv"]
+    }
+
     fn create_audio_unit(&self) -> Box<dyn AudioUnit> {
         Box::new(AdsrUnit {
             params: self.params.clone(),
+            display: self.display.clone(),
             trigger: SchmittTrigger::default(),
             state: State::Silent,
             samples_remaining: None,
@@ -48,7 +59,10 @@ impl Module for Adsr {
     }
 
     fn create_panel(&self) -> Box<dyn Panel> {
-        Box::new(AdsrPanel(self.params.clone()))
+        Box::new(AdsrPanel {
+            params: self.params.clone(),
+            display: self.display.clone(),
+        })
     }
 }
 
@@ -73,6 +87,7 @@ impl Default for AdsrParams {
 
 struct AdsrUnit {
     params: Arc<AdsrParams>,
+    display: Arc<TripleBuffer<(State, f32)>>,
     trigger: SchmittTrigger,
     state: State,
     samples_remaining: Option<usize>,
@@ -153,19 +168,55 @@ impl AudioUnit for AdsrUnit {
         // Compute final output.
         self.level += self.step;
         outputs[Adsr::CV_OUT] = CV_VOLTS * self.level;
+        self.display.publish((self.state, self.level));
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
 enum State {
     Attack,
     Decay,
     Sustain,
     Release,
+    #[default]
     Silent,
 }
 
-struct AdsrPanel(Arc<AdsrParams>);
+impl State {
+    fn name(&self) -> &'static str {
+        match self {
+            State::Attack => "Attack",
+            State::Decay => "Decay",
+            State::Sustain => "Sustain",
+            State::Release => "Release",
+            State::Silent => "Idle",
+        }
+    }
+}
+
+struct AdsrPanel {
+    params: Arc<AdsrParams>,
+    display: Arc<TripleBuffer<(State, f32)>>,
+}
+
+impl AdsrPanel {
+    /// A bar showing the envelope's current level, labelled with its stage.
+    fn level(&self, ui: &mut egui::Ui) {
+        let (state, level) = self.display.read();
+        let size = vec2(ui.available_width(), 0.5 * ui.spacing().interact_size.y);
+        let (rect, _) = ui.allocate_exact_size(size, Sense::hover());
+        if ui.is_rect_visible(rect) {
+            let visuals = ui.visuals();
+            let painter = ui.painter();
+            painter.rect_filled(rect, 2.0, visuals.extreme_bg_color);
+            let mut filled = rect;
+            filled.set_width(rect.width() * level.clamp(0.0, 1.0));
+            painter.rect_filled(filled, 2.0, visuals.selection.bg_fill);
+            painter.rect_stroke(rect, 2.0, Stroke::new(1.0, visuals.weak_text_color()));
+        }
+        ui.small(state.name());
+    }
+}
 
 impl Panel for AdsrPanel {
     fn width(&self) -> usize {
@@ -175,18 +226,23 @@ impl Panel for AdsrPanel {
     fn update(&mut self, handle: &module::ModuleHandle, ui: &mut egui::Ui) {
         ui.heading("ADSR");
         ui.add_space(20.0);
-        ui.add(Knob::new(&self.0.attack).scale(0.75));
+        ui.add(time_knob(&self.params.attack).default_value(0.005));
         ui.small("Attack");
         ui.add_space(10.0);
-        ui.add(Knob::new(&self.0.decay).scale(0.75));
+        ui.add(time_knob(&self.params.decay).default_value(0.1));
         ui.small("Decay");
         ui.add_space(10.0);
-        ui.add(Knob::new(&self.0.sustain).scale(0.75));
+        ui.add(
+            Knob::new(&self.params.sustain)
+                .scale(0.75)
+                .default_value(0.8),
+        );
         ui.small("Sustain");
         ui.add_space(10.0);
-        ui.add(Knob::new(&self.0.release).scale(0.75));
+        ui.add(time_knob(&self.params.release).default_value(0.5));
         ui.small("Release");
         ui.add_space(10.0);
+        self.level(ui);
         ui.with_layout(Layout::bottom_up(Align::Center), |ui| {
             jack::outputs(ui, |ui| {
                 ui.add(Jack::output(handle.output(Adsr::CV_OUT)));
@@ -197,3 +253,12 @@ impl Panel for AdsrPanel {
         });
     }
 }
+
+/// A knob for a stage's length, with finer control over short stages.
+fn time_knob(param: &Duration) -> Knob<'_> {
+    Knob::new(param)
+        .scale(0.75)
+        .exponential(0.0..=1.0, 3.0)
+        .display_scale(1000.0)
+        .hover_text(|v| format!("{:.0} ms", 1000.0 * v))
+}
@@ -10,6 +10,11 @@ use widgets::{
     signal::SignalFlow,
 };
 
+/// The highest cutoff frequency, which is also the range of the cutoff CV input. Patches saved
+/// before version 2 of the format, when the range was a sixth of the sample rate, have their
+/// attenuverter scaled down when they're loaded.
+const MAX_CUTOFF: f32 = 20_000.0;
+
 #[derive(Default)]
 pub struct Vcf {
     params: Arc<VcfParams>,
@@ -49,6 +54,24 @@ impl Module for Vcf {
         Some(self.params.as_ref())
     }
 
+    fn input_names(&self) -> &'static [&'static str] {
+        &["cutoff", "resonance", "audio"]
+    }
+
+    fn output_names(&self) -> &'static [&'static str] {
+        &["lowpass", "bandpass", "highpass"]
+    }
+
+    fn bypass_routes(&self) -> Vec<(usize, usize)> {
+        vec![(Vcf::AUDIO_IN, Vcf::LOWPASS_OUT)]
+    }
+
+    fn default_oversampling(&self) -> Oversampling {
+        // The filter becomes unstable above a sixth of the sample rate, so we need 4x oversampling
+        // to reach the top of the audible range.
+        Oversampling::X4
+    }
+
     fn create_audio_unit(&self) -> Box<dyn AudioUnit> {
         Box::new(VcfUnit {
             params: self.params.clone(),
@@ -90,21 +113,22 @@ struct VcfUnit {
 impl AudioUnit for VcfUnit {
     fn reset(&mut self, sample_rate: usize) {
         self.sample_rate = sample_rate as f32;
+        self.last_out = [0.0; 3];
     }
 
     fn tick(&mut self, inputs: &[Option<Voltage>], outputs: &mut [Voltage]) {
         // Compute modulated inputs.
         //
         // As state variable filters become unstable somewhere around Fs/6, we manually clamp the
-        // max cutoff there. We could raise this limit by oversampling (and we probably should do
-        // so later).
+        // max cutoff there. When oversampled (as we are by default), this is above the audible
+        // range.
         //
         // Additionally, the current resonance mapping is arbitrary and could use more tuning.
-        let cutoff_in = inputs[Vcf::CUTOFF_IN].unwrap_or(0.0) / CV_VOLTS * self.sample_rate / 6.0;
+        let cutoff_in = inputs[Vcf::CUTOFF_IN].unwrap_or(0.0) / CV_VOLTS * MAX_CUTOFF;
         let resonance_in = inputs[Vcf::RESONANCE_IN].unwrap_or(0.0);
 
         let cutoff = (self.params.cutoff.read() + self.params.cutoff_atten.read() * cutoff_in)
-            .clamp(0.0, self.sample_rate / 6.0);
+            .clamp(0.0, MAX_CUTOFF.min(self.sample_rate / 6.0));
         let resonance =
             self.params.resonance.read() + 0.5 * resonance_in * self.params.resonance_atten.read();
 
@@ -121,13 +145,8 @@ impl AudioUnit for VcfUnit {
         outputs[Vcf::LOWPASS_OUT] =
             f1 * outputs[Vcf::BANDPASS_OUT] + self.last_out[Vcf::LOWPASS_OUT];
 
-        // Check for numerical stability in the filter. For now, we use an assert because I want to
-        // bubble these up into crashes for testing. Eventually, this should be replaced with hard
-        // clamping at the power limits.
-        for o in outputs.iter() {
-            assert!(o.is_finite());
-        }
-
+        // If the filter becomes numerically unstable, the rack will catch the non-finite outputs
+        // and reset us, which clears the filter state.
         self.last_out[Vcf::HIPASS_OUT] = outputs[Vcf::HIPASS_OUT];
         self.last_out[Vcf::BANDPASS_OUT] = outputs[Vcf::BANDPASS_OUT];
         self.last_out[Vcf::LOWPASS_OUT] = outputs[Vcf::LOWPASS_OUT];
@@ -146,7 +165,7 @@ impl Panel for VcfPanel {
         ui.add_space(20.0);
         ui.columns(2, |columns| {
             columns[0].vertical_centered(|ui| {
-                ui.add(Knob::frequency(&self.0.cutoff));
+                ui.add(Knob::frequency(&self.0.cutoff).default_value(20_000.0));
                 ui.add(SignalFlow::down_arrow());
                 ui.small("Cutoff");
                 ui.add(SignalFlow::up_arrow());
@@ -155,7 +174,11 @@ impl Panel for VcfPanel {
                 ui.add(Jack::input(handle.input(Vcf::CUTOFF_IN)));
             });
             columns[1].vertical_centered(|ui| {
-                ui.add(Knob::new(&self.0.resonance).range(0.5..=5.0));
+                ui.add(
+                    Knob::new(&self.0.resonance)
+                        .range(0.5..=5.0)
+                        .default_value(1.0),
+                );
                 ui.add(SignalFlow::down_arrow());
                 ui.small("Resonance");
                 ui.add(SignalFlow::up_arrow());
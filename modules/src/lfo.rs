@@ -37,6 +37,14 @@ impl Module for Lfo {
         Some(self.params.as_ref())
     }
 
+    fn input_names(&self) -> &'static [&'static str] {
+        &["freq"]
+    }
+
+    fn output_names(&self) -> &'static [&'static str] {
+        &["sine", "saw", "square", "triangle"]
+    }
+
     fn create_audio_unit(&self) -> Box<dyn AudioUnit> {
         Box::new(LfoUnit {
             params: self.params.clone(),
@@ -106,7 +114,12 @@ impl Panel for LfoPanel {
     fn update(&mut self, handle: &module::ModuleHandle, ui: &mut egui::Ui) {
         ui.heading("LFO");
         ui.add_space(20.0);
-        ui.add(Knob::new(&self.params.frequency).logarithmic(0.0..=100.0));
+        ui.add(
+            Knob::new(&self.params.frequency)
+                .logarithmic(0.01..=100.0)
+                .default_value(1.0)
+                .hover_text(|v| format!("{:.2} Hz", v)),
+        );
         ui.label("Freq");
         ui.add(Jack::input(handle.input(Lfo::FREQ_IN)));
         ui.with_layout(Layout::bottom_up(Align::Center), |ui| {
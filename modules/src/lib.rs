@@ -1,4 +1,4 @@
-use module::registry::ModuleRegistry;
+use module::registry::{Category, ModuleManifest, ModuleRegistry};
 
 pub mod amplifier;
 pub mod clock;
@@ -7,17 +7,67 @@ pub mod filters;
 pub mod lfo;
 pub mod midi;
 pub mod oscillators;
+pub mod oscilloscope;
 pub mod sequencer;
+pub mod spectrum;
 
 pub fn builtin_modules() -> ModuleRegistry {
     let mut registry = ModuleRegistry::default();
-    registry.register::<amplifier::Vca>("builtins::Vca", "VCA");
-    registry.register::<clock::Clock>("builtins::Clock", "Clock");
-    registry.register::<envelope::Adsr>("builtins::Adsr", "Adsr");
-    registry.register::<filters::Vcf>("builtins::Vcf", "VCF");
-    registry.register::<lfo::Lfo>("builtins::Lfo", "LFO");
-    registry.register::<midi::MidiIn>("builtins::MidiIn", "MidiIn");
-    registry.register::<oscillators::Vco>("builtins::Vco", "VCO");
-    registry.register::<sequencer::Sequencer>("builtins::Sequencer", "Sequencer");
+    registry.register::<amplifier::Vca>(
+        builtin("Vca", "VCA", Category::Amplifier)
+            .tags(&["vca", "gain", "volume"])
+            .description("Scales an audio signal by a control voltage."),
+    );
+    registry.register::<clock::Clock>(
+        builtin("Clock", "Clock", Category::Sequencing)
+            .tags(&["tempo", "bpm", "trigger"])
+            .description("Emits a trigger on every beat, at a set tempo."),
+    );
+    registry.register::<envelope::Adsr>(
+        builtin("Adsr", "Adsr", Category::Envelope)
+            .tags(&["adsr", "gate", "contour"])
+            .description("An attack, decay, sustain and release envelope, driven by a gate."),
+    );
+    registry.register::<filters::Vcf>(
+        builtin("Vcf", "VCF", Category::Filter)
+            .tags(&["vcf", "lowpass", "bandpass", "highpass", "resonance"])
+            .description("A resonant state variable filter, with voltage controlled cutoff."),
+    );
+    registry.register::<lfo::Lfo>(
+        builtin("Lfo", "LFO", Category::Modulation)
+            .tags(&["lfo", "sine", "saw", "square", "triangle"])
+            .description("A low frequency oscillator for modulating other modules."),
+    );
+    registry.register::<midi::MidiIn>(
+        builtin("MidiIn", "MidiIn", Category::InputOutput)
+            .tags(&["midi", "keyboard", "gate", "pitch"])
+            .description("Converts notes from a MIDI device into pitch and gate voltages."),
+    );
+    registry.register::<oscillators::Vco>(
+        builtin("Vco", "VCO", Category::Oscillator)
+            .tags(&["vco", "saw", "square", "triangle", "pitch"])
+            .description("A voltage controlled oscillator that tracks 1V/octave."),
+    );
+    registry.register::<oscilloscope::Oscilloscope>(
+        builtin("Oscilloscope", "Scope", Category::Utility)
+            .tags(&["scope", "oscilloscope", "waveform", "display", "trigger"])
+            .description("Draws up to four signals over time, passing them straight through."),
+    );
+    registry.register::<sequencer::Sequencer>(
+        builtin("Sequencer", "Sequencer", Category::Sequencing)
+            .tags(&["notes", "steps", "melody"])
+            .description("Steps through a sequence of notes on each trigger."),
+    );
+    registry.register::<spectrum::SpectrumAnalyzer>(
+        builtin("SpectrumAnalyzer", "Spectrum", Category::Utility)
+            .tags(&["spectrum", "analyzer", "fft", "frequency", "display"])
+            .description("Shows the frequency content of a signal, passing it straight through."),
+    );
     registry
 }
+
+fn builtin(id: &str, name: &str, category: Category) -> ModuleManifest {
+    ModuleManifest::new(&format!("builtins::{}", id), name)
+        .category(category)
+        .author("oxcable")
+}
@@ -30,6 +30,10 @@ impl Module for MidiIn {
         None
     }
 
+    fn output_names(&self) -> &'static [&'static str] {
+        &["v_oct", "gate"]
+    }
+
     fn create_audio_unit(&self) -> Box<dyn AudioUnit> {
         // TODO: We should figure out how to actually do error handling for this; we probably don't
         // want panics in this function.
@@ -30,6 +30,14 @@ impl Module for Vco {
         None
     }
 
+    fn input_names(&self) -> &'static [&'static str] {
+        &["v_oct"]
+    }
+
+    fn output_names(&self) -> &'static [&'static str] {
+        &["saw", "square", "triangle"]
+    }
+
     fn create_audio_unit(&self) -> Box<dyn AudioUnit> {
         Box::new(VcoUnit {
             phase: 0.0,
@@ -0,0 +1,323 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicU8},
+    Arc,
+};
+
+use eurorack::Voltage;
+use module::{buffer::TripleBuffer, *};
+use portable_atomic::AtomicF32;
+use widgets::{
+    egui::{self, pos2, vec2, Color32, Sense, Shape, Stroke},
+    jack::{self, Jack},
+    knob::Knob,
+    selector::Selector,
+    toggle::Toggle,
+};
+
+/// The number of points drawn across the screen in each sweep.
+const SWEEP_POINTS: usize = 400;
+/// The screen is divided into this many divisions across, and `V_DIVISIONS` up.
+const H_DIVISIONS: usize = 10;
+const V_DIVISIONS: usize = 8;
+
+const TRACE_COLORS: [Color32; Oscilloscope::CHANNELS] = [
+    Color32::from_rgb(250, 220, 60),
+    Color32::from_rgb(60, 220, 240),
+    Color32::from_rgb(240, 90, 200),
+    Color32::from_rgb(110, 230, 110),
+];
+const CHANNEL_NAMES: [&str; Oscilloscope::CHANNELS] = ["A", "B", "C", "D"];
+
+/// Every point in a sweep, for every channel.
+type Sweep = [[Voltage; Oscilloscope::CHANNELS]; SWEEP_POINTS];
+
+/// Draws up to four signals over time. Each input is passed straight through to the matching
+/// output, so the scope can be patched inline.
+#[derive(Default)]
+pub struct Oscilloscope {
+    params: Arc<ScopeParams>,
+    display: Arc<ScopeDisplay>,
+}
+
+impl Oscilloscope {
+    pub const CHANNELS: usize = 4;
+}
+
+impl Module for Oscilloscope {
+    fn inputs(&self) -> usize {
+        Oscilloscope::CHANNELS
+    }
+
+    fn outputs(&self) -> usize {
+        Oscilloscope::CHANNELS
+    }
+
+    fn params(&self) -> Option<&dyn Parameters> {
+        Some(self.params.as_ref())
+    }
+
+    fn input_names(&self) -> &'static [&'static str] {
+        &["a", "b", "c", "d"]
+    }
+
+    fn output_names(&self) -> &'static [&'static str] {
+        &["a", "b", "c", "d"]
+    }
+
+    fn bypass_routes(&self) -> Vec<(usize, usize)> {
+        (0..Oscilloscope::CHANNELS).map(|i| (i, i)).collect()
+    }
+
+    fn create_audio_unit(&self) -> Box<dyn AudioUnit> {
+        Box::new(ScopeUnit {
+            params: self.params.clone(),
+            display: self.display.clone(),
+            points: Box::new([[0.0; Oscilloscope::CHANNELS]; SWEEP_POINTS]),
+            sample_rate: 0.0,
+            last_source: 0.0,
+            last_frame: [0.0; Oscilloscope::CHANNELS],
+            sweep: None,
+            next_point: 0.0,
+        })
+    }
+
+    fn create_panel(&self) -> Box<dyn Panel> {
+        Box::new(ScopePanel {
+            params: self.params.clone(),
+            display: self.display.clone(),
+        })
+    }
+}
+
+#[derive(Parameters)]
+struct ScopeParams {
+    /// Seconds per horizontal division.
+    time_div: AtomicF32,
+    volts_div: AtomicF32,
+    trigger_level: AtomicF32,
+    trigger_source: AtomicU8,
+    /// Whether sweeps wait for a trigger, or run freely.
+    triggered: AtomicBool,
+}
+
+impl Default for ScopeParams {
+    fn default() -> Self {
+        ScopeParams {
+            time_div: AtomicF32::new(0.001),
+            volts_div: AtomicF32::new(2.0),
+            trigger_level: AtomicF32::new(0.0),
+            trigger_source: AtomicU8::new(0),
+            triggered: AtomicBool::new(true),
+        }
+    }
+}
+
+/// The last complete sweep, kept on screen until the next one arrives. However fast sweeps are
+/// captured, the panel always has a whole one to draw.
+struct ScopeDisplay(TripleBuffer<Sweep>);
+
+impl Default for ScopeDisplay {
+    fn default() -> Self {
+        ScopeDisplay(TripleBuffer::new(
+            [[0.0; Oscilloscope::CHANNELS]; SWEEP_POINTS],
+        ))
+    }
+}
+
+struct ScopeUnit {
+    params: Arc<ScopeParams>,
+    display: Arc<ScopeDisplay>,
+    /// The sweep being captured.
+    points: Box<Sweep>,
+    sample_rate: f32,
+    last_source: Voltage,
+    /// The previous sample on every channel, for points that fall between samples.
+    last_frame: [Voltage; Oscilloscope::CHANNELS],
+    /// The number of points captured so far in the current sweep, if one is running.
+    sweep: Option<usize>,
+    /// When the next point falls, in samples after `last_frame`.
+    next_point: f32,
+}
+
+impl AudioUnit for ScopeUnit {
+    fn reset(&mut self, sample_rate: usize) {
+        self.sample_rate = sample_rate as f32;
+        self.last_frame = [0.0; Oscilloscope::CHANNELS];
+        self.sweep = None;
+    }
+
+    fn tick(&mut self, inputs: &[Option<Voltage>], outputs: &mut [Voltage]) {
+        for (output, input) in outputs.iter_mut().zip(inputs) {
+            *output = input.unwrap_or(0.0);
+        }
+
+        let source = self.params.trigger_source.read() as usize % Oscilloscope::CHANNELS;
+        let level = self.params.trigger_level.read();
+        let source = outputs[source];
+        let crossed = self.last_source < level && source >= level;
+        self.last_source = source;
+
+        let mut frame = [0.0; Oscilloscope::CHANNELS];
+        frame.copy_from_slice(outputs);
+        let last_frame = std::mem::replace(&mut self.last_frame, frame);
+
+        let mut captured = match self.sweep {
+            Some(captured) => captured,
+            None if crossed || !self.params.triggered.read() => {
+                // The sweep starts on this sample.
+                self.next_point = 1.0;
+                0
+            }
+            None => return,
+        };
+
+        // Points are evenly spaced in time, so that the screen always spans ten divisions. At
+        // fast timebases they fall between samples, and are interpolated.
+        let sweep_samples = H_DIVISIONS as f32 * self.params.time_div.read() * self.sample_rate;
+        let spacing = (sweep_samples / (SWEEP_POINTS - 1) as f32).max(1e-3);
+        while self.next_point <= 1.0 {
+            let t = self.next_point;
+            let point: [Voltage; Oscilloscope::CHANNELS] =
+                std::array::from_fn(|i| last_frame[i] + t * (frame[i] - last_frame[i]));
+            self.points[captured] = point;
+            self.next_point += spacing;
+            captured += 1;
+            if captured == SWEEP_POINTS {
+                self.display.0.publish(*self.points);
+                self.sweep = None;
+                return;
+            }
+        }
+        self.next_point -= 1.0;
+        self.sweep = Some(captured);
+    }
+}
+
+struct ScopePanel {
+    params: Arc<ScopeParams>,
+    display: Arc<ScopeDisplay>,
+}
+
+impl ScopePanel {
+    fn screen(&mut self, ui: &mut egui::Ui) {
+        let size = vec2(ui.available_width(), 0.75 * ui.available_width());
+        let (rect, _) = ui.allocate_exact_size(size, Sense::hover());
+        if !ui.is_rect_visible(rect) {
+            return;
+        }
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 4.0, Color32::from_gray(16));
+
+        // Graticule:
+        let grid = Stroke::new(1.0, Color32::from_gray(48));
+        for i in 1..H_DIVISIONS {
+            let x = rect.left() + rect.width() * i as f32 / H_DIVISIONS as f32;
+            painter.line_segment([pos2(x, rect.top()), pos2(x, rect.bottom())], grid);
+        }
+        for i in 1..V_DIVISIONS {
+            let y = rect.top() + rect.height() * i as f32 / V_DIVISIONS as f32;
+            painter.line_segment([pos2(rect.left(), y), pos2(rect.right(), y)], grid);
+        }
+
+        let volts_div = self.params.volts_div.read();
+        let to_y = |v: Voltage| {
+            let divisions =
+                (v / volts_div).clamp(-0.5 * V_DIVISIONS as f32, 0.5 * V_DIVISIONS as f32);
+            rect.center().y - divisions * rect.height() / V_DIVISIONS as f32
+        };
+
+        // Trigger level, as a tick on the left edge:
+        if self.params.triggered.read() {
+            let source = self.params.trigger_source.read() as usize % Oscilloscope::CHANNELS;
+            let y = to_y(self.params.trigger_level.read());
+            painter.line_segment(
+                [pos2(rect.left(), y), pos2(rect.left() + 6.0, y)],
+                Stroke::new(2.0, TRACE_COLORS[source]),
+            );
+        }
+
+        let sweep = self.display.0.read();
+        for (channel, color) in TRACE_COLORS.iter().enumerate() {
+            let points = sweep
+                .iter()
+                .enumerate()
+                .map(|(i, frame)| {
+                    let x = rect.left() + rect.width() * i as f32 / (SWEEP_POINTS - 1) as f32;
+                    pos2(x, to_y(frame[channel]))
+                })
+                .collect();
+            painter.add(Shape::line(points, Stroke::new(1.5, *color)));
+        }
+    }
+}
+
+impl Panel for ScopePanel {
+    fn width(&self) -> usize {
+        12
+    }
+
+    fn update(&mut self, handle: &module::ModuleHandle, ui: &mut egui::Ui) {
+        ui.heading("Scope");
+        ui.add_space(10.0);
+        self.screen(ui);
+        ui.add_space(10.0);
+
+        ui.columns(3, |columns| {
+            columns[0].vertical_centered(|ui| {
+                ui.add(
+                    Knob::new(&self.params.time_div)
+                        .logarithmic(0.0001..=0.5)
+                        .default_value(0.001)
+                        .display_scale(1000.0)
+                        .hover_text(|v| format!("{:.1} ms/div", 1000.0 * v)),
+                );
+                ui.small("Time");
+            });
+            columns[1].vertical_centered(|ui| {
+                ui.add(
+                    Knob::new(&self.params.volts_div)
+                        .logarithmic(0.1..=5.0)
+                        .default_value(2.0)
+                        .hover_text(|v| format!("{:.2} V/div", v)),
+                );
+                ui.small("Volts");
+            });
+            columns[2].vertical_centered(|ui| {
+                ui.add(
+                    Knob::new(&self.params.trigger_level)
+                        .range(-10.0..=10.0)
+                        .snap_to_center()
+                        .hover_text(|v| format!("{:+.2} V", v)),
+                );
+                ui.small("Trig level");
+            });
+        });
+        ui.add_space(10.0);
+
+        ui.horizontal(|ui| {
+            ui.add(Toggle::new(&self.params.triggered).default_value(true));
+            ui.small("Trig");
+            ui.separator();
+            ui.add(Selector::new(&self.params.trigger_source, &CHANNEL_NAMES));
+        });
+        ui.add_space(10.0);
+
+        ui.columns(Oscilloscope::CHANNELS, |columns| {
+            for (i, ui) in columns.iter_mut().enumerate() {
+                ui.vertical_centered(|ui| {
+                    ui.colored_label(TRACE_COLORS[i], CHANNEL_NAMES[i]);
+                    ui.add(Jack::input(handle.input(i)));
+                });
+            }
+        });
+        jack::outputs(ui, |ui| {
+            ui.columns(Oscilloscope::CHANNELS, |columns| {
+                for (i, ui) in columns.iter_mut().enumerate() {
+                    ui.vertical_centered(|ui| {
+                        ui.add(Jack::output(handle.output(i)));
+                    });
+                }
+            });
+        });
+    }
+}
@@ -3,10 +3,11 @@
 use std::sync::{atomic::AtomicU8, Arc};
 
 use eurorack::{midi_to_voltage, utils::SchmittTrigger, Voltage};
-use module::*;
+use module::{buffer::TripleBuffer, *};
 use widgets::{
     egui::{self, Layout, Slider},
     jack::{self, Jack},
+    led::StepIndicator,
     signal::SignalFlow,
 };
 
@@ -15,6 +16,8 @@ pub const SEQUENCE_LENGTH: usize = 8;
 #[derive(Default)]
 pub struct Sequencer {
     params: Arc<SequencerParams>,
+    /// The step being played, once the first trigger arrives.
+    step: Arc<TripleBuffer<Option<usize>>>,
 }
 
 impl Sequencer {
@@ -43,16 +46,28 @@ impl Module for Sequencer {
         Some(self.params.as_ref())
     }
 
+    fn input_names(&self) -> &'static [&'static str] {
+        &["trigger"]
+    }
+
+    fn output_names(&self) -> &'static [&'static str] {
+        &["v_oct"]
+    }
+
     fn create_audio_unit(&self) -> Box<dyn AudioUnit> {
         Box::new(SequencerUnit {
             params: self.params.clone(),
+            step: self.step.clone(),
             trigger: SchmittTrigger::default(),
             position: SEQUENCE_LENGTH - 1,
         })
     }
 
     fn create_panel(&self) -> Box<dyn Panel> {
-        Box::new(SequencerPanel(self.params.clone()))
+        Box::new(SequencerPanel {
+            params: self.params.clone(),
+            step: self.step.clone(),
+        })
     }
 }
 
@@ -80,6 +95,7 @@ impl Default for SequencerParams {
 
 struct SequencerUnit {
     params: Arc<SequencerParams>,
+    step: Arc<TripleBuffer<Option<usize>>>,
     trigger: SchmittTrigger,
     position: usize,
 }
@@ -91,12 +107,16 @@ impl AudioUnit for SequencerUnit {
         let trigger = inputs[Sequencer::TRIGGER_IN].unwrap_or(0.0);
         if self.trigger.detect(trigger) {
             self.position = (self.position + 1) % SEQUENCE_LENGTH;
+            self.step.publish(Some(self.position));
         }
         outputs[Sequencer::V_OCT_OUT] = midi_to_voltage(self.params.notes[self.position].read());
     }
 }
 
-struct SequencerPanel(Arc<SequencerParams>);
+struct SequencerPanel {
+    params: Arc<SequencerParams>,
+    step: Arc<TripleBuffer<Option<usize>>>,
+}
 
 impl Panel for SequencerPanel {
     fn width(&self) -> usize {
@@ -107,7 +127,7 @@ impl Panel for SequencerPanel {
         ui.heading("Sequencer");
         ui.add_space(20.0);
 
-        let mut notes: Vec<u8> = self.0.notes.iter().map(Parameter::read).collect();
+        let mut notes: Vec<u8> = self.params.notes.iter().map(Parameter::read).collect();
         ui.columns(SEQUENCE_LENGTH / 2, |columns| {
             for i in 0..SEQUENCE_LENGTH / 2 {
                 let i2 = i + SEQUENCE_LENGTH / 2;
@@ -129,8 +149,10 @@ impl Panel for SequencerPanel {
             }
         });
         for (i, n) in notes.into_iter().enumerate() {
-            self.0.notes[i].write(n);
+            self.params.notes[i].write(n);
         }
+        ui.add_space(10.0);
+        ui.add(StepIndicator::new(SEQUENCE_LENGTH, self.step.read()));
 
         ui.add_space(128.0);
         ui.with_layout(Layout::right_to_left(), |ui| {
@@ -0,0 +1,307 @@
+use std::{
+    f32::consts::PI,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use eurorack::{Voltage, AUDIO_VOLTS};
+use module::{buffer::SampleRing, *};
+use portable_atomic::AtomicF32;
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+use widgets::{
+    egui::{self, pos2, vec2, Color32, Sense, Shape, Stroke},
+    jack::{self, Jack},
+    knob::Knob,
+    toggle::Toggle,
+};
+
+/// The number of samples in each transform.
+const FFT_SIZE: usize = 4096;
+/// The bottom of the display, in decibels relative to a full scale audio signal.
+const MIN_DB: f32 = -96.0;
+/// How quickly held peaks fall back, in decibels per second.
+const PEAK_DECAY_DB: f32 = 12.0;
+
+/// Shows the frequency content of a signal, which is passed straight through.
+#[derive(Default)]
+pub struct SpectrumAnalyzer {
+    params: Arc<SpectrumParams>,
+    capture: Arc<SpectrumCapture>,
+}
+
+impl SpectrumAnalyzer {
+    pub const AUDIO_IN: usize = 0;
+
+    pub const AUDIO_OUT: usize = 0;
+}
+
+impl Module for SpectrumAnalyzer {
+    fn inputs(&self) -> usize {
+        1
+    }
+
+    fn outputs(&self) -> usize {
+        1
+    }
+
+    fn params(&self) -> Option<&dyn Parameters> {
+        Some(self.params.as_ref())
+    }
+
+    fn input_names(&self) -> &'static [&'static str] {
+        &["audio"]
+    }
+
+    fn output_names(&self) -> &'static [&'static str] {
+        &["audio"]
+    }
+
+    fn bypass_routes(&self) -> Vec<(usize, usize)> {
+        vec![(SpectrumAnalyzer::AUDIO_IN, SpectrumAnalyzer::AUDIO_OUT)]
+    }
+
+    fn create_audio_unit(&self) -> Box<dyn AudioUnit> {
+        Box::new(SpectrumUnit(self.capture.clone()))
+    }
+
+    fn create_panel(&self) -> Box<dyn Panel> {
+        Box::new(SpectrumPanel {
+            params: self.params.clone(),
+            capture: self.capture.clone(),
+            fft: FftPlanner::new().plan_fft_forward(FFT_SIZE),
+            window: (0..FFT_SIZE)
+                .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / FFT_SIZE as f32).cos())
+                .collect(),
+            last_read: 0,
+            spectrum: Vec::new(),
+            peaks: Vec::new(),
+        })
+    }
+}
+
+#[derive(Parameters)]
+struct SpectrumParams {
+    /// The frequencies at the left and right edges of the display.
+    min_freq: AtomicF32,
+    max_freq: AtomicF32,
+    /// How much of the previous spectrum is kept with each update, from 0 to 1.
+    averaging: AtomicF32,
+    peak_hold: AtomicBool,
+}
+
+impl Default for SpectrumParams {
+    fn default() -> Self {
+        SpectrumParams {
+            min_freq: AtomicF32::new(20.0),
+            max_freq: AtomicF32::new(20_000.0),
+            averaging: AtomicF32::new(0.5),
+            peak_hold: AtomicBool::new(true),
+        }
+    }
+}
+
+/// The latest input samples, shared between the audio unit and the panel.
+struct SpectrumCapture {
+    ring: SampleRing,
+    sample_rate: AtomicUsize,
+}
+
+impl Default for SpectrumCapture {
+    fn default() -> Self {
+        SpectrumCapture {
+            ring: SampleRing::new(1, 2 * FFT_SIZE),
+            sample_rate: AtomicUsize::new(0),
+        }
+    }
+}
+
+struct SpectrumUnit(Arc<SpectrumCapture>);
+
+impl AudioUnit for SpectrumUnit {
+    fn reset(&mut self, sample_rate: usize) {
+        self.0.sample_rate.store(sample_rate, Ordering::Relaxed);
+    }
+
+    fn tick(&mut self, inputs: &[Option<Voltage>], outputs: &mut [Voltage]) {
+        let audio = inputs[SpectrumAnalyzer::AUDIO_IN].unwrap_or(0.0);
+        outputs[SpectrumAnalyzer::AUDIO_OUT] = audio;
+        self.0.ring.push(&[audio]);
+    }
+}
+
+struct SpectrumPanel {
+    params: Arc<SpectrumParams>,
+    capture: Arc<SpectrumCapture>,
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    /// The ring position of the newest sample in the last transform.
+    last_read: usize,
+    /// The averaged level of each bin, in decibels.
+    spectrum: Vec<f32>,
+    /// The held peak level of each bin, in decibels.
+    peaks: Vec<f32>,
+}
+
+impl SpectrumPanel {
+    /// Transforms the latest samples, if any have arrived, and folds them into the spectrum.
+    fn analyze(&mut self, dt: f32) {
+        let written = self.capture.ring.written();
+        if written == self.last_read {
+            return;
+        }
+        let samples = match written
+            .checked_sub(FFT_SIZE)
+            .and_then(|start| self.capture.ring.read(start, FFT_SIZE))
+        {
+            Some(samples) => samples,
+            None => return,
+        };
+        self.last_read = written;
+
+        let mut buffer: Vec<Complex<f32>> = samples
+            .iter()
+            .zip(&self.window)
+            .map(|(s, w)| Complex::new(s * w, 0.0))
+            .collect();
+        self.fft.process(&mut buffer);
+
+        // Scale so that a full scale sine reads 0 dB, whatever the window.
+        let gain = 2.0 / (self.window.iter().sum::<f32>() * AUDIO_VOLTS);
+        let levels = buffer[..FFT_SIZE / 2]
+            .iter()
+            .map(|x| (20.0 * (x.norm() * gain).log10()).max(MIN_DB));
+        let averaging = self.params.averaging.read().clamp(0.0, 0.99);
+        if self.spectrum.len() != FFT_SIZE / 2 {
+            self.spectrum = levels.collect();
+            self.peaks = self.spectrum.clone();
+            return;
+        }
+        for ((average, peak), level) in self.spectrum.iter_mut().zip(&mut self.peaks).zip(levels) {
+            *average = averaging * *average + (1.0 - averaging) * level;
+            *peak = (*peak - PEAK_DECAY_DB * dt).max(*average);
+        }
+    }
+
+    fn screen(&mut self, ui: &mut egui::Ui) {
+        let size = vec2(ui.available_width(), 0.75 * ui.available_width());
+        let (rect, _) = ui.allocate_exact_size(size, Sense::hover());
+        if !ui.is_rect_visible(rect) {
+            return;
+        }
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 4.0, Color32::from_gray(16));
+
+        let min_freq = self.params.min_freq.read().max(1.0);
+        let max_freq = self.params.max_freq.read().max(2.0 * min_freq);
+        let x_for =
+            |f: f32| rect.left() + rect.width() * (f / min_freq).ln() / (max_freq / min_freq).ln();
+        let y_for = |db: f32| rect.top() + rect.height() * (db / MIN_DB).clamp(0.0, 1.0);
+
+        // Grid lines every decade and every 12 dB:
+        let grid = Stroke::new(1.0, Color32::from_gray(48));
+        let mut decade = 10f32.powf(min_freq.log10().ceil());
+        while decade < max_freq {
+            let x = x_for(decade);
+            painter.line_segment([pos2(x, rect.top()), pos2(x, rect.bottom())], grid);
+            decade *= 10.0;
+        }
+        let mut db = -12.0;
+        while db > MIN_DB {
+            let y = y_for(db);
+            painter.line_segment([pos2(rect.left(), y), pos2(rect.right(), y)], grid);
+            db -= 12.0;
+        }
+
+        let sample_rate = self.capture.sample_rate.load(Ordering::Relaxed) as f32;
+        if self.spectrum.is_empty() || sample_rate == 0.0 {
+            return;
+        }
+        let bin_width = sample_rate / FFT_SIZE as f32;
+        // Each column shows the loudest bin it covers, so that narrow peaks aren't missed when
+        // zoomed out.
+        let columns = rect.width().max(1.0) as usize;
+        let trace = |levels: &[f32]| -> Vec<_> {
+            (0..columns)
+                .filter_map(|column| {
+                    let t = |c: usize| c as f32 / columns as f32;
+                    let f0 = min_freq * (max_freq / min_freq).powf(t(column));
+                    let f1 = min_freq * (max_freq / min_freq).powf(t(column + 1));
+                    let b0 = (f0 / bin_width).round() as usize;
+                    let b1 = ((f1 / bin_width).round() as usize).max(b0 + 1);
+                    let level = levels.get(b0..b1.min(levels.len()))?;
+                    let level = level.iter().copied().fold(MIN_DB, f32::max);
+                    Some(pos2(rect.left() + column as f32, y_for(level)))
+                })
+                .collect()
+        };
+        if self.params.peak_hold.read() {
+            painter.add(Shape::line(
+                trace(&self.peaks),
+                Stroke::new(1.0, Color32::from_rgb(240, 120, 60)),
+            ));
+        }
+        painter.add(Shape::line(
+            trace(&self.spectrum),
+            Stroke::new(1.5, Color32::from_rgb(90, 220, 240)),
+        ));
+    }
+}
+
+impl Panel for SpectrumPanel {
+    fn width(&self) -> usize {
+        12
+    }
+
+    fn update(&mut self, handle: &module::ModuleHandle, ui: &mut egui::Ui) {
+        self.analyze(ui.input().unstable_dt);
+
+        ui.heading("Spectrum");
+        ui.add_space(10.0);
+        self.screen(ui);
+        ui.add_space(10.0);
+
+        ui.columns(3, |columns| {
+            columns[0].vertical_centered(|ui| {
+                ui.add(Knob::frequency(&self.params.min_freq).default_value(20.0));
+                ui.small("Low");
+            });
+            columns[1].vertical_centered(|ui| {
+                ui.add(Knob::frequency(&self.params.max_freq).default_value(20_000.0));
+                ui.small("High");
+            });
+            columns[2].vertical_centered(|ui| {
+                ui.add(
+                    Knob::new(&self.params.averaging)
+                        .range(0.0..=0.95)
+                        .default_value(0.5)
+                        .display_scale(100.0)
+                        .hover_text(|v| format!("{:.0}%", 100.0 * v)),
+                );
+                ui.small("Average");
+            });
+        });
+        ui.add_space(10.0);
+        ui.horizontal(|ui| {
+            ui.add(Toggle::new(&self.params.peak_hold).default_value(true));
+            ui.small("Peak hold");
+        });
+        ui.add_space(10.0);
+
+        ui.columns(2, |columns| {
+            columns[0].vertical_centered(|ui| {
+                jack::inputs(ui, |ui| {
+                    ui.small("In");
+                    ui.add(Jack::input(handle.input(SpectrumAnalyzer::AUDIO_IN)));
+                });
+            });
+            columns[1].vertical_centered(|ui| {
+                jack::outputs(ui, |ui| {
+                    ui.small("Out");
+                    ui.add(Jack::output(handle.output(SpectrumAnalyzer::AUDIO_OUT)));
+                });
+            });
+        });
+    }
+}
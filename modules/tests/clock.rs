@@ -0,0 +1,49 @@
+use eurorack::GATE_THRESHOLD_VOLTS;
+use module_test::{assert_near, assert_trigger_times, Harness};
+use modules::clock::Clock;
+
+const SAMPLE_RATE: usize = 48_000;
+
+#[test]
+fn keeps_time() {
+    for bpm in [40.0, 90.0, 120.0, 137.0, 200.0] {
+        let mut harness = Harness::new(Box::new(Clock::default()), SAMPLE_RATE);
+        harness.set_param("bpm", bpm);
+        let capture = harness.run(10.0);
+        let trig = capture.output(Clock::TRIGGER_OUT);
+        let edges = trig.rising_edges(GATE_THRESHOLD_VOLTS);
+        let period = (edges[edges.len() - 1] - edges[0]) / (edges.len() - 1) as f32;
+        // The clock rounds its period down to a whole number of samples.
+        assert_near("bpm", 60.0 / period, bpm, 0.05);
+    }
+}
+
+#[test]
+fn triggers_on_the_beat() {
+    let mut harness = Harness::new(Box::new(Clock::default()), SAMPLE_RATE);
+    harness.set_param("bpm", 120.0);
+    let capture = harness.run(2.1);
+    let expected = [0.5, 1.0, 1.5, 2.0];
+    assert_trigger_times(
+        &capture.output(Clock::TRIGGER_OUT).skip(0.1),
+        GATE_THRESHOLD_VOLTS,
+        &expected.map(|t| t - 0.1),
+        2.0 / SAMPLE_RATE as f32,
+    );
+}
+
+#[test]
+fn registered_as_builtin() {
+    let mut registry = modules::builtin_modules();
+    let mut harness =
+        Harness::from_registry(&mut registry, "builtins::Clock", SAMPLE_RATE).unwrap();
+    harness.set_param("pulse_width", 0.25);
+    let capture = harness.run(1.0);
+    let trig = capture.output(Clock::TRIGGER_OUT);
+    assert_near(
+        "duty cycle",
+        trig.dc_offset() / eurorack::CV_VOLTS,
+        0.25,
+        0.01,
+    );
+}
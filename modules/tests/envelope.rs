@@ -0,0 +1,71 @@
+use eurorack::CV_VOLTS;
+use module_test::{assert_golden, assert_near, assert_peak, Harness, Input};
+use modules::envelope::Adsr;
+
+const SAMPLE_RATE: usize = 48_000;
+
+fn adsr(attack: f32, decay: f32, sustain: f32, release: f32) -> Harness {
+    let mut harness = Harness::new(Box::new(Adsr::default()), SAMPLE_RATE);
+    harness
+        .set_param("attack", attack)
+        .set_param("decay", decay)
+        .set_param("sustain", sustain)
+        .set_param("release", release);
+    harness
+}
+
+#[test]
+fn follows_stage_timings() {
+    let mut harness = adsr(0.01, 0.1, 0.5, 0.2);
+    harness.set_input(Adsr::GATE_IN, Input::gate(0.0, 0.5, 5.0));
+    let capture = harness.run(1.0);
+    let cv = capture.output(Adsr::CV_OUT);
+    let tolerance = 0.001;
+
+    // Attack: up to full scale.
+    assert_peak(&cv, CV_VOLTS, 0.01);
+    assert_near(
+        "attack time",
+        cv.time_to_reach(0.999 * CV_VOLTS).unwrap(),
+        0.01,
+        tolerance,
+    );
+
+    // Decay: down to the sustain level.
+    let decay = cv.skip(0.01);
+    assert_near(
+        "decay time",
+        decay.time_to_fall(0.5 * CV_VOLTS + 0.001).unwrap(),
+        0.1,
+        tolerance,
+    );
+    assert_near("sustain level", cv.at(0.3), 0.5 * CV_VOLTS, 0.01);
+
+    // Release: down to silence after the gate falls.
+    let release = cv.skip(0.5);
+    assert_near(
+        "release time",
+        release.time_to_fall(0.001).unwrap(),
+        0.2,
+        tolerance,
+    );
+    assert_near("final level", cv.at(0.9), 0.0, 0.001);
+}
+
+#[test]
+fn releases_from_attack() {
+    let mut harness = adsr(0.1, 0.1, 0.5, 0.1);
+    harness.set_input(Adsr::GATE_IN, Input::gate(0.0, 0.05, 5.0));
+    let capture = harness.run(0.5);
+    let cv = capture.output(Adsr::CV_OUT);
+    assert_peak(&cv, 0.5 * CV_VOLTS, 0.05);
+    assert_near("final level", cv.at(0.4), 0.0, 0.001);
+}
+
+#[test]
+fn matches_golden_envelope() {
+    let mut harness = adsr(0.002, 0.005, 0.6, 0.004);
+    harness.set_input(Adsr::GATE_IN, Input::gate(0.001, 0.01, 5.0));
+    let capture = harness.run(0.02);
+    assert_golden("tests/golden/adsr.txt", &capture.output(Adsr::CV_OUT), 1e-4);
+}
@@ -0,0 +1,29 @@
+use module_test::{assert_near, Harness, Input};
+use modules::oscilloscope::Oscilloscope;
+
+const SAMPLE_RATE: usize = 48_000;
+
+#[test]
+fn passes_signals_through() {
+    let mut harness = Harness::new(Box::new(Oscilloscope::default()), SAMPLE_RATE);
+    harness
+        .set_input(0, Input::function(|t| (100.0 * t).sin()))
+        .set_input(2, Input::Constant(-3.0));
+    let capture = harness.run(0.5);
+    for t in [0.01, 0.1, 0.25, 0.4] {
+        assert_near("a", capture.output(0).at(t), (100.0 * t).sin(), 0.01);
+    }
+    assert_near("b", capture.output(1).peak(), 0.0, 0.0);
+    assert_near("c", capture.output(2).dc_offset(), -3.0, 1e-6);
+}
+
+#[test]
+fn registered_as_builtin() {
+    let mut registry = modules::builtin_modules();
+    let mut harness =
+        Harness::from_registry(&mut registry, "builtins::Oscilloscope", SAMPLE_RATE).unwrap();
+    harness.set_param("time_div", 0.0001);
+    harness.set_input(3, Input::Constant(1.0));
+    let capture = harness.run(0.1);
+    assert_near("d", capture.output(3).dc_offset(), 1.0, 1e-6);
+}
@@ -0,0 +1,88 @@
+use std::sync::atomic::{AtomicBool, AtomicU8};
+
+use module::*;
+use portable_atomic::AtomicF32;
+
+#[derive(Parameters)]
+struct TestParams {
+    #[param(
+        label = "Cutoff",
+        min = 20.0,
+        max = 20000.0,
+        default = 1000.0,
+        log,
+        unit = "Hz"
+    )]
+    cutoff: AtomicF32,
+    #[param(min = -1.0, max = 1.0)]
+    cutoff_atten: AtomicF32,
+    #[param(options("Low", "Band", "High"))]
+    mode: AtomicU8,
+    bypass: AtomicBool,
+    steps: [AtomicU8; 2],
+}
+
+fn test_params() -> TestParams {
+    TestParams {
+        cutoff: AtomicF32::new(440.0),
+        cutoff_atten: AtomicF32::new(0.0),
+        mode: AtomicU8::new(2),
+        bypass: AtomicBool::new(true),
+        steps: [AtomicU8::new(1), AtomicU8::new(2)],
+    }
+}
+
+#[test]
+fn derived_info_describes_each_parameter() {
+    let params = test_params();
+    let info = params.info();
+    let names: Vec<_> = info.iter().map(|i| i.name.as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "cutoff",
+            "cutoff_atten",
+            "mode",
+            "bypass",
+            "steps[0]",
+            "steps[1]"
+        ]
+    );
+
+    let cutoff = &info[0];
+    assert_eq!(cutoff.label, "Cutoff");
+    assert_eq!(cutoff.range, Some(20.0..=20000.0));
+    assert_eq!(cutoff.default, Some(1000.0));
+    assert!(cutoff.logarithmic);
+    assert_eq!(cutoff.unit, Some("Hz"));
+
+    assert_eq!(info[1].label, "Cutoff atten");
+    assert_eq!(info[1].range, Some(-1.0..=1.0));
+    assert!(!info[1].logarithmic);
+    assert_eq!(info[2].options, ["Low", "Band", "High"]);
+    assert_eq!(info[5].label, "Steps 2");
+}
+
+#[test]
+fn derived_info_is_bound_to_the_parameters() {
+    let params = test_params();
+    let info = params.info();
+    match info[0].param {
+        ParameterRef::Float(param) => param.write(880.0),
+        _ => panic!("cutoff should be a float"),
+    }
+    match info[2].param {
+        ParameterRef::Choice(param) => assert_eq!(param.read(), 2),
+        _ => panic!("mode should be a choice"),
+    }
+    match info[3].param {
+        ParameterRef::Switch(param) => param.write(false),
+        _ => panic!("bypass should be a switch"),
+    }
+    match info[5].param {
+        ParameterRef::Choice(param) => assert_eq!(param.read(), 2),
+        _ => panic!("steps should be choices"),
+    }
+    assert_eq!(params.cutoff.read(), 880.0);
+    assert!(!params.bypass.read());
+}
@@ -0,0 +1,18 @@
+#[test]
+fn builtins_take_their_width_from_their_panels() {
+    let mut registry = modules::builtin_modules();
+    let manifests = registry.all_modules();
+    assert!(!manifests.is_empty());
+    for manifest in manifests {
+        let (_, module) = registry.create_module(&manifest.id).unwrap();
+        assert_eq!(
+            manifest.hp,
+            module.create_panel().width(),
+            "{}",
+            manifest.id
+        );
+    }
+
+    let scope = registry.manifest("builtins::Oscilloscope").unwrap();
+    assert_eq!(scope.hp, 12);
+}
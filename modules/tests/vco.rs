@@ -0,0 +1,48 @@
+use module_test::{assert_dc_offset, assert_frequency, assert_golden, Harness, Input};
+use modules::oscillators::Vco;
+
+const SAMPLE_RATE: usize = 48_000;
+
+#[test]
+fn tracks_one_volt_per_octave() {
+    for v_oct in [-2.0, -1.0, 0.0, 1.0, 2.0, 3.0] {
+        let mut harness = Harness::new(Box::new(Vco::default()), SAMPLE_RATE);
+        harness.set_input(Vco::V_OCT_IN, Input::Constant(v_oct));
+        let capture = harness.run(1.0);
+        let expected = eurorack::V_OCT_F0 * 2f32.powf(v_oct);
+        for output in [Vco::SAW_OUT, Vco::SQUARE_OUT] {
+            assert_frequency(&capture.output(output).skip(0.1), expected, 1.0);
+        }
+    }
+}
+
+#[test]
+fn outputs_are_centred() {
+    let mut harness = Harness::new(Box::new(Vco::default()), SAMPLE_RATE);
+    let capture = harness.run(1.0);
+    for output in [Vco::SAW_OUT, Vco::SQUARE_OUT, Vco::TRI_OUT] {
+        assert_dc_offset(&capture.output(output).skip(0.1), 0.0, 0.1);
+    }
+}
+
+#[test]
+fn matches_golden_waveforms() {
+    let mut harness = Harness::new(Box::new(Vco::default()), SAMPLE_RATE);
+    harness.set_input(Vco::V_OCT_IN, Input::Constant(1.0));
+    let capture = harness.run(0.01);
+    assert_golden(
+        "tests/golden/vco_saw.txt",
+        &capture.output(Vco::SAW_OUT),
+        1e-4,
+    );
+    assert_golden(
+        "tests/golden/vco_square.txt",
+        &capture.output(Vco::SQUARE_OUT),
+        1e-4,
+    );
+    assert_golden(
+        "tests/golden/vco_tri.txt",
+        &capture.output(Vco::TRI_OUT),
+        1e-4,
+    );
+}
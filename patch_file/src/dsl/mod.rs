@@ -0,0 +1,322 @@
+//! A small declarative language for writing patches by hand.
+//!
+//! ```text
+//! # Comments start with a hash.
+//! clock = Clock(bpm=180)
+//! seq = Sequencer(notes=[61, 65, 68, 77, 61, 65, 68, 75])
+//! vco = Vco()
+//! vcf = Vcf(cutoff=1000, state=bypassed)
+//!
+//! clock.trigger -> seq.trigger
+//! seq.v_oct -> vco.v_oct
+//! vco.saw -> vcf.audio
+//! vcf.lowpass -> out
+//! ```
+//!
+//! Module types are registry ids, where the `builtins::` prefix may be left out. Parameters that
+//! aren't given keep their defaults, and `state`, `oversampling` and `position` (as `[row, hp]`)
+//! may be set like parameters. Channels are referred to by the names modules give them, or by
+//! number. Text patches compile to the same [`SerializedPatch`] as JSON files, except that cable
+//! colours aren't kept.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    ops::Range,
+};
+
+use module::{registry::ModuleRegistry, Module, ModuleState, Oversampling, SerializedParameter};
+
+use crate::{
+    validate::same_shape, RackPosition, SerializedConnection, SerializedModule, SerializedPatch,
+};
+
+use self::syntax::{Channel, Port, Spanned, Value};
+
+mod printer;
+mod syntax;
+
+pub use printer::print;
+
+/// The file extension used for text patches.
+pub const EXTENSION: &str = "patch";
+
+/// The name that refers to the audio output in connections.
+const AUDIO_OUTPUT: &str = "out";
+
+/// The prefix of module ids that may be left out of module types.
+const BUILTINS_PREFIX: &str = "builtins::";
+
+#[derive(thiserror::Error, Debug)]
+#[error("{message}")]
+pub struct DslError {
+    /// The byte range of the source that the error refers to.
+    pub span: Range<usize>,
+    pub message: String,
+}
+
+impl DslError {
+    fn new(span: Range<usize>, message: String) -> Self {
+        DslError { span, message }
+    }
+
+    /// Formats the error with the line it occurred on, underlining the offending source.
+    pub fn render(&self, source: &str, file_name: &str) -> String {
+        let line_start = source[..self.span.start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[line_start..]
+            .find('\n')
+            .map_or(source.len(), |i| line_start + i);
+        let line = &source[line_start..line_end];
+        let line_number = source[..line_start].matches('\n').count() + 1;
+        let column = source[line_start..self.span.start].chars().count();
+        let width = source[self.span.start..self.span.end.min(line_end)]
+            .chars()
+            .count()
+            .max(1);
+        let gutter = " ".repeat(line_number.to_string().len());
+
+        let mut rendered = String::new();
+        writeln!(rendered, "error: {}", self.message).unwrap();
+        writeln!(
+            rendered,
+            "{}--> {}:{}:{}",
+            gutter,
+            file_name,
+            line_number,
+            column + 1
+        )
+        .unwrap();
+        writeln!(rendered, "{} |", gutter).unwrap();
+        writeln!(rendered, "{} | {}", line_number, line).unwrap();
+        write!(
+            rendered,
+            "{} | {}{}",
+            gutter,
+            " ".repeat(column),
+            "^".repeat(width)
+        )
+        .unwrap();
+        rendered
+    }
+}
+
+/// Compiles a text patch, resolving module types, parameters and channels against the registry.
+pub fn parse(source: &str, registry: &mut ModuleRegistry) -> Result<SerializedPatch, DslError> {
+    let statements = syntax::parse(source)?;
+    let mut patch = SerializedPatch::default();
+
+    // Create each module, so that we can check its parameters and channels.
+    let mut names: HashMap<&str, usize> = HashMap::new();
+    let mut modules: Vec<Box<dyn Module>> = Vec::new();
+    for declaration in &statements.declarations {
+        let name = &declaration.name;
+        if name.value == AUDIO_OUTPUT {
+            return Err(DslError::new(
+                name.span.clone(),
+                format!("'{}' is reserved for the audio output", AUDIO_OUTPUT),
+            ));
+        }
+        if names.insert(&name.value, modules.len()).is_some() {
+            return Err(DslError::new(
+                name.span.clone(),
+                format!("a module named '{}' already exists", name.value),
+            ));
+        }
+
+        let (id, module) = create_module(registry, &declaration.module_type)?;
+        let mut serialized = SerializedModule {
+            id,
+            state: ModuleState::Active,
+            oversampling: None,
+            position: None,
+            params: module
+                .params()
+                .map(|params| params.serialize())
+                .unwrap_or_default(),
+        };
+        for arg in &declaration.args {
+            set_argument(&mut serialized, &arg.name, &arg.value)?;
+        }
+        patch.modules.push(serialized);
+        modules.push(module);
+    }
+
+    let mut connected_inputs = HashSet::new();
+    for connection in &statements.connections {
+        let (src_index, src_channel) = resolve_port(&connection.src, &names, &modules, false)?;
+        let (dst_index, dst_channel) = resolve_port(&connection.dst, &names, &modules, true)?;
+        if !connected_inputs.insert((dst_index, dst_channel)) {
+            return Err(DslError::new(
+                connection.dst.span(),
+                "this input is already connected".to_owned(),
+            ));
+        }
+        patch.connections.push(SerializedConnection {
+            src_index,
+            src_channel,
+            dst_index,
+            dst_channel,
+            color: None,
+        });
+    }
+    Ok(patch)
+}
+
+/// Resolves a module type to a registry id, and creates the module.
+fn create_module(
+    registry: &mut ModuleRegistry,
+    module_type: &Spanned<String>,
+) -> Result<(String, Box<dyn Module>), DslError> {
+    let candidates = [
+        module_type.value.clone(),
+        format!("{}{}", BUILTINS_PREFIX, module_type.value),
+    ];
+    for id in candidates {
+        if let Ok((_, module)) = registry.create_module(&id) {
+            return Ok((id, module));
+        }
+    }
+    Err(DslError::new(
+        module_type.span.clone(),
+        format!("no module type '{}' exists", module_type.value),
+    ))
+}
+
+fn set_argument(
+    module: &mut SerializedModule,
+    name: &Spanned<String>,
+    value: &Spanned<Value>,
+) -> Result<(), DslError> {
+    let invalid = |expected: &str| {
+        Err(DslError::new(
+            value.span.clone(),
+            format!("expected {} for '{}'", expected, name.value),
+        ))
+    };
+    match (name.value.as_str(), &value.value) {
+        ("state", Value::Ident(state)) => {
+            module.state = match state.as_str() {
+                "active" => ModuleState::Active,
+                "bypassed" => ModuleState::Bypassed,
+                "muted" => ModuleState::Muted,
+                _ => return invalid("'active', 'bypassed' or 'muted'"),
+            };
+        }
+        ("state", _) => return invalid("'active', 'bypassed' or 'muted'"),
+        ("oversampling", Value::Num(factor)) => match Oversampling::try_from(*factor as usize) {
+            Ok(oversampling) if factor.fract() == 0.0 => {
+                module.oversampling = Some(oversampling);
+            }
+            _ => return invalid("1, 2, 4 or 8"),
+        },
+        ("oversampling", _) => return invalid("1, 2, 4 or 8"),
+        ("position", Value::List(values)) => match values.as_slice() {
+            [Value::Num(row), Value::Num(hp)]
+                if [row, hp].iter().all(|v| **v >= 0.0 && v.fract() == 0.0) =>
+            {
+                module.position = Some(RackPosition {
+                    row: *row as usize,
+                    hp: *hp as usize,
+                });
+            }
+            _ => return invalid("[row, hp]"),
+        },
+        ("position", _) => return invalid("[row, hp]"),
+        (param, value) => {
+            let expected = module.params.get(param).ok_or_else(|| {
+                DslError::new(
+                    name.span.clone(),
+                    format!("'{}' has no parameter '{}'", module.id, param),
+                )
+            })?;
+            match to_parameter(value) {
+                Some(value) if same_shape(expected, &value) => {
+                    module.params.insert(param.to_owned(), value);
+                }
+                _ => {
+                    let expected = match expected {
+                        SerializedParameter::Num(_) => "a number".to_owned(),
+                        SerializedParameter::List(values) => {
+                            format!("a list of {} numbers", values.len())
+                        }
+                    };
+                    return invalid(&expected);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn to_parameter(value: &Value) -> Option<SerializedParameter> {
+    match value {
+        Value::Num(value) => Some(SerializedParameter::Num(*value)),
+        Value::List(values) => values
+            .iter()
+            .map(to_parameter)
+            .collect::<Option<_>>()
+            .map(SerializedParameter::List),
+        Value::Ident(_) => None,
+    }
+}
+
+/// Finds the module index and channel for one end of a connection.
+fn resolve_port(
+    port: &Port,
+    names: &HashMap<&str, usize>,
+    modules: &[Box<dyn Module>],
+    is_input: bool,
+) -> Result<(usize, usize), DslError> {
+    let direction = if is_input { "input" } else { "output" };
+    if port.module.value == AUDIO_OUTPUT {
+        return match &port.channel {
+            None if is_input => Ok((modules.len(), 0)),
+            None => Err(DslError::new(
+                port.span(),
+                "the audio output can't be connected from".to_owned(),
+            )),
+            Some(channel) => Err(DslError::new(
+                channel.span.clone(),
+                "the audio output has no channels".to_owned(),
+            )),
+        };
+    }
+
+    let index = *names.get(port.module.value.as_str()).ok_or_else(|| {
+        DslError::new(
+            port.module.span.clone(),
+            format!("no module named '{}' exists", port.module.value),
+        )
+    })?;
+    let module = &modules[index];
+    let channel = port.channel.as_ref().ok_or_else(|| {
+        DslError::new(
+            port.span(),
+            format!(
+                "expected an {} channel, e.g. '{}.0'",
+                direction, port.module.value
+            ),
+        )
+    })?;
+    let (names, count) = if is_input {
+        (module.input_names(), module.inputs())
+    } else {
+        (module.output_names(), module.outputs())
+    };
+    let resolved = match &channel.value {
+        Channel::Name(name) => names.iter().position(|n| n == name),
+        Channel::Index(i) => Some(*i).filter(|&i| i < count),
+    };
+    resolved.map(|channel| (index, channel)).ok_or_else(|| {
+        let mut message = format!("'{}' has no {} '", port.module.value, direction);
+        match &channel.value {
+            Channel::Name(name) => message.push_str(name),
+            Channel::Index(i) => message.push_str(&i.to_string()),
+        }
+        message.push('\'');
+        if !names.is_empty() {
+            message.push_str(&format!(" (expected one of: {})", names.join(", ")));
+        }
+        DslError::new(channel.span.clone(), message)
+    })
+}
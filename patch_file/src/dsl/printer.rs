@@ -0,0 +1,127 @@
+use std::{collections::HashSet, fmt::Write};
+
+use module::{registry::ModuleRegistry, Module, ModuleState, SerializedParameter};
+
+use crate::{SerializedPatch, ValidationError};
+
+use super::{AUDIO_OUTPUT, BUILTINS_PREFIX};
+
+/// Writes a patch as text. Modules are named after their type, and channels are named wherever
+/// the module names them. Fails if a connection refers to a module that doesn't exist.
+pub fn print(
+    patch: &SerializedPatch,
+    registry: &mut ModuleRegistry,
+) -> Result<String, ValidationError> {
+    let mut out = String::new();
+
+    // Modules missing from the registry are still printed, but only with numbered channels.
+    let modules: Vec<Option<Box<dyn Module>>> = patch
+        .modules
+        .iter()
+        .map(|m| registry.create_module(&m.id).ok().map(|(_, module)| module))
+        .collect();
+
+    let mut used = HashSet::from([AUDIO_OUTPUT.to_owned()]);
+    let mut names = Vec::new();
+    for module in &patch.modules {
+        let module_type = module
+            .id
+            .strip_prefix(BUILTINS_PREFIX)
+            .unwrap_or(&module.id);
+        let base: String = module_type
+            .split("::")
+            .last()
+            .unwrap()
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_lowercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let mut name = base.clone();
+        let mut n = 2;
+        while !used.insert(name.clone()) {
+            name = format!("{}{}", base, n);
+            n += 1;
+        }
+
+        let mut args = Vec::new();
+        let mut params: Vec<_> = module.params.iter().collect();
+        params.sort_by_key(|(name, _)| name.as_str());
+        for (param, value) in params {
+            args.push(format!("{}={}", param, format_value(value)));
+        }
+        match module.state {
+            ModuleState::Active => (),
+            ModuleState::Bypassed => args.push("state=bypassed".to_owned()),
+            ModuleState::Muted => args.push("state=muted".to_owned()),
+        }
+        if let Some(oversampling) = module.oversampling {
+            args.push(format!("oversampling={}", oversampling.factor()));
+        }
+        if let Some(position) = module.position {
+            args.push(format!("position=[{}, {}]", position.row, position.hp));
+        }
+        writeln!(out, "{} = {}({})", name, module_type, args.join(", ")).unwrap();
+        names.push(name);
+    }
+
+    if !patch.connections.is_empty() {
+        out.push('\n');
+    }
+    for (i, c) in patch.connections.iter().enumerate() {
+        let src = port(&names, &modules, i, c.src_index, c.src_channel, false)?;
+        let dst = port(&names, &modules, i, c.dst_index, c.dst_channel, true)?;
+        writeln!(out, "{} -> {}", src, dst).unwrap();
+    }
+    Ok(out)
+}
+
+/// Names one end of connection `connection`.
+fn port(
+    names: &[String],
+    modules: &[Option<Box<dyn Module>>],
+    connection: usize,
+    index: usize,
+    channel: usize,
+    is_input: bool,
+) -> Result<String, ValidationError> {
+    if index == names.len() {
+        // The audio output has a single input.
+        if !is_input || channel != 0 {
+            let direction = if is_input { "input" } else { "output" };
+            return Err(ValidationError::InvalidChannel(
+                connection, index, direction, channel,
+            ));
+        }
+        return Ok(AUDIO_OUTPUT.to_owned());
+    }
+    if index > names.len() {
+        return Err(ValidationError::InvalidModule(connection, index));
+    }
+    let channel_name = modules[index].as_ref().and_then(|module| {
+        let names = if is_input {
+            module.input_names()
+        } else {
+            module.output_names()
+        };
+        names.get(channel).copied()
+    });
+    Ok(match channel_name {
+        Some(channel) => format!("{}.{}", names[index], channel),
+        None => format!("{}.{}", names[index], channel),
+    })
+}
+
+fn format_value(value: &SerializedParameter) -> String {
+    match value {
+        SerializedParameter::Num(value) => value.to_string(),
+        SerializedParameter::List(values) => {
+            let values: Vec<_> = values.iter().map(format_value).collect();
+            format!("[{}]", values.join(", "))
+        }
+    }
+}
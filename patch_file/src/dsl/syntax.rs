@@ -0,0 +1,340 @@
+//! Tokenizing and parsing of text patches, before any names are resolved.
+
+use std::ops::Range;
+
+use super::DslError;
+
+pub(super) type Span = Range<usize>;
+
+#[derive(Clone, Debug)]
+pub(super) struct Spanned<T> {
+    pub(super) value: T,
+    pub(super) span: Span,
+}
+
+pub(super) struct Declaration {
+    pub(super) name: Spanned<String>,
+    pub(super) module_type: Spanned<String>,
+    pub(super) args: Vec<Argument>,
+}
+
+pub(super) struct Argument {
+    pub(super) name: Spanned<String>,
+    pub(super) value: Spanned<Value>,
+}
+
+#[derive(Clone, Debug)]
+pub(super) enum Value {
+    Num(f32),
+    List(Vec<Value>),
+    Ident(String),
+}
+
+pub(super) struct Connection {
+    pub(super) src: Port,
+    pub(super) dst: Port,
+}
+
+/// One end of a connection, e.g. `clock.trigger`, `vco.0` or `out`.
+pub(super) struct Port {
+    pub(super) module: Spanned<String>,
+    pub(super) channel: Option<Spanned<Channel>>,
+}
+
+impl Port {
+    pub(super) fn span(&self) -> Span {
+        match &self.channel {
+            Some(channel) => self.module.span.start..channel.span.end,
+            None => self.module.span.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(super) enum Channel {
+    Name(String),
+    Index(usize),
+}
+
+#[derive(Default)]
+pub(super) struct Statements {
+    pub(super) declarations: Vec<Declaration>,
+    pub(super) connections: Vec<Connection>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Num(f32),
+    Equals,
+    Comma,
+    Dot,
+    PathSeparator,
+    Arrow,
+    OpenParen,
+    CloseParen,
+    OpenBracket,
+    CloseBracket,
+    End,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Ident(name) => format!("'{}'", name),
+            Token::Num(value) => format!("'{}'", value),
+            Token::Equals => "'='".to_owned(),
+            Token::Comma => "','".to_owned(),
+            Token::Dot => "'.'".to_owned(),
+            Token::PathSeparator => "'::'".to_owned(),
+            Token::Arrow => "'->'".to_owned(),
+            Token::OpenParen => "'('".to_owned(),
+            Token::CloseParen => "')'".to_owned(),
+            Token::OpenBracket => "'['".to_owned(),
+            Token::CloseBracket => "']'".to_owned(),
+            Token::End => "end of file".to_owned(),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Spanned<Token>>, DslError> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let start = i;
+        let c = bytes[i] as char;
+        let token = match c {
+            _ if c.is_ascii_whitespace() => {
+                i += 1;
+                continue;
+            }
+            // Comments run to the end of the line.
+            '#' => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            '=' => Token::Equals,
+            ',' => Token::Comma,
+            '.' => Token::Dot,
+            '(' => Token::OpenParen,
+            ')' => Token::CloseParen,
+            '[' => Token::OpenBracket,
+            ']' => Token::CloseBracket,
+            ':' if bytes.get(i + 1) == Some(&b':') => {
+                i += 1;
+                Token::PathSeparator
+            }
+            '-' if bytes.get(i + 1) == Some(&b'>') => {
+                i += 1;
+                Token::Arrow
+            }
+            '-' | '0'..='9' => {
+                i += 1;
+                while i < bytes.len()
+                    && (bytes[i].is_ascii_digit()
+                        || bytes[i] == b'.'
+                        || bytes[i] == b'e'
+                        || (bytes[i] == b'-' && bytes[i - 1] == b'e'))
+                {
+                    i += 1;
+                }
+                let text = &source[start..i];
+                let value: f32 = text
+                    .parse()
+                    .map_err(|_| DslError::new(start..i, format!("invalid number '{}'", text)))?;
+                if !value.is_finite() {
+                    return Err(DslError::new(
+                        start..i,
+                        format!("'{}' is too large to be a number", text),
+                    ));
+                }
+                tokens.push(Spanned {
+                    value: Token::Num(value),
+                    span: start..i,
+                });
+                continue;
+            }
+            _ if c.is_ascii_alphabetic() || c == '_' => {
+                while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                    i += 1;
+                }
+                tokens.push(Spanned {
+                    value: Token::Ident(source[start..i].to_owned()),
+                    span: start..i,
+                });
+                continue;
+            }
+            _ => {
+                let len = source[i..].chars().next().map_or(1, char::len_utf8);
+                return Err(DslError::new(
+                    start..start + len,
+                    format!("unexpected character '{}'", &source[i..i + len]),
+                ));
+            }
+        };
+        i += 1;
+        tokens.push(Spanned {
+            value: token,
+            span: start..i,
+        });
+    }
+    tokens.push(Spanned {
+        value: Token::End,
+        span: source.len()..source.len(),
+    });
+    Ok(tokens)
+}
+
+pub(super) fn parse(source: &str) -> Result<Statements, DslError> {
+    let mut parser = Parser {
+        tokens: tokenize(source)?,
+        position: 0,
+    };
+    let mut statements = Statements::default();
+    while parser.peek().value != Token::End {
+        let first = parser.ident("a module name")?;
+        if parser.peek().value == Token::Equals {
+            parser.advance();
+            statements.declarations.push(parser.declaration(first)?);
+        } else {
+            let src = parser.port(first)?;
+            parser.expect(Token::Arrow, "'=' or '->'")?;
+            let module = parser.ident("a module name")?;
+            let dst = parser.port(module)?;
+            statements.connections.push(Connection { src, dst });
+        }
+    }
+    Ok(statements)
+}
+
+struct Parser {
+    tokens: Vec<Spanned<Token>>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Spanned<Token> {
+        &self.tokens[self.position]
+    }
+
+    fn advance(&mut self) -> Spanned<Token> {
+        let token = self.tokens[self.position].clone();
+        if token.value != Token::End {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn unexpected(&self, expected: &str) -> DslError {
+        let token = self.peek();
+        DslError::new(
+            token.span.clone(),
+            format!("expected {}, found {}", expected, token.value.describe()),
+        )
+    }
+
+    fn expect(&mut self, token: Token, expected: &str) -> Result<Span, DslError> {
+        if self.peek().value == token {
+            Ok(self.advance().span)
+        } else {
+            Err(self.unexpected(expected))
+        }
+    }
+
+    fn ident(&mut self, expected: &str) -> Result<Spanned<String>, DslError> {
+        match &self.peek().value {
+            Token::Ident(name) => {
+                let name = name.clone();
+                let span = self.advance().span;
+                Ok(Spanned { value: name, span })
+            }
+            _ => Err(self.unexpected(expected)),
+        }
+    }
+
+    /// Parses the rest of `name = Type(arg=value, ...)`.
+    fn declaration(&mut self, name: Spanned<String>) -> Result<Declaration, DslError> {
+        let mut module_type = self.ident("a module type")?;
+        while self.peek().value == Token::PathSeparator {
+            self.advance();
+            let part = self.ident("a module type")?;
+            module_type.value = format!("{}::{}", module_type.value, part.value);
+            module_type.span.end = part.span.end;
+        }
+
+        self.expect(Token::OpenParen, "'('")?;
+        let mut args = Vec::new();
+        while self.peek().value != Token::CloseParen {
+            let name = self.ident("a parameter name or ')'")?;
+            self.expect(Token::Equals, "'='")?;
+            let value = self.value()?;
+            args.push(Argument { name, value });
+            if self.peek().value != Token::CloseParen {
+                self.expect(Token::Comma, "',' or ')'")?;
+            }
+        }
+        self.advance();
+
+        Ok(Declaration {
+            name,
+            module_type,
+            args,
+        })
+    }
+
+    fn value(&mut self) -> Result<Spanned<Value>, DslError> {
+        let value = match &self.peek().value {
+            Token::Num(value) => Value::Num(*value),
+            Token::Ident(name) => Value::Ident(name.clone()),
+            Token::OpenBracket => {
+                let start = self.advance().span.start;
+                let mut values = Vec::new();
+                while self.peek().value != Token::CloseBracket {
+                    values.push(self.value()?.value);
+                    if self.peek().value != Token::CloseBracket {
+                        self.expect(Token::Comma, "',' or ']'")?;
+                    }
+                }
+                let end = self.advance().span.end;
+                return Ok(Spanned {
+                    value: Value::List(values),
+                    span: start..end,
+                });
+            }
+            _ => return Err(self.unexpected("a value")),
+        };
+        Ok(Spanned {
+            value,
+            span: self.advance().span,
+        })
+    }
+
+    /// Parses the rest of `module.channel`, or nothing for the audio output.
+    fn port(&mut self, module: Spanned<String>) -> Result<Port, DslError> {
+        if self.peek().value != Token::Dot {
+            return Ok(Port {
+                module,
+                channel: None,
+            });
+        }
+        self.advance();
+        let channel = match &self.peek().value {
+            Token::Ident(name) => Channel::Name(name.clone()),
+            Token::Num(value) if *value >= 0.0 && value.fract() == 0.0 => {
+                Channel::Index(*value as usize)
+            }
+            _ => return Err(self.unexpected("a channel name or number")),
+        };
+        let span = self.advance().span;
+        Ok(Port {
+            module,
+            channel: Some(Spanned {
+                value: channel,
+                span,
+            }),
+        })
+    }
+}
@@ -0,0 +1,239 @@
+//! The on-disk patch format, shared by the GUI and the command line tools.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::{BufReader, Read, Write},
+    path::Path,
+};
+
+use module::{
+    registry::{ModuleRegistry, RegistryError},
+    Module, ModuleInput, ModuleOutput, ModuleState, Oversampling, SerializedParameter,
+};
+use rack::{Rack, RackError};
+
+pub mod dsl;
+mod upgrade;
+mod validate;
+
+pub use validate::ValidationError;
+
+/// The version of the format written by this version of the code.
+pub const CURRENT_VERSION: u32 = 2;
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct SerializedPatch {
+    /// The version of the format that this patch was read from. Patches are always saved with
+    /// [`CURRENT_VERSION`]. Files from before versioning was introduced are version 0.
+    #[serde(default)]
+    pub version: u32,
+    pub modules: Vec<SerializedModule>,
+    /// Connections to the module index one past the last module go to the audio output.
+    pub connections: Vec<SerializedConnection>,
+}
+
+impl Default for SerializedPatch {
+    fn default() -> Self {
+        SerializedPatch {
+            version: CURRENT_VERSION,
+            modules: Vec::new(),
+            connections: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct SerializedModule {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "ModuleState::is_active")]
+    pub state: ModuleState,
+    /// Only set if it differs from the module's default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oversampling: Option<Oversampling>,
+    /// Where the module's panel sits in the rack. Modules without one are placed automatically.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<RackPosition>,
+    /// Written in order of name, so that saving the same patch always gives the same file.
+    #[serde(flatten, serialize_with = "sorted")]
+    pub params: HashMap<String, SerializedParameter>,
+}
+
+fn sorted<S: serde::Serializer>(
+    params: &HashMap<String, SerializedParameter>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serde::Serialize::serialize(&params.iter().collect::<BTreeMap<_, _>>(), serializer)
+}
+
+/// A position in the rack, as a row and a horizontal offset in HP from the start of the row.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct RackPosition {
+    pub row: usize,
+    pub hp: usize,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct SerializedConnection {
+    pub src_index: usize,
+    pub src_channel: usize,
+    pub dst_index: usize,
+    pub dst_channel: usize,
+    /// The cable's colour as RGB. Cables without one are coloured automatically.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<[u8; 3]>,
+}
+
+/// The form in which patches are written, which always carries the current version.
+#[derive(serde::Serialize)]
+struct VersionedPatch<'a> {
+    version: u32,
+    modules: &'a [SerializedModule],
+    connections: &'a [SerializedConnection],
+}
+
+impl SerializedPatch {
+    /// Reads a patch, upgrading it from older versions of the format as needed.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, PatchError> {
+        SerializedPatch::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    /// Reads a patch in either JSON or the text format, picking the text format by its
+    /// [extension](dsl::EXTENSION).
+    pub fn read<P: AsRef<Path>>(
+        path: P,
+        registry: &mut ModuleRegistry,
+    ) -> Result<Self, PatchError> {
+        let path = path.as_ref();
+        if is_text(path) {
+            let source = std::fs::read_to_string(path)?;
+            dsl::parse(&source, registry)
+                .map_err(|e| PatchError::Syntax(e.render(&source, &path.display().to_string())))
+        } else {
+            SerializedPatch::load(path)
+        }
+    }
+
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, PatchError> {
+        let mut value = serde_json::from_reader(reader)?;
+        let version = upgrade::upgrade(&mut value)?;
+        let mut patch: SerializedPatch = serde_json::from_value(value)?;
+        patch.version = version;
+        Ok(patch)
+    }
+
+    /// Writes a patch in either JSON or the text format, picking the format as in
+    /// [`SerializedPatch::read`].
+    pub fn write<P: AsRef<Path>>(
+        &self,
+        path: P,
+        registry: &mut ModuleRegistry,
+    ) -> Result<(), PatchError> {
+        let path = path.as_ref();
+        if is_text(path) {
+            let text = dsl::print(self, registry).map_err(PatchError::Invalid)?;
+            std::fs::write(path, text)?;
+            Ok(())
+        } else {
+            self.save(path)
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), PatchError> {
+        self.to_writer(File::create(path)?)
+    }
+
+    pub fn to_writer<W: Write>(&self, writer: W) -> Result<(), PatchError> {
+        serde_json::to_writer_pretty(
+            writer,
+            &VersionedPatch {
+                version: CURRENT_VERSION,
+                modules: &self.modules,
+                connections: &self.connections,
+            },
+        )?;
+        Ok(())
+    }
+
+    /// Checks that every module exists in the registry with matching parameters, and that every
+    /// connection refers to real channels.
+    pub fn validate(&self, registry: &mut ModuleRegistry) -> Vec<ValidationError> {
+        validate::validate(self, registry)
+    }
+
+    /// Builds a rack that plays the patch, without any device or UI. The patch is validated first,
+    /// as invalid parameters would otherwise panic.
+    pub fn build_rack(
+        &self,
+        registry: &mut ModuleRegistry,
+    ) -> Result<(Rack, Vec<Box<dyn Module>>), PatchError> {
+        if let Some(error) = self.validate(registry).into_iter().next() {
+            return Err(PatchError::Invalid(error));
+        }
+
+        let mut rack = Rack::new();
+        let mut handles = Vec::new();
+        let mut modules = Vec::new();
+        for serialized in &self.modules {
+            let (handle, module) = registry.create_module(&serialized.id)?;
+            if let Some(params) = module.params() {
+                params.deserialize(&serialized.params);
+            }
+            let oversampling = serialized
+                .oversampling
+                .unwrap_or_else(|| module.default_oversampling());
+            rack.add_audio_unit(
+                handle,
+                module.inputs(),
+                module.outputs(),
+                module.bypass_routes(),
+                oversampling.wrap(
+                    module.create_audio_unit(),
+                    module.inputs(),
+                    module.outputs(),
+                ),
+            );
+            rack.set_module_state(handle, serialized.state)?;
+            handles.push(handle);
+            modules.push(module);
+        }
+        handles.push(rack::AUDIO_OUTPUT_HANDLE);
+
+        for connection in &self.connections {
+            rack.connect(
+                ModuleOutput {
+                    module: handles[connection.src_index],
+                    channel: connection.src_channel,
+                },
+                ModuleInput {
+                    module: handles[connection.dst_index],
+                    channel: connection.dst_channel,
+                },
+            )?;
+        }
+        Ok((rack, modules))
+    }
+}
+
+fn is_text(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == dsl::EXTENSION)
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum PatchError {
+    #[error("couldn't access patch file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("malformed patch file: {0}")]
+    Json(#[from] serde_json::Error),
+    /// A rendered error from a text patch.
+    #[error("{0}")]
+    Syntax(String),
+    #[error("patch file version {0} is newer than this program supports")]
+    UnsupportedVersion(u32),
+    #[error("invalid patch: {0}")]
+    Invalid(ValidationError),
+    #[error(transparent)]
+    Registry(#[from] RegistryError),
+    #[error(transparent)]
+    Rack(#[from] RackError),
+}
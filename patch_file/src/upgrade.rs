@@ -0,0 +1,50 @@
+use serde_json::Value;
+
+use crate::{PatchError, CURRENT_VERSION};
+
+/// A migration from the version at its index in [`MIGRATIONS`] to the next version.
+type Migration = fn(&mut Value) -> Result<(), PatchError>;
+
+const MIGRATIONS: [Migration; CURRENT_VERSION as usize] = [
+    // Version 0 files predate the version field, but are otherwise the same as version 1.
+    |_| Ok(()),
+    rescale_vcf_cutoff_cv,
+];
+
+/// The VCF's cutoff CV input used to span a sixth of the sample rate, and now spans its full
+/// cutoff range of 20kHz. Its attenuverter is scaled down to match, as if the patch had been
+/// played at 48kHz.
+fn rescale_vcf_cutoff_cv(value: &mut Value) -> Result<(), PatchError> {
+    const SCALE: f64 = 48_000.0 / 6.0 / 20_000.0;
+    let modules = value
+        .get_mut("modules")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten();
+    for module in modules {
+        if module.get("id").and_then(Value::as_str) != Some("builtins::Vcf") {
+            continue;
+        }
+        if let Some(atten) = module.get_mut("cutoff_atten") {
+            if let Some(old) = atten.as_f64() {
+                *atten = Value::from(old * SCALE);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Migrates a parsed patch file to the current version, returning the version it was written with.
+pub(crate) fn upgrade(value: &mut Value) -> Result<u32, PatchError> {
+    let version = match value.get("version") {
+        Some(version) => serde_json::from_value(version.clone())?,
+        None => 0,
+    };
+    if version > CURRENT_VERSION {
+        return Err(PatchError::UnsupportedVersion(version));
+    }
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(value)?;
+    }
+    Ok(version)
+}
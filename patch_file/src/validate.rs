@@ -0,0 +1,111 @@
+use std::collections::HashSet;
+
+use module::{registry::ModuleRegistry, SerializedParameter};
+
+use crate::SerializedPatch;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ValidationError {
+    #[error("module {0}: no module with id '{1}' exists")]
+    UnknownModule(usize, String),
+    #[error("module {0}: missing parameter '{1}'")]
+    MissingParameter(usize, String),
+    #[error("module {0}: unknown parameter '{1}'")]
+    UnknownParameter(usize, String),
+    #[error("module {0}: parameter '{1}' doesn't match the module's parameter")]
+    MismatchedParameter(usize, String),
+    #[error("connection {0}: module {1} doesn't exist")]
+    InvalidModule(usize, usize),
+    #[error("connection {0}: module {1} has no {2} channel {3}")]
+    InvalidChannel(usize, usize, &'static str, usize),
+    #[error("connection {0}: input channel is already connected")]
+    DuplicateInput(usize),
+}
+
+pub(crate) fn validate(
+    patch: &SerializedPatch,
+    registry: &mut ModuleRegistry,
+) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+
+    // Check each module, remembering its channel counts for checking connections.
+    let mut channels = Vec::new();
+    for (i, serialized) in patch.modules.iter().enumerate() {
+        let module = match registry.create_module(&serialized.id) {
+            Ok((_, module)) => module,
+            Err(_) => {
+                errors.push(ValidationError::UnknownModule(i, serialized.id.clone()));
+                channels.push(None);
+                continue;
+            }
+        };
+        channels.push(Some((module.inputs(), module.outputs())));
+
+        let expected = module
+            .params()
+            .map(|params| params.serialize())
+            .unwrap_or_default();
+        let mut names: Vec<_> = expected.keys().chain(serialized.params.keys()).collect();
+        names.sort();
+        names.dedup();
+        for name in names {
+            match (expected.get(name), serialized.params.get(name)) {
+                (Some(_), None) => {
+                    errors.push(ValidationError::MissingParameter(i, name.clone()));
+                }
+                (None, Some(_)) => {
+                    errors.push(ValidationError::UnknownParameter(i, name.clone()));
+                }
+                (Some(expected), Some(actual)) if !same_shape(expected, actual) => {
+                    errors.push(ValidationError::MismatchedParameter(i, name.clone()));
+                }
+                _ => (),
+            }
+        }
+    }
+    // The audio output has a single input.
+    channels.push(Some((1, 0)));
+
+    let mut connected_inputs = HashSet::new();
+    for (i, connection) in patch.connections.iter().enumerate() {
+        let ends = [
+            (connection.src_index, connection.src_channel, "output"),
+            (connection.dst_index, connection.dst_channel, "input"),
+        ];
+        for (module, channel, direction) in ends {
+            match channels.get(module) {
+                None => errors.push(ValidationError::InvalidModule(i, module)),
+                Some(Some((inputs, outputs))) => {
+                    let count = if direction == "input" {
+                        inputs
+                    } else {
+                        outputs
+                    };
+                    if channel >= *count {
+                        errors.push(ValidationError::InvalidChannel(
+                            i, module, direction, channel,
+                        ));
+                    }
+                }
+                // Unknown modules have already been reported.
+                Some(None) => (),
+            }
+        }
+        if !connected_inputs.insert((connection.dst_index, connection.dst_channel)) {
+            errors.push(ValidationError::DuplicateInput(i));
+        }
+    }
+    errors
+}
+
+/// Whether a serialized parameter could be deserialized in place of the `expected` one.
+pub(crate) fn same_shape(expected: &SerializedParameter, actual: &SerializedParameter) -> bool {
+    match (expected, actual) {
+        (SerializedParameter::Num(_), SerializedParameter::Num(_)) => true,
+        (SerializedParameter::List(expected), SerializedParameter::List(actual)) => {
+            expected.len() == actual.len()
+                && expected.iter().zip(actual).all(|(e, a)| same_shape(e, a))
+        }
+        _ => false,
+    }
+}
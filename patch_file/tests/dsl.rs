@@ -0,0 +1,266 @@
+use std::{fs, path::PathBuf};
+
+use module::registry::ModuleRegistry;
+use patch_file::{
+    dsl::{self, DslError},
+    SerializedConnection, SerializedPatch, ValidationError,
+};
+
+fn example_patches() -> Vec<PathBuf> {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../patches");
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|e| e == "json"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+    paths
+}
+
+fn ends(connection: &SerializedConnection) -> (usize, usize, usize, usize) {
+    (
+        connection.src_index,
+        connection.src_channel,
+        connection.dst_index,
+        connection.dst_channel,
+    )
+}
+
+#[test]
+fn example_patches_round_trip() {
+    let mut registry = modules::builtin_modules();
+    for path in example_patches() {
+        let patch = SerializedPatch::load(&path).unwrap();
+        let text = dsl::print(&patch, &mut registry).unwrap();
+        let parsed = dsl::parse(&text, &mut registry)
+            .unwrap_or_else(|e| panic!("{}", e.render(&text, &path.display().to_string())));
+
+        assert_eq!(parsed.modules.len(), patch.modules.len(), "{:?}", path);
+        for (parsed, original) in parsed.modules.iter().zip(&patch.modules) {
+            assert_eq!(parsed.id, original.id);
+            assert_eq!(parsed.state, original.state);
+            assert_eq!(parsed.oversampling, original.oversampling);
+            assert_eq!(parsed.position, original.position);
+            // Parameters missing from the file take their defaults.
+            for (name, value) in &original.params {
+                assert_eq!(parsed.params.get(name), Some(value), "{}", name);
+            }
+        }
+        let parsed_ends: Vec<_> = parsed.connections.iter().map(ends).collect();
+        let original_ends: Vec<_> = patch.connections.iter().map(ends).collect();
+        assert_eq!(parsed_ends, original_ends, "{:?}", path);
+
+        assert_eq!(dsl::print(&parsed, &mut registry).unwrap(), text);
+    }
+}
+
+#[test]
+fn prints_names_and_arguments() {
+    let mut registry = modules::builtin_modules();
+    let source = "\
+clock = Clock(bpm=90, pulse_width=0.25, state=muted, position=[1, 4])
+vco = Vco()
+vco2 = Vco(oversampling=4)
+vca = Vca(gain=0.5, gain_atten=1)
+
+clock.trigger -> vco.v_oct
+vco.saw -> vca.audio
+vco2.0 -> vca.1
+vca.audio -> out
+";
+    let patch = dsl::parse(source, &mut registry).unwrap();
+    let printed = dsl::print(&patch, &mut registry).unwrap();
+    assert_eq!(
+        printed,
+        "\
+clock = Clock(bpm=90, pulse_width=0.25, state=muted, position=[1, 4])
+vco = Vco()
+vco2 = Vco(oversampling=4)
+vca = Vca(gain=0.5, gain_atten=1)
+
+clock.trigger -> vco.v_oct
+vco.saw -> vca.audio
+vco2.saw -> vca.cv
+vca.audio -> out
+"
+    );
+}
+
+#[test]
+fn printing_refuses_missing_modules() {
+    let mut registry = modules::builtin_modules();
+    let mut patch = dsl::parse("vco = Vco()\nvco.saw -> out", &mut registry).unwrap();
+    patch.connections[0].dst_index = 2;
+    assert!(matches!(
+        dsl::print(&patch, &mut registry),
+        Err(ValidationError::InvalidModule(0, 2))
+    ));
+
+    // The audio output can't be connected from.
+    patch.connections[0].src_index = 1;
+    patch.connections[0].dst_index = 0;
+    assert!(matches!(
+        dsl::print(&patch, &mut registry),
+        Err(ValidationError::InvalidChannel(0, 1, "output", 0))
+    ));
+}
+
+fn parse_error(source: &str) -> DslError {
+    let mut registry: ModuleRegistry = modules::builtin_modules();
+    match dsl::parse(source, &mut registry) {
+        Ok(_) => panic!("{:?} parsed", source),
+        Err(e) => e,
+    }
+}
+
+/// Checks an error's message, and the source it points at.
+fn assert_error(source: &str, message: &str, at: &str) {
+    let error = parse_error(source);
+    assert_eq!(error.message, message, "{:?}", source);
+    assert_eq!(&source[error.span.clone()], at, "{:?}", source);
+}
+
+#[test]
+fn tokenizer_errors() {
+    assert_error("a = Clock() $", "unexpected character '$'", "$");
+    assert_error("a = Clock() é", "unexpected character 'é'", "é");
+    assert_error("a = Clock(bpm=1.2.3)", "invalid number '1.2.3'", "1.2.3");
+    assert_error(
+        "a = Clock(bpm=1e999)",
+        "'1e999' is too large to be a number",
+        "1e999",
+    );
+    assert_error(
+        "a = Clock(bpm=-1e39)",
+        "'-1e39' is too large to be a number",
+        "-1e39",
+    );
+}
+
+#[test]
+fn parser_errors() {
+    assert_error("a = Clock(bpm 120)", "expected '=', found '120'", "120");
+    assert_error(
+        "a = Clock(bpm=120 pulse_width=0.5)",
+        "expected ',' or ')', found 'pulse_width'",
+        "pulse_width",
+    );
+    assert_error("a = (bpm=120)", "expected a module type, found '('", "(");
+    assert_error(
+        "a = Clock(bpm=[1, 2)",
+        "expected ',' or ']', found ')'",
+        ")",
+    );
+    assert_error(
+        "a = Clock(",
+        "expected a parameter name or ')', found end of file",
+        "",
+    );
+    assert_error("a.0 b.0", "expected '=' or '->', found 'b'", "b");
+    assert_error(
+        "a.x -> b.-1",
+        "expected a channel name or number, found '-1'",
+        "-1",
+    );
+    assert_eq!(parse_error("a = Clock(").span, 10..10);
+}
+
+#[test]
+fn resolution_errors() {
+    assert_error("a = Nope()", "no module type 'Nope' exists", "Nope");
+    assert_error(
+        "out = Vco()",
+        "'out' is reserved for the audio output",
+        "out",
+    );
+    assert_error(
+        "a = Vco()\na = Vco()",
+        "a module named 'a' already exists",
+        "a",
+    );
+    assert_eq!(parse_error("a = Vco()\na = Vco()").span, 10..11);
+    assert_error(
+        "a = Clock(tempo=120)",
+        "'builtins::Clock' has no parameter 'tempo'",
+        "tempo",
+    );
+    assert_error(
+        "a = Clock(bpm=[1, 2])",
+        "expected a number for 'bpm'",
+        "[1, 2]",
+    );
+    assert_error(
+        "a = Clock(state=paused)",
+        "expected 'active', 'bypassed' or 'muted' for 'state'",
+        "paused",
+    );
+    assert_error(
+        "a = Vco(oversampling=3)",
+        "expected 1, 2, 4 or 8 for 'oversampling'",
+        "3",
+    );
+    assert_error(
+        "a = Vco(position=[1])",
+        "expected [row, hp] for 'position'",
+        "[1]",
+    );
+    assert_error(
+        "a = Vco()\na.sine -> out",
+        "'a' has no output 'sine' (expected one of: saw, square, triangle)",
+        "sine",
+    );
+    assert_error(
+        "a = Vco()\na.saw -> a.3",
+        "'a' has no input '3' (expected one of: v_oct)",
+        "3",
+    );
+    assert_error(
+        "a = Vco()\na -> out",
+        "expected an output channel, e.g. 'a.0'",
+        "a",
+    );
+    assert_error("a = Vco()\na.saw -> b.0", "no module named 'b' exists", "b");
+    assert_error(
+        "a = Vco()\nout -> a.0",
+        "the audio output can't be connected from",
+        "out",
+    );
+    assert_error(
+        "a = Vco()\na.0 -> out.0",
+        "the audio output has no channels",
+        "0",
+    );
+    assert_error(
+        "a = Vco()\na.saw -> out\na.square -> out",
+        "this input is already connected",
+        "out",
+    );
+}
+
+#[test]
+fn renders_errors_with_source() {
+    let source = "clock = Clock()\nvco = Vco()\n\nclock.trigger -> vco.pitch\n";
+    let error = parse_error(source);
+    assert_eq!(
+        error.render(source, "test.patch"),
+        "\
+error: 'vco' has no input 'pitch' (expected one of: v_oct)
+ --> test.patch:4:22
+  |
+4 | clock.trigger -> vco.pitch
+  |                      ^^^^^"
+    );
+
+    // Errors at the end of the file still get a marker.
+    let source = "clock = Clock(";
+    assert_eq!(
+        parse_error(source).render(source, "test.patch"),
+        "\
+error: expected a parameter name or ')', found end of file
+ --> test.patch:1:15
+  |
+1 | clock = Clock(
+  |               ^"
+    );
+}
@@ -0,0 +1,93 @@
+use module::SerializedParameter;
+use patch_file::{PatchError, SerializedPatch, CURRENT_VERSION};
+
+fn read(json: &str) -> Result<SerializedPatch, PatchError> {
+    SerializedPatch::from_reader(json.as_bytes())
+}
+
+#[test]
+fn rescales_vcf_cutoff_cv() {
+    let patch = read(
+        r#"{
+            "version": 1,
+            "modules": [
+                {"id": "builtins::Vcf", "cutoff": 1000.0, "cutoff_atten": 1.0},
+                {"id": "builtins::Vcf", "cutoff": 1000.0, "cutoff_atten": -0.5},
+                {"id": "builtins::Vca", "gain": 1.0, "gain_atten": 1.0}
+            ],
+            "connections": []
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(patch.version, 1);
+    let param = |module: usize, name: &str| patch.modules[module].params[name].clone();
+    assert_eq!(param(0, "cutoff_atten"), SerializedParameter::Num(0.4));
+    assert_eq!(param(1, "cutoff_atten"), SerializedParameter::Num(-0.2));
+    assert_eq!(param(0, "cutoff"), SerializedParameter::Num(1000.0));
+    assert_eq!(param(2, "gain_atten"), SerializedParameter::Num(1.0));
+}
+
+#[test]
+fn leaves_current_patches_alone() {
+    let patch = read(&format!(
+        r#"{{
+            "version": {},
+            "modules": [{{"id": "builtins::Vcf", "cutoff_atten": 1.0}}],
+            "connections": []
+        }}"#,
+        CURRENT_VERSION
+    ))
+    .unwrap();
+    assert_eq!(patch.version, CURRENT_VERSION);
+    assert_eq!(
+        patch.modules[0].params["cutoff_atten"],
+        SerializedParameter::Num(1.0)
+    );
+}
+
+#[test]
+fn upgrades_unversioned_patches() {
+    let patch =
+        read(r#"{"modules": [{"id": "builtins::Vcf", "cutoff_atten": 1.0}], "connections": []}"#)
+            .unwrap();
+    assert_eq!(patch.version, 0);
+    assert_eq!(
+        patch.modules[0].params["cutoff_atten"],
+        SerializedParameter::Num(0.4)
+    );
+}
+
+#[test]
+fn refuses_newer_patches() {
+    let newer = format!(
+        r#"{{"version": {}, "modules": [], "connections": []}}"#,
+        CURRENT_VERSION + 1
+    );
+    assert!(matches!(
+        read(&newer),
+        Err(PatchError::UnsupportedVersion(v)) if v == CURRENT_VERSION + 1
+    ));
+}
+
+#[test]
+fn writes_params_in_order_of_name() {
+    let patch = read(
+        r#"{
+            "modules": [{"id": "builtins::Adsr", "release": 0.5, "decay": 0.1, "sustain": 0.8, "attack": 0.005}],
+            "connections": []
+        }"#,
+    )
+    .unwrap();
+    let mut json = Vec::new();
+    patch.to_writer(&mut json).unwrap();
+    let json = String::from_utf8(json).unwrap();
+    let positions: Vec<usize> = ["attack", "decay", "release", "sustain"]
+        .iter()
+        .map(|name| json.find(name).unwrap())
+        .collect();
+    assert!(
+        positions.windows(2).all(|pair| pair[0] < pair[1]),
+        "{}",
+        json
+    );
+}
@@ -0,0 +1,404 @@
+//! The interface between the rack and modules loaded from shared libraries.
+//!
+//! A plugin is a `cdylib` that exports two functions: `oxcable_plugin_abi_version`, returning the
+//! [`ABI_VERSION`] it was built against, and `oxcable_plugin_descriptor`, returning a
+//! [`PluginDescriptor`] that lists its modules. The rack only reads the descriptor if the
+//! versions match, so the layout of everything else may change between versions.
+//!
+//! Plugins written in Rust implement [`Unit`] for each module and use [`export_plugin!`] to
+//! define both functions:
+//!
+//! ```ignore
+//! plugin_api::export_plugin!(vec![
+//!     ModuleExport::new::<Folder>("mypack::Folder", "Folder")
+//!         .inputs(&["in"])
+//!         .outputs(&["out"])
+//!         .param("drive", "Drive", 1.0..=10.0, 1.0),
+//! ]);
+//! ```
+//!
+//! Plugin modules have continuous parameters only, and the rack lays out their panels.
+//!
+//! Panics in a [`Unit`] are caught before they reach the rack, which mutes the module until it's
+//! reset. Plugins must be built to unwind on panic for this to work.
+
+use std::{
+    ffi::{c_char, c_void, CString},
+    ops::RangeInclusive,
+    panic::{self, AssertUnwindSafe},
+    ptr,
+};
+
+/// Bumped whenever any type in this crate changes layout.
+pub const ABI_VERSION: u32 = 3;
+
+/// The names of the functions a plugin exports, with a nul terminator.
+pub const ABI_VERSION_SYMBOL: &[u8] = b"oxcable_plugin_abi_version\0";
+pub const DESCRIPTOR_SYMBOL: &[u8] = b"oxcable_plugin_descriptor\0";
+
+pub type AbiVersionFn = unsafe extern "C" fn() -> u32;
+pub type DescriptorFn = unsafe extern "C" fn() -> *const PluginDescriptor;
+
+/// The categories a module can be listed under, in the order the rack shows them.
+pub mod category {
+    pub const OSCILLATOR: u32 = 0;
+    pub const FILTER: u32 = 1;
+    pub const AMPLIFIER: u32 = 2;
+    pub const ENVELOPE: u32 = 3;
+    pub const MODULATION: u32 = 4;
+    pub const SEQUENCING: u32 = 5;
+    pub const INPUT_OUTPUT: u32 = 6;
+    pub const UTILITY: u32 = 7;
+}
+
+/// What the functions in a [`ModuleVTable`] that run a module's code return.
+pub mod status {
+    pub const OK: u32 = 0;
+    /// The module panicked, and may have been left inconsistent.
+    pub const PANICKED: u32 = 1;
+}
+
+/// Every module in a plugin. The descriptor and everything it points to must live as long as the
+/// library is loaded.
+#[repr(C)]
+pub struct PluginDescriptor {
+    pub modules: *const ModuleDescriptor,
+    pub module_count: usize,
+}
+
+/// Describes a module. Strings are nul terminated UTF-8.
+#[repr(C)]
+pub struct ModuleDescriptor {
+    /// Identifies the module in saved patches, and so must never change. By convention, this is
+    /// prefixed with the plugin's name, as in `mypack::Folder`.
+    pub id: *const c_char,
+    pub name: *const c_char,
+    pub author: *const c_char,
+    pub description: *const c_char,
+    pub tags: *const *const c_char,
+    pub tag_count: usize,
+    /// One of the [`category`] constants.
+    pub category: u32,
+    /// The width of the module's panel, in horizontal pitch units.
+    pub hp: u32,
+    pub input_names: *const *const c_char,
+    pub inputs: usize,
+    pub output_names: *const *const c_char,
+    pub outputs: usize,
+    pub params: *const ParamDescriptor,
+    pub param_count: usize,
+    pub vtable: ModuleVTable,
+}
+
+#[repr(C)]
+pub struct ParamDescriptor {
+    /// Identifies the parameter in saved patches.
+    pub name: *const c_char,
+    pub label: *const c_char,
+    pub min: f32,
+    pub max: f32,
+    pub default: f32,
+}
+
+/// The functions the rack calls on a module's instances.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct ModuleVTable {
+    /// Creates an instance, which is passed to the other functions, for ticking with
+    /// `input_count` inputs. Returns null if the module couldn't be created.
+    pub create: unsafe extern "C" fn(input_count: usize) -> *mut c_void,
+    pub destroy: unsafe extern "C" fn(instance: *mut c_void),
+    /// Returns one of the [`status`] constants.
+    pub reset: unsafe extern "C" fn(instance: *mut c_void, sample_rate: u32) -> u32,
+    /// Processes one sample. `inputs` and `connected` hold a value for each input, `params` the
+    /// current value of each parameter, and `outputs` a value for each output to be written.
+    /// Returns one of the [`status`] constants.
+    pub tick: unsafe extern "C" fn(
+        instance: *mut c_void,
+        inputs: *const f32,
+        connected: *const bool,
+        input_count: usize,
+        params: *const f32,
+        param_count: usize,
+        outputs: *mut f32,
+        output_count: usize,
+    ) -> u32,
+}
+
+/// The processing for a module written in Rust. The rack creates one for each instance of the
+/// module, and ticks it on the audio thread.
+pub trait Unit: Default + 'static {
+    fn reset(&mut self, sample_rate: u32);
+    /// Processes one sample. Disconnected inputs are `None`, and parameters are given in the
+    /// order they were declared.
+    fn tick(&mut self, inputs: &[Option<f32>], params: &[f32], outputs: &mut [f32]);
+}
+
+/// A module for [`export_plugin!`] to export, built up from its metadata.
+pub struct ModuleExport {
+    id: CString,
+    name: CString,
+    author: CString,
+    description: CString,
+    tags: Vec<CString>,
+    category: u32,
+    hp: u32,
+    inputs: Vec<CString>,
+    outputs: Vec<CString>,
+    params: Vec<(CString, CString, RangeInclusive<f32>, f32)>,
+    vtable: ModuleVTable,
+}
+
+impl ModuleExport {
+    pub fn new<U: Unit>(id: &str, name: &str) -> Self {
+        ModuleExport {
+            id: c_string(id),
+            name: c_string(name),
+            author: c_string(""),
+            description: c_string(""),
+            tags: Vec::new(),
+            category: category::UTILITY,
+            hp: 4,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            params: Vec::new(),
+            vtable: ModuleVTable {
+                create: create::<U>,
+                destroy: destroy::<U>,
+                reset: reset::<U>,
+                tick: tick::<U>,
+            },
+        }
+    }
+
+    pub fn author(mut self, author: &str) -> Self {
+        self.author = c_string(author);
+        self
+    }
+
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = c_string(description);
+        self
+    }
+
+    pub fn tags(mut self, tags: &[&str]) -> Self {
+        self.tags = tags.iter().map(|tag| c_string(tag)).collect();
+        self
+    }
+
+    /// One of the [`category`] constants.
+    pub fn category(mut self, category: u32) -> Self {
+        self.category = category;
+        self
+    }
+
+    pub fn hp(mut self, hp: u32) -> Self {
+        self.hp = hp;
+        self
+    }
+
+    /// Names each input, and sets how many there are.
+    pub fn inputs(mut self, names: &[&str]) -> Self {
+        self.inputs = names.iter().map(|name| c_string(name)).collect();
+        self
+    }
+
+    /// Names each output, and sets how many there are.
+    pub fn outputs(mut self, names: &[&str]) -> Self {
+        self.outputs = names.iter().map(|name| c_string(name)).collect();
+        self
+    }
+
+    /// Adds a parameter, which is passed to [`Unit::tick`] after those added before it.
+    pub fn param(
+        mut self,
+        name: &str,
+        label: &str,
+        range: RangeInclusive<f32>,
+        default: f32,
+    ) -> Self {
+        self.params
+            .push((c_string(name), c_string(label), range, default));
+        self
+    }
+}
+
+/// Every module exported by a plugin, along with the descriptors pointing into them. Created
+/// once by [`export_plugin!`], and never changed after.
+pub struct Plugin {
+    _modules: Vec<ModuleExport>,
+    /// The pointer arrays that descriptors refer to.
+    _strings: Vec<Vec<*const c_char>>,
+    _params: Vec<Vec<ParamDescriptor>>,
+    descriptors: Vec<ModuleDescriptor>,
+    descriptor: PluginDescriptor,
+}
+
+// Safety: the raw pointers only refer to data owned by the plugin, which is never changed.
+unsafe impl Send for Plugin {}
+unsafe impl Sync for Plugin {}
+
+impl Plugin {
+    pub fn new(modules: Vec<ModuleExport>) -> Self {
+        let mut strings = Vec::new();
+        let mut params = Vec::new();
+        let mut descriptors = Vec::new();
+        for module in &modules {
+            let mut pointers = |list: &[CString]| -> *const *const c_char {
+                let pointers: Vec<_> = list.iter().map(|s| s.as_ptr()).collect();
+                // The vector's buffer doesn't move when the vector is.
+                let ptr = pointers.as_ptr();
+                strings.push(pointers);
+                ptr
+            };
+            let tags = pointers(&module.tags);
+            let input_names = pointers(&module.inputs);
+            let output_names = pointers(&module.outputs);
+            let module_params: Vec<_> = module
+                .params
+                .iter()
+                .map(|(name, label, range, default)| ParamDescriptor {
+                    name: name.as_ptr(),
+                    label: label.as_ptr(),
+                    min: *range.start(),
+                    max: *range.end(),
+                    default: *default,
+                })
+                .collect();
+            descriptors.push(ModuleDescriptor {
+                id: module.id.as_ptr(),
+                name: module.name.as_ptr(),
+                author: module.author.as_ptr(),
+                description: module.description.as_ptr(),
+                tags,
+                tag_count: module.tags.len(),
+                category: module.category,
+                hp: module.hp,
+                input_names,
+                inputs: module.inputs.len(),
+                output_names,
+                outputs: module.outputs.len(),
+                params: module_params.as_ptr(),
+                param_count: module_params.len(),
+                vtable: module.vtable,
+            });
+            params.push(module_params);
+        }
+        let descriptor = PluginDescriptor {
+            modules: descriptors.as_ptr(),
+            module_count: descriptors.len(),
+        };
+        Plugin {
+            _modules: modules,
+            _strings: strings,
+            _params: params,
+            descriptors,
+            descriptor,
+        }
+    }
+
+    pub fn descriptor(&self) -> *const PluginDescriptor {
+        debug_assert_eq!(self.descriptor.modules, self.descriptors.as_ptr());
+        &self.descriptor
+    }
+}
+
+/// Defines the functions a plugin exports, given an expression listing its [`ModuleExport`]s.
+/// The expression is evaluated once, the first time the rack asks for the plugin's modules.
+#[macro_export]
+macro_rules! export_plugin {
+    ($modules:expr) => {
+        #[no_mangle]
+        pub extern "C" fn oxcable_plugin_abi_version() -> u32 {
+            $crate::ABI_VERSION
+        }
+
+        #[no_mangle]
+        pub extern "C" fn oxcable_plugin_descriptor() -> *const $crate::PluginDescriptor {
+            static PLUGIN: ::std::sync::OnceLock<$crate::Plugin> = ::std::sync::OnceLock::new();
+            PLUGIN
+                .get_or_init(|| $crate::Plugin::new($modules))
+                .descriptor()
+        }
+    };
+}
+
+/// A unit along with room to unpack its inputs, so that ticking doesn't allocate.
+struct Instance<U> {
+    unit: U,
+    inputs: Vec<Option<f32>>,
+}
+
+fn c_string(s: &str) -> CString {
+    CString::new(s.replace('\0', "")).unwrap()
+}
+
+/// Runs a unit's code, stopping any panic before it unwinds into the rack.
+fn guard(f: impl FnOnce()) -> u32 {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(()) => status::OK,
+        Err(_) => status::PANICKED,
+    }
+}
+
+unsafe extern "C" fn create<U: Unit>(input_count: usize) -> *mut c_void {
+    match panic::catch_unwind(U::default) {
+        Ok(unit) => {
+            let instance = Instance {
+                unit,
+                inputs: Vec::with_capacity(input_count),
+            };
+            Box::into_raw(Box::new(instance)) as *mut c_void
+        }
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+unsafe extern "C" fn destroy<U: Unit>(instance: *mut c_void) {
+    // There's no one to report a panic in a destructor to, so it's only stopped.
+    guard(|| drop(Box::from_raw(instance as *mut Instance<U>)));
+}
+
+unsafe extern "C" fn reset<U: Unit>(instance: *mut c_void, sample_rate: u32) -> u32 {
+    let instance = &mut *(instance as *mut Instance<U>);
+    guard(|| instance.unit.reset(sample_rate))
+}
+
+#[allow(clippy::too_many_arguments)]
+unsafe extern "C" fn tick<U: Unit>(
+    instance: *mut c_void,
+    inputs: *const f32,
+    connected: *const bool,
+    input_count: usize,
+    params: *const f32,
+    param_count: usize,
+    outputs: *mut f32,
+    output_count: usize,
+) -> u32 {
+    let instance = &mut *(instance as *mut Instance<U>);
+    let values = slice(inputs, input_count);
+    let connected = slice(connected, input_count);
+    instance.inputs.clear();
+    instance.inputs.extend(
+        values
+            .iter()
+            .zip(connected)
+            .map(|(&v, &connected)| Some(v).filter(|_| connected)),
+    );
+    let params = slice(params, param_count);
+    let outputs = if output_count == 0 {
+        &mut []
+    } else {
+        std::slice::from_raw_parts_mut(outputs, output_count)
+    };
+    guard(|| instance.unit.tick(&instance.inputs, params, outputs))
+}
+
+/// Views an array passed by the rack, which may be null if it's empty.
+unsafe fn slice<'a, T>(ptr: *const T, len: usize) -> &'a [T] {
+    if len == 0 {
+        &[]
+    } else {
+        std::slice::from_raw_parts(ptr, len)
+    }
+}
@@ -0,0 +1,339 @@
+//! Loads modules from plugins: shared libraries built against [`plugin_api`].
+
+use std::{
+    ffi::{c_char, c_void, CStr},
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use eurorack::Voltage;
+use libloading::Library;
+use module::{
+    parameters::{NamedParameter, ParameterSet},
+    registry::{Category, ModuleManifest, ModuleRegistry, RegistryError},
+    AudioUnit, Module, ModuleFault, Panel, Parameters,
+};
+use plugin_api::{
+    status, AbiVersionFn, DescriptorFn, ModuleDescriptor, ModuleVTable, ABI_VERSION,
+    ABI_VERSION_SYMBOL, DESCRIPTOR_SYMBOL,
+};
+use widgets::panel::GenericPanel;
+
+#[derive(thiserror::Error, Debug)]
+pub enum PluginError {
+    #[error("couldn't read the plugins directory: {0}")]
+    Io(#[from] io::Error),
+    #[error("couldn't load the library: {0}")]
+    Load(#[from] libloading::Error),
+    #[error("the plugin was built for version {found} of the plugin interface, not {expected}")]
+    IncompatibleVersion { found: u32, expected: u32 },
+    #[error("the plugin's descriptor is invalid: {0}")]
+    InvalidDescriptor(String),
+    #[error("{0}")]
+    Registry(#[from] RegistryError),
+}
+
+/// The directory plugins are loaded from by default: `plugins`, next to the executable.
+pub fn default_dir() -> Option<PathBuf> {
+    let exe = std::env::current_exe().ok()?;
+    Some(exe.parent()?.join("plugins"))
+}
+
+/// Loads every plugin in a directory, registering their modules. Returns an error for each
+/// plugin that couldn't be loaded, without stopping the others. A missing directory has no
+/// plugins.
+pub fn load_dir(registry: &mut ModuleRegistry, dir: &Path) -> Vec<(PathBuf, PluginError)> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Vec::new(),
+        Err(e) => return vec![(dir.to_owned(), e.into())],
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.extension() == Some(std::env::consts::DLL_EXTENSION.as_ref()))
+        .collect();
+    paths.sort();
+    paths
+        .into_iter()
+        .filter_map(|path| match load(registry, &path) {
+            Ok(_) => None,
+            Err(e) => Some((path, e)),
+        })
+        .collect()
+}
+
+/// Loads a plugin, registering its modules. Returns the ids of the modules registered. Nothing is
+/// registered if any of the plugin's ids are already taken.
+pub fn load(registry: &mut ModuleRegistry, path: &Path) -> Result<Vec<String>, PluginError> {
+    // Safety: loading a library runs its initialisers. Plugins are trusted as much as the rack
+    // itself.
+    let library = Arc::new(unsafe { Library::new(path)? });
+
+    let found = unsafe {
+        let abi_version = library.get::<AbiVersionFn>(ABI_VERSION_SYMBOL)?;
+        abi_version()
+    };
+    if found != ABI_VERSION {
+        return Err(PluginError::IncompatibleVersion {
+            found,
+            expected: ABI_VERSION,
+        });
+    }
+
+    let modules = unsafe {
+        let descriptor = library.get::<DescriptorFn>(DESCRIPTOR_SYMBOL)?;
+        let plugin = descriptor()
+            .as_ref()
+            .ok_or_else(|| invalid("the descriptor is null"))?;
+        let descriptors = array(plugin.modules, plugin.module_count)?;
+        descriptors
+            .iter()
+            .map(|descriptor| ModuleType::read(&library, descriptor))
+            .collect::<Result<Vec<_>, _>>()?
+    };
+
+    for (i, module) in modules.iter().enumerate() {
+        let id = &module.manifest.id;
+        if registry.manifest(id).is_some() || modules[..i].iter().any(|m| &m.manifest.id == id) {
+            return Err(RegistryError::AlreadyRegistered(id.clone()).into());
+        }
+    }
+
+    let mut ids = Vec::new();
+    for module in modules {
+        let module = Arc::new(module);
+        ids.push(module.manifest.id.clone());
+        let manifest = module.manifest.clone();
+        registry.register_factory(manifest, move || {
+            Box::new(PluginModule::new(module.clone()))
+        })?;
+    }
+    Ok(ids)
+}
+
+/// A module loaded from a plugin.
+struct ModuleType {
+    manifest: ModuleManifest,
+    // Port names last as long as the program, as `Module` requires. Each plugin's are leaked
+    // once, when it's loaded.
+    input_names: &'static [&'static str],
+    output_names: &'static [&'static str],
+    params: Vec<(String, String, f32, f32, f32)>,
+    vtable: ModuleVTable,
+    /// Keeps the plugin's code loaded while any of its modules exist.
+    _library: Arc<Library>,
+}
+
+impl ModuleType {
+    /// Copies out a module's metadata from its descriptor.
+    unsafe fn read(
+        library: &Arc<Library>,
+        descriptor: &ModuleDescriptor,
+    ) -> Result<Self, PluginError> {
+        let id = string(descriptor.id)?;
+        let tags = strings(descriptor.tags, descriptor.tag_count)?;
+        let tags: Vec<&str> = tags.iter().map(String::as_str).collect();
+        let category = Category::ALL
+            .get(descriptor.category as usize)
+            .copied()
+            .unwrap_or(Category::Utility);
+        let manifest = ModuleManifest::new(&id, &string(descriptor.name)?)
+            .category(category)
+            .tags(&tags)
+            .author(&string(descriptor.author)?)
+            .description(&string(descriptor.description)?)
+            .hp(descriptor.hp.max(1) as usize);
+        let leak = |names: Vec<String>| -> &'static [&'static str] {
+            let names: Vec<&'static str> = names
+                .into_iter()
+                .map(|name| &*Box::leak(name.into_boxed_str()))
+                .collect();
+            Box::leak(names.into_boxed_slice())
+        };
+        let params = array(descriptor.params, descriptor.param_count)?
+            .iter()
+            .map(|p| Ok((string(p.name)?, string(p.label)?, p.min, p.max, p.default)))
+            .collect::<Result<_, PluginError>>()?;
+        Ok(ModuleType {
+            manifest,
+            input_names: leak(strings(descriptor.input_names, descriptor.inputs)?),
+            output_names: leak(strings(descriptor.output_names, descriptor.outputs)?),
+            params,
+            vtable: descriptor.vtable,
+            _library: library.clone(),
+        })
+    }
+}
+
+struct PluginModule {
+    module_type: Arc<ModuleType>,
+    params: Arc<ParameterSet>,
+}
+
+impl PluginModule {
+    fn new(module_type: Arc<ModuleType>) -> Self {
+        let params = module_type
+            .params
+            .iter()
+            .map(|(name, label, min, max, default)| {
+                NamedParameter::new(name, label, *min..=*max, *default)
+            })
+            .collect();
+        PluginModule {
+            module_type,
+            params: Arc::new(ParameterSet::new(params)),
+        }
+    }
+}
+
+impl Module for PluginModule {
+    fn inputs(&self) -> usize {
+        self.module_type.input_names.len()
+    }
+
+    fn outputs(&self) -> usize {
+        self.module_type.output_names.len()
+    }
+
+    fn params(&self) -> Option<&dyn Parameters> {
+        Some(self.params.as_ref())
+    }
+
+    fn input_names(&self) -> &'static [&'static str] {
+        self.module_type.input_names
+    }
+
+    fn output_names(&self) -> &'static [&'static str] {
+        self.module_type.output_names
+    }
+
+    fn create_audio_unit(&self) -> Box<dyn AudioUnit> {
+        let vtable = self.module_type.vtable;
+        // Safety: the vtable came from the plugin, which is kept loaded by `module_type`.
+        let instance = unsafe { (vtable.create)(self.inputs()) };
+        Box::new(PluginUnit {
+            instance,
+            faulted: instance.is_null(),
+            fault: None,
+            vtable,
+            inputs: vec![0.0; self.inputs()],
+            connected: vec![false; self.inputs()],
+            param_values: vec![0.0; self.params.len()],
+            params: self.params.clone(),
+            _module_type: self.module_type.clone(),
+        })
+    }
+
+    fn create_panel(&self) -> Box<dyn Panel> {
+        Box::new(
+            GenericPanel::new(&self.module_type.manifest.name, self)
+                .params(self.params.clone())
+                .width(self.module_type.manifest.hp),
+        )
+    }
+}
+
+/// An instance of a plugin's module, with buffers for passing it inputs and parameters.
+struct PluginUnit {
+    /// Null if the plugin couldn't create the instance.
+    instance: *mut c_void,
+    /// Whether the module panicked, leaving it muted until it's reset.
+    faulted: bool,
+    /// A panic that the rack hasn't been told about yet.
+    fault: Option<ModuleFault>,
+    vtable: ModuleVTable,
+    inputs: Vec<Voltage>,
+    connected: Vec<bool>,
+    param_values: Vec<f32>,
+    params: Arc<ParameterSet>,
+    _module_type: Arc<ModuleType>,
+}
+
+// Safety: plugin units are only used from one thread at a time, as the ABI requires.
+unsafe impl Send for PluginUnit {}
+
+impl AudioUnit for PluginUnit {
+    fn reset(&mut self, sample_rate: usize) {
+        if self.instance.is_null() {
+            return;
+        }
+        let status = unsafe { (self.vtable.reset)(self.instance, sample_rate as u32) };
+        self.faulted = status != status::OK;
+        if self.faulted {
+            self.fault = Some(ModuleFault::Panicked);
+        }
+    }
+
+    fn tick(&mut self, inputs: &[Option<Voltage>], outputs: &mut [Voltage]) {
+        if self.faulted {
+            outputs.fill(0.0);
+            return;
+        }
+        for ((value, connected), input) in
+            self.inputs.iter_mut().zip(&mut self.connected).zip(inputs)
+        {
+            *value = input.unwrap_or(0.0);
+            *connected = input.is_some();
+        }
+        self.params.read_into(&mut self.param_values);
+        let status = unsafe {
+            (self.vtable.tick)(
+                self.instance,
+                self.inputs.as_ptr(),
+                self.connected.as_ptr(),
+                self.inputs.len(),
+                self.param_values.as_ptr(),
+                self.param_values.len(),
+                outputs.as_mut_ptr(),
+                outputs.len(),
+            )
+        };
+        if status != status::OK {
+            outputs.fill(0.0);
+            self.faulted = true;
+            self.fault = Some(ModuleFault::Panicked);
+        }
+    }
+
+    /// Passes on a panic caught inside the plugin, which couldn't unwind across the plugin's
+    /// boundary itself. The rack flags the module, and ticks it again once it's reset.
+    fn take_fault(&mut self) -> Option<ModuleFault> {
+        self.fault.take()
+    }
+}
+
+impl Drop for PluginUnit {
+    fn drop(&mut self) {
+        if !self.instance.is_null() {
+            unsafe { (self.vtable.destroy)(self.instance) };
+        }
+    }
+}
+
+fn invalid(reason: &str) -> PluginError {
+    PluginError::InvalidDescriptor(reason.to_owned())
+}
+
+/// Views an array in a descriptor, which may be null if it's empty.
+unsafe fn array<'a, T>(ptr: *const T, len: usize) -> Result<&'a [T], PluginError> {
+    match (ptr.is_null(), len) {
+        (_, 0) => Ok(&[]),
+        (true, _) => Err(invalid("an array is null")),
+        (false, _) => Ok(std::slice::from_raw_parts(ptr, len)),
+    }
+}
+
+unsafe fn string(ptr: *const c_char) -> Result<String, PluginError> {
+    if ptr.is_null() {
+        return Err(invalid("a string is null"));
+    }
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map(str::to_owned)
+        .map_err(|_| invalid("a string isn't UTF-8"))
+}
+
+unsafe fn strings(ptr: *const *const c_char, len: usize) -> Result<Vec<String>, PluginError> {
+    array(ptr, len)?.iter().map(|&s| string(s)).collect()
+}
@@ -0,0 +1,120 @@
+use std::{
+    collections::HashMap,
+    env::consts::{DLL_PREFIX, DLL_SUFFIX},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use module::{
+    registry::{Category, ModuleRegistry, RegistryError},
+    Module, SerializedParameter,
+};
+use module_test::{assert_near, Harness, Input};
+use plugin_host::PluginError;
+use rack::{ModuleFault, Rack};
+
+const SAMPLE_RATE: usize = 48_000;
+
+/// Builds the sample plugin into a directory of its own, returning the directory.
+fn build_sample_plugin() -> PathBuf {
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .unwrap()
+        .join("sample_plugin/Cargo.toml");
+    let target_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("sample_plugin");
+    let status = Command::new(env!("CARGO"))
+        .args(["build", "--quiet", "--manifest-path"])
+        .arg(&manifest)
+        .arg("--target-dir")
+        .arg(&target_dir)
+        .status()
+        .expect("couldn't run cargo");
+    assert!(status.success(), "couldn't build the sample plugin");
+    target_dir.join("debug")
+}
+
+fn sample_plugin_path() -> PathBuf {
+    build_sample_plugin().join(format!("{}sample_plugin{}", DLL_PREFIX, DLL_SUFFIX))
+}
+
+#[test]
+fn loads_sample_plugin() {
+    let mut registry = ModuleRegistry::default();
+    let ids = plugin_host::load(&mut registry, &sample_plugin_path()).unwrap();
+    assert_eq!(ids, ["sample::Folder", "sample::Unstable"]);
+
+    let manifest = registry.manifest("sample::Folder").unwrap();
+    assert_eq!(manifest.name, "Folder");
+    assert_eq!(manifest.category, Category::Utility);
+    assert!(manifest.matches("wavefolder"));
+
+    let mut harness = Harness::from_registry(&mut registry, "sample::Folder", SAMPLE_RATE).unwrap();
+    assert_eq!(harness.module().input_names(), ["audio", "drive"]);
+    assert_eq!(harness.module().output_names(), ["audio"]);
+
+    // Quiet signals pass straight through, and loud ones fold back below 5V.
+    harness.set_input(0, Input::Constant(3.0));
+    assert_near("quiet", harness.run(0.01).output(0).dc_offset(), 3.0, 1e-6);
+    harness.set_param("drive", 2.0);
+    assert_near("folded", harness.run(0.01).output(0).dc_offset(), 4.0, 1e-6);
+    harness.set_input(1, Input::Constant(1.0));
+    assert_near(
+        "drive cv",
+        harness.run(0.01).output(0).dc_offset(),
+        1.0,
+        1e-6,
+    );
+}
+
+#[test]
+fn loads_plugins_directory() {
+    let dir = build_sample_plugin();
+    let mut registry = ModuleRegistry::default();
+    let errors = plugin_host::load_dir(&mut registry, &dir);
+    assert!(errors.is_empty(), "{:?}", errors);
+    assert!(registry.manifest("sample::Folder").is_some());
+
+    let missing = dir.join("no such directory");
+    assert!(plugin_host::load_dir(&mut registry, &missing).is_empty());
+}
+
+#[test]
+fn rejects_taken_ids() {
+    let mut registry = ModuleRegistry::default();
+    plugin_host::load(&mut registry, &sample_plugin_path()).unwrap();
+    assert!(matches!(
+        plugin_host::load(&mut registry, &sample_plugin_path()),
+        Err(PluginError::Registry(RegistryError::AlreadyRegistered(id))) if id == "sample::Folder"
+    ));
+}
+
+fn set_crash(module: &dyn Module, value: f32) {
+    let params = HashMap::from([("crash".to_owned(), SerializedParameter::Num(value))]);
+    module.params().unwrap().deserialize(&params);
+}
+
+#[test]
+fn panicking_plugin_is_muted_until_reset() {
+    let mut registry = ModuleRegistry::default();
+    plugin_host::load(&mut registry, &sample_plugin_path()).unwrap();
+    let (handle, module) = registry.create_module("sample::Unstable").unwrap();
+
+    let mut rack = Rack::new();
+    rack.add_audio_unit(handle, 0, 1, Vec::new(), module.create_audio_unit());
+    rack.connect(handle.output(0), Rack::audio_output())
+        .unwrap();
+    rack.reset(SAMPLE_RATE);
+    assert_eq!(rack.tick(), 1.0);
+
+    // The panic is caught at the plugin's boundary and reported to the rack, which mutes the
+    // module until it's reset.
+    set_crash(module.as_ref(), 1.0);
+    assert_eq!(rack.tick(), 0.0);
+    let mut faults = Vec::new();
+    rack.drain_faults(|handle, fault| faults.push((handle, fault)));
+    assert_eq!(faults, [(handle, ModuleFault::Panicked)]);
+    set_crash(module.as_ref(), 0.0);
+    assert_eq!(rack.tick(), 0.0);
+    rack.reset_module(handle).unwrap();
+    assert_eq!(rack.tick(), 1.0);
+}
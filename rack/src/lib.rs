@@ -1,13 +1,24 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    panic::{self, AssertUnwindSafe},
+    time::{Duration, Instant},
+};
 
 use eurorack::Voltage;
-use module::{AudioUnit, Module, ModuleHandle, ModuleInput, ModuleOutput};
+pub use module::ModuleFault;
+use module::{
+    AudioUnit, Module, ModuleHandle, ModuleInput, ModuleOutput, ModuleState, SignalLevel,
+};
 
 pub struct Rack {
     sample_rate: usize,
     modules: HashMap<ModuleHandle, AudioUnitFacade>,
     patch_cables: Vec<(ModuleOutput, ModuleInput)>,
     output_channel: Option<ModuleOutput>,
+    profiling: bool,
+    /// The handle given to the next module added with [`Rack::add_module`]. Handles aren't reused,
+    /// so that a removed module's can't be mistaken for a new one's.
+    next_handle: usize,
 }
 
 impl Rack {
@@ -18,6 +29,8 @@ impl Rack {
             modules: HashMap::new(),
             patch_cables: Vec::new(),
             output_channel: None,
+            profiling: false,
+            next_handle: 0,
         }
     }
 
@@ -30,26 +43,38 @@ impl Rack {
         handle: ModuleHandle,
         inputs: usize,
         outputs: usize,
-        mut audio_unit: Box<dyn AudioUnit>,
+        mut bypass_routes: Vec<(usize, usize)>,
+        audio_unit: Box<dyn AudioUnit>,
     ) {
-        audio_unit.reset(self.sample_rate);
-        self.modules.insert(
-            handle,
-            AudioUnitFacade {
-                audio_unit,
-                inputs: vec![None; inputs],
-                outputs: vec![0.0; outputs],
-            },
-        );
+        bypass_routes.retain(|&(input, output)| input < inputs && output < outputs);
+        let mut module = AudioUnitFacade {
+            audio_unit,
+            inputs: vec![None; inputs],
+            outputs: vec![0.0; outputs],
+            meters: vec![LevelMeter::default(); outputs],
+            state: ModuleState::Active,
+            bypass_routes,
+            tick_time: Duration::ZERO,
+            fault: None,
+            fault_pending: false,
+        };
+        module.reset(self.sample_rate);
+        self.modules.insert(handle, module);
     }
 
     pub fn add_module<M: Module>(&mut self, module: &M) -> ModuleHandle {
-        let handle = ModuleHandle(self.modules.len());
+        let handle = ModuleHandle(self.next_handle);
+        self.next_handle += 1;
         self.add_audio_unit(
             handle,
             module.inputs(),
             module.outputs(),
-            module.create_audio_unit(),
+            module.bypass_routes(),
+            module.default_oversampling().wrap(
+                module.create_audio_unit(),
+                module.inputs(),
+                module.outputs(),
+            ),
         );
         handle
     }
@@ -94,10 +119,121 @@ impl Rack {
         }
     }
 
+    /// Removes a module along with every cable connected to it, including the audio output.
+    pub fn remove_module(&mut self, handle: ModuleHandle) -> Result<(), RackError> {
+        self.modules
+            .remove(&handle)
+            .ok_or(RackError::InvalidModule)?;
+        let modules = &mut self.modules;
+        self.patch_cables.retain(|(src, dst)| {
+            if src.module == handle {
+                // As in disconnect, reset inputs that were fed by the removed module.
+                if let Some(module) = modules.get_mut(&dst.module) {
+                    module.inputs[dst.channel] = None;
+                }
+            }
+            src.module != handle && dst.module != handle
+        });
+        if self.output_channel.is_some_and(|src| src.module == handle) {
+            self.output_channel = None;
+        }
+        Ok(())
+    }
+
     pub fn reset(&mut self, sample_rate: usize) {
         self.sample_rate = sample_rate;
         for module in self.modules.values_mut() {
-            module.audio_unit.reset(sample_rate);
+            module.reset(sample_rate);
+        }
+    }
+
+    /// Swaps out a module's audio unit, while keeping its connections. The new unit starts from a
+    /// fresh reset.
+    pub fn replace_audio_unit(
+        &mut self,
+        handle: ModuleHandle,
+        audio_unit: Box<dyn AudioUnit>,
+    ) -> Result<(), RackError> {
+        let module = self
+            .modules
+            .get_mut(&handle)
+            .ok_or(RackError::InvalidModule)?;
+        module.audio_unit = audio_unit;
+        module.fault = None;
+        module.fault_pending = false;
+        module.outputs.fill(0.0);
+        module.reset(self.sample_rate);
+        Ok(())
+    }
+
+    /// Changes whether a module is processed, bypassed or muted.
+    pub fn set_module_state(
+        &mut self,
+        handle: ModuleHandle,
+        state: ModuleState,
+    ) -> Result<(), RackError> {
+        let module = self
+            .modules
+            .get_mut(&handle)
+            .ok_or(RackError::InvalidModule)?;
+        module.state = state;
+        Ok(())
+    }
+
+    /// Resets a single module, clearing any fault so that it is ticked again.
+    pub fn reset_module(&mut self, handle: ModuleHandle) -> Result<(), RackError> {
+        let module = self
+            .modules
+            .get_mut(&handle)
+            .ok_or(RackError::InvalidModule)?;
+        module.fault = None;
+        module.fault_pending = false;
+        module.outputs.fill(0.0);
+        module.reset(self.sample_rate);
+        Ok(())
+    }
+
+    /// Reports any modules that have faulted since the last call.
+    pub fn drain_faults(&mut self, mut f: impl FnMut(ModuleHandle, ModuleFault)) {
+        for (handle, module) in self.modules.iter_mut() {
+            if module.fault_pending {
+                module.fault_pending = false;
+                f(*handle, module.fault.unwrap());
+            }
+        }
+    }
+
+    /// Enables or disables measuring the time spent ticking each module.
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profiling = enabled;
+        if !enabled {
+            for module in self.modules.values_mut() {
+                module.tick_time = Duration::ZERO;
+            }
+        }
+    }
+
+    /// Reports the time spent ticking each module since the last call, and resets the counters.
+    ///
+    /// Nothing is reported unless profiling is enabled.
+    pub fn drain_tick_times(&mut self, mut f: impl FnMut(ModuleHandle, Duration)) {
+        if !self.profiling {
+            return;
+        }
+        for (handle, module) in self.modules.iter_mut() {
+            f(*handle, module.tick_time);
+            module.tick_time = Duration::ZERO;
+        }
+    }
+
+    /// Reports the level of every module output since the last call, and resets the meters.
+    pub fn drain_signal_levels(&mut self, mut f: impl FnMut(ModuleOutput, SignalLevel)) {
+        for (handle, module) in self.modules.iter_mut() {
+            for (channel, meter) in module.meters.iter_mut().enumerate() {
+                if let Some(level) = meter.take() {
+                    f(handle.output(channel), level);
+                }
+            }
         }
     }
 
@@ -110,7 +246,16 @@ impl Rack {
         }
 
         for module in self.modules.values_mut() {
-            module.audio_unit.tick(&module.inputs, &mut module.outputs);
+            if self.profiling {
+                let start = Instant::now();
+                module.tick(self.sample_rate);
+                module.tick_time += start.elapsed();
+            } else {
+                module.tick(self.sample_rate);
+            }
+            for (meter, &v) in module.meters.iter_mut().zip(&module.outputs) {
+                meter.record(v);
+            }
         }
 
         self.output_channel
@@ -124,6 +269,112 @@ struct AudioUnitFacade {
     audio_unit: Box<dyn AudioUnit>,
     inputs: Vec<Option<Voltage>>,
     outputs: Vec<Voltage>,
+    meters: Vec<LevelMeter>,
+    state: ModuleState,
+    bypass_routes: Vec<(usize, usize)>,
+    tick_time: Duration,
+    fault: Option<ModuleFault>,
+    fault_pending: bool,
+}
+
+impl AudioUnitFacade {
+    fn reset(&mut self, sample_rate: usize) {
+        let audio_unit = &mut self.audio_unit;
+        if panic::catch_unwind(AssertUnwindSafe(|| audio_unit.reset(sample_rate))).is_err() {
+            self.set_fault(ModuleFault::Panicked);
+        } else if let Some(fault) = self.audio_unit.take_fault() {
+            self.set_fault(fault);
+        }
+    }
+
+    fn tick(&mut self, sample_rate: usize) {
+        match self.state {
+            ModuleState::Active => (),
+            ModuleState::Bypassed => {
+                self.outputs.fill(0.0);
+                for &(input, output) in &self.bypass_routes {
+                    self.outputs[output] = self.inputs[input].unwrap_or(0.0);
+                }
+                return;
+            }
+            ModuleState::Muted => {
+                self.outputs.fill(0.0);
+                return;
+            }
+        }
+
+        // A module that has panicked may have been left in an inconsistent state, so it stays muted
+        // until it is explicitly reset.
+        if self.fault == Some(ModuleFault::Panicked) {
+            return;
+        }
+
+        let (audio_unit, inputs, outputs) = (&mut self.audio_unit, &self.inputs, &mut self.outputs);
+        if panic::catch_unwind(AssertUnwindSafe(|| audio_unit.tick(inputs, outputs))).is_err() {
+            self.outputs.fill(0.0);
+            self.set_fault(ModuleFault::Panicked);
+            return;
+        }
+        if let Some(fault) = self.audio_unit.take_fault() {
+            self.outputs.fill(0.0);
+            self.set_fault(fault);
+            return;
+        }
+
+        // Non-finite voltages would poison every module downstream of this one (and the DAC), so
+        // we silence them here. The unit is then reset, in the hope that it can recover.
+        if self.outputs.iter().any(|v| !v.is_finite()) {
+            for v in self.outputs.iter_mut().filter(|v| !v.is_finite()) {
+                *v = 0.0;
+            }
+            self.reset(sample_rate);
+            self.set_fault(ModuleFault::NonFinite);
+        }
+    }
+
+    fn set_fault(&mut self, fault: ModuleFault) {
+        // Panics take precedence, and we only report each change once.
+        if self.fault != Some(ModuleFault::Panicked) && self.fault != Some(fault) {
+            self.fault = Some(fault);
+            self.fault_pending = true;
+        }
+    }
+}
+
+/// Accumulates the voltages seen on an output between reports.
+#[derive(Copy, Clone, Default)]
+struct LevelMeter {
+    last: Voltage,
+    peak: Voltage,
+    sum: f32,
+    sum_squares: f32,
+    samples: usize,
+}
+
+impl LevelMeter {
+    fn record(&mut self, v: Voltage) {
+        self.last = v;
+        self.peak = self.peak.max(v.abs());
+        self.sum += v;
+        self.sum_squares += v * v;
+        self.samples += 1;
+    }
+
+    /// The level since the last call, if any samples have been recorded.
+    fn take(&mut self) -> Option<SignalLevel> {
+        if self.samples == 0 {
+            return None;
+        }
+        let n = self.samples as f32;
+        let level = SignalLevel {
+            value: self.last,
+            peak: self.peak,
+            rms: (self.sum_squares / n).sqrt(),
+            mean: self.sum / n,
+        };
+        *self = LevelMeter::default();
+        Some(level)
+    }
 }
 
 #[derive(thiserror::Error, Debug)]
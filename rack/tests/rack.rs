@@ -0,0 +1,23 @@
+use modules::{amplifier::Vca, clock::Clock};
+use rack::{Rack, RackError};
+
+#[test]
+fn handles_are_not_reused_after_removal() {
+    let mut rack = Rack::new();
+    let a = rack.take_module(Clock::default());
+    let b = rack.take_module(Vca::default());
+    rack.connect(b.output(0), Rack::audio_output()).unwrap();
+    rack.remove_module(a).unwrap();
+
+    let c = rack.take_module(Vca::default());
+    assert!(c != a && c != b);
+    rack.connect(c.output(0), b.input(0)).unwrap();
+
+    // Both modules are still there, each under its own handle.
+    rack.remove_module(c).unwrap();
+    rack.remove_module(b).unwrap();
+    assert!(matches!(
+        rack.remove_module(a),
+        Err(RackError::InvalidModule)
+    ));
+}
@@ -0,0 +1,78 @@
+//! An example plugin, with a wavefolder module and a module that panics on demand. It is loaded
+//! by `plugin_host`'s tests.
+
+use plugin_api::{category, export_plugin, ModuleExport, Unit};
+
+/// The largest voltage the folder passes before reflecting it back.
+const FOLD_VOLTS: f32 = 5.0;
+
+/// Boosts a signal, then folds back whatever goes past ±5V, adding harmonics as the drive rises.
+#[derive(Default)]
+struct Folder;
+
+impl Folder {
+    const AUDIO_IN: usize = 0;
+    const DRIVE_CV_IN: usize = 1;
+    const AUDIO_OUT: usize = 0;
+
+    const DRIVE: usize = 0;
+}
+
+impl Unit for Folder {
+    fn reset(&mut self, _sample_rate: u32) {}
+
+    fn tick(&mut self, inputs: &[Option<f32>], params: &[f32], outputs: &mut [f32]) {
+        let cv = inputs[Folder::DRIVE_CV_IN].unwrap_or(0.0);
+        let drive = (params[Folder::DRIVE] + cv).max(0.0);
+        let audio = inputs[Folder::AUDIO_IN].unwrap_or(0.0);
+        outputs[Folder::AUDIO_OUT] = fold(drive * audio);
+    }
+}
+
+fn fold(v: f32) -> f32 {
+    // Reflect into a triangle wave with a period of four times the threshold.
+    let period = 4.0 * FOLD_VOLTS;
+    let phase = (v + FOLD_VOLTS).rem_euclid(period);
+    if phase < 2.0 * FOLD_VOLTS {
+        phase - FOLD_VOLTS
+    } else {
+        3.0 * FOLD_VOLTS - phase
+    }
+}
+
+/// Outputs a steady 1V, until its crash parameter is turned up, when it panics. For checking that a
+/// misbehaving plugin can't take the rack down with it.
+#[derive(Default)]
+struct Unstable;
+
+impl Unit for Unstable {
+    fn reset(&mut self, _sample_rate: u32) {}
+
+    fn tick(&mut self, _inputs: &[Option<f32>], params: &[f32], outputs: &mut [f32]) {
+        if params[0] > 0.5 {
+            panic!("crashed on purpose");
+        }
+        outputs[0] = 1.0;
+    }
+}
+
+fn modules() -> Vec<ModuleExport> {
+    vec![
+        ModuleExport::new::<Folder>("sample::Folder", "Folder")
+            .author("oxcable")
+            .description("Folds back loud signals, for brighter tones.")
+            .tags(&["wavefolder", "distortion", "harmonics"])
+            .category(category::UTILITY)
+            .hp(4)
+            .inputs(&["audio", "drive"])
+            .outputs(&["audio"])
+            .param("drive", "Drive", 1.0..=10.0, 1.0),
+        ModuleExport::new::<Unstable>("sample::Unstable", "Unstable")
+            .author("oxcable")
+            .description("Panics when asked to, for testing.")
+            .outputs(&["out"])
+            .param("crash", "Crash", 0.0..=1.0, 0.0),
+    ]
+}
+
+export_plugin!(modules());
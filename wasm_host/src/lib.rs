@@ -0,0 +1,607 @@
+//! Runs modules compiled to WebAssembly, sandboxed so that a faulty module can't affect the rest
+//! of the rack.
+//!
+//! A module is a `.wasm` file that imports nothing, and exports:
+//!
+//! - `memory`, its linear memory.
+//! - `oxcable_abi_version() -> i32`, returning [`ABI_VERSION`].
+//! - `inputs() -> i32`, `outputs() -> i32` and `params() -> i32`, giving how many of each it has.
+//!   Modules may have up to 32 inputs.
+//! - `input_buffer() -> i32`, `output_buffer() -> i32` and `param_buffer() -> i32`, giving the
+//!   addresses of arrays of `f32`. Before each tick, the rack writes the inputs and parameter
+//!   values to their arrays, and afterwards it reads the outputs from theirs.
+//! - `reset(sample_rate: i32)`.
+//! - `tick(connected: i32)`, which processes one sample. Bit `i` of `connected` is set if input
+//!   `i` is patched. Disconnected inputs read as 0V.
+//!
+//! It may also export any of:
+//!
+//! - `name() -> i32`, `input_name(i: i32) -> i32`, `output_name(i: i32) -> i32` and
+//!   `param_name(i: i32) -> i32`, giving the addresses of nul terminated UTF-8 strings.
+//! - `param_min(i: i32) -> f32`, `param_max(i: i32) -> f32` and `param_default(i: i32) -> f32`.
+//!   Parameters range from 0 to 1 by default.
+//!
+//! Modules are registered as `wasm::<file name>`, and the rack lays out their panels. A module
+//! that traps, or runs for too long, is silenced until it's reset. Modules may run about 25 million
+//! instructions for each second of audio, which they can save up for a buffer's worth of samples,
+//! and may use up to 128MiB of memory.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+};
+
+use eurorack::Voltage;
+use module::{
+    parameters::{NamedParameter, ParameterSet},
+    registry::{Category, ModuleManifest, ModuleRegistry, RegistryError},
+    AudioUnit, Module, ModuleFault, ModuleHandle, Panel, Parameters,
+};
+use wasmi::{Config, Engine, Linker, Memory, Store, StoreLimits, StoreLimitsBuilder, TypedFunc};
+use widgets::{egui, panel::GenericPanel};
+
+/// Bumped whenever the interface changes.
+pub const ABI_VERSION: i32 = 1;
+
+/// How many instructions a module may run for each second of audio. wasmi runs around 250 million
+/// a second on a desktop CPU, so this keeps a module to a tenth of a core or so.
+const FUEL_PER_SECOND: u64 = 25_000_000;
+/// How many samples' worth of instructions a module may save up, so that modules that process
+/// blocks of samples at once can. About one audio buffer.
+const FUEL_WINDOW: u64 = 512;
+/// The most instructions a module may run while it's set up and describes itself.
+const SETUP_FUEL: u64 = 10_000_000;
+/// The most memory a module may use, which is enough for a ten minute delay line.
+const MAX_MEMORY: usize = 128 << 20;
+/// The most entries a module's function table may have.
+const MAX_TABLE_ELEMENTS: u32 = 10_000;
+/// The longest string read from a module.
+const MAX_STRING: usize = 256;
+const MAX_INPUTS: usize = 32;
+
+#[derive(thiserror::Error, Debug)]
+pub enum WasmError {
+    #[error("couldn't read the module: {0}")]
+    Io(#[from] io::Error),
+    #[error("{0}")]
+    Wasm(#[from] wasmi::Error),
+    #[error("the module was built for version {found} of the interface, not {expected}")]
+    IncompatibleVersion { found: i32, expected: i32 },
+    #[error("the module doesn't export '{0}'")]
+    MissingExport(&'static str),
+    #[error("the module is invalid: {0}")]
+    Invalid(String),
+    #[error("{0}")]
+    Registry(#[from] RegistryError),
+}
+
+/// Loads every `.wasm` module in a directory, registering them. Returns an error for each module
+/// that couldn't be loaded, without stopping the others. A missing directory has no modules.
+pub fn load_dir(registry: &mut ModuleRegistry, dir: &Path) -> Vec<(PathBuf, WasmError)> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Vec::new(),
+        Err(e) => return vec![(dir.to_owned(), e.into())],
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.extension() == Some("wasm".as_ref()))
+        .collect();
+    paths.sort();
+    paths
+        .into_iter()
+        .filter_map(|path| match load(registry, &path) {
+            Ok(_) => None,
+            Err(e) => Some((path, e)),
+        })
+        .collect()
+}
+
+/// Loads a module from a file, registering it. Returns the module's id.
+pub fn load(registry: &mut ModuleRegistry, path: &Path) -> Result<String, WasmError> {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    load_bytes(registry, &stem, &fs::read(path)?)
+}
+
+/// Loads a module, registering it as `wasm::<name>`. Returns the module's id.
+pub fn load_bytes(
+    registry: &mut ModuleRegistry,
+    name: &str,
+    wasm: &[u8],
+) -> Result<String, WasmError> {
+    let module_type = Arc::new(ModuleType::new(name, wasm)?);
+    let id = module_type.manifest.id.clone();
+    let manifest = module_type.manifest.clone();
+    registry.register_factory(manifest, move || {
+        Box::new(WasmModule::new(module_type.clone()))
+    })?;
+    Ok(id)
+}
+
+/// A compiled module, along with what it told us about itself.
+struct ModuleType {
+    manifest: ModuleManifest,
+    engine: Engine,
+    module: wasmi::Module,
+    // Port names last as long as the program, as `Module` requires. Each module's are leaked
+    // once, when it's loaded.
+    input_names: &'static [&'static str],
+    output_names: &'static [&'static str],
+    params: Vec<NamedParameter>,
+}
+
+impl ModuleType {
+    fn new(name: &str, wasm: &[u8]) -> Result<Self, WasmError> {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module = wasmi::Module::new(&engine, wasm)?;
+        let mut instance = Running::new(&engine, &module)?;
+
+        let display_name = instance
+            .optional_string("name", None)?
+            .unwrap_or_else(|| name.to_owned());
+        let inputs = instance.names("input_name", instance.inputs)?;
+        let outputs = instance.names("output_name", instance.outputs)?;
+
+        let mut params = Vec::new();
+        for i in 0..instance.params {
+            let name = instance
+                .optional_string("param_name", Some(i))?
+                .unwrap_or_else(|| format!("param{}", i + 1));
+            let min = instance.optional_f32("param_min", i)?.unwrap_or(0.0);
+            let max = instance.optional_f32("param_max", i)?.unwrap_or(1.0);
+            let default = instance.optional_f32("param_default", i)?.unwrap_or(min);
+            params.push(NamedParameter::new(&name, &name, min..=max, default));
+        }
+
+        let leak = |names: Vec<String>| -> &'static [&'static str] {
+            let names: Vec<&'static str> = names
+                .into_iter()
+                .map(|name| &*Box::leak(name.into_boxed_str()))
+                .collect();
+            Box::leak(names.into_boxed_slice())
+        };
+        Ok(ModuleType {
+            manifest: ModuleManifest::new(&format!("wasm::{}", name), &display_name)
+                .category(Category::Utility)
+                .tags(&["wasm"])
+                .description("A module compiled to WebAssembly.")
+                .hp(4 * params
+                    .len()
+                    .max(inputs.len())
+                    .max(outputs.len())
+                    .div_ceil(2)
+                    .max(1)),
+            engine,
+            module,
+            input_names: leak(inputs),
+            output_names: leak(outputs),
+            params,
+        })
+    }
+
+    fn params(&self) -> ParameterSet {
+        ParameterSet::new(
+            self.params
+                .iter()
+                .map(|p| NamedParameter::new(&p.name, &p.label, p.range.clone(), p.default))
+                .collect(),
+        )
+    }
+}
+
+/// An instance of a module, with the exports the rack calls while it runs.
+struct Running {
+    store: Store<StoreLimits>,
+    instance: wasmi::Instance,
+    memory: Memory,
+    reset: TypedFunc<i32, ()>,
+    tick: TypedFunc<i32, ()>,
+    inputs: usize,
+    outputs: usize,
+    params: usize,
+    input_buffer: usize,
+    output_buffer: usize,
+    param_buffer: usize,
+    /// The instructions the module may run each sample, at the current sample rate.
+    fuel_per_sample: u64,
+    /// The instructions the module has saved up, up to a window's worth.
+    fuel: u64,
+}
+
+impl Running {
+    fn new(engine: &Engine, module: &wasmi::Module) -> Result<Self, WasmError> {
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(MAX_MEMORY)
+            .memories(1)
+            .tables(1)
+            .table_elements(MAX_TABLE_ELEMENTS)
+            .instances(1)
+            .build();
+        let mut store = Store::new(engine, limits);
+        store.limiter(|limits| limits);
+        store.set_fuel(SETUP_FUEL).unwrap();
+        let instance = Linker::new(engine)
+            .instantiate(&mut store, module)?
+            .start(&mut store)?;
+        let memory = instance
+            .get_memory(&store, "memory")
+            .ok_or(WasmError::MissingExport("memory"))?;
+
+        let get =
+            |store: &mut Store<StoreLimits>, export: &'static str| -> Result<usize, WasmError> {
+                let func = instance
+                    .get_typed_func::<(), i32>(&*store, export)
+                    .map_err(|_| WasmError::MissingExport(export))?;
+                store.set_fuel(SETUP_FUEL).unwrap();
+                let value = func.call(store, ())?;
+                usize::try_from(value)
+                    .map_err(|_| WasmError::Invalid(format!("'{}' returned {}", export, value)))
+            };
+        let found = get(&mut store, "oxcable_abi_version")? as i32;
+        if found != ABI_VERSION {
+            return Err(WasmError::IncompatibleVersion {
+                found,
+                expected: ABI_VERSION,
+            });
+        }
+        let inputs = get(&mut store, "inputs")?;
+        if inputs > MAX_INPUTS {
+            return Err(WasmError::Invalid(format!("it has {} inputs", inputs)));
+        }
+        let function = |export| {
+            instance
+                .get_typed_func::<i32, ()>(&store, export)
+                .map_err(|_| WasmError::MissingExport(export))
+        };
+        let (reset, tick) = (function("reset")?, function("tick")?);
+        Ok(Running {
+            inputs,
+            outputs: get(&mut store, "outputs")?,
+            params: get(&mut store, "params")?,
+            input_buffer: get(&mut store, "input_buffer")?,
+            output_buffer: get(&mut store, "output_buffer")?,
+            param_buffer: get(&mut store, "param_buffer")?,
+            fuel_per_sample: 0,
+            fuel: 0,
+            store,
+            instance,
+            memory,
+            reset,
+            tick,
+        })
+    }
+
+    /// Resets the module, which may use a full window of instructions.
+    fn reset(&mut self, sample_rate: usize) -> Result<(), WasmError> {
+        self.fuel_per_sample = (FUEL_PER_SECOND / sample_rate.max(1) as u64).max(1);
+        self.fuel = FUEL_WINDOW * self.fuel_per_sample;
+        self.store.set_fuel(self.fuel).unwrap();
+        self.reset.call(&mut self.store, sample_rate as i32)?;
+        Ok(())
+    }
+
+    /// Runs one sample. `bytes` is scratch space, which is grown as needed.
+    fn tick(
+        &mut self,
+        inputs: &[Option<Voltage>],
+        params: &[f32],
+        outputs: &mut [Voltage],
+        bytes: &mut Vec<u8>,
+    ) -> Result<(), WasmError> {
+        let connected = inputs
+            .iter()
+            .enumerate()
+            .filter(|(_, input)| input.is_some())
+            .fold(0u32, |mask, (i, _)| mask | 1 << i);
+        let inputs = inputs.iter().map(|input| input.unwrap_or(0.0));
+        self.write(self.input_buffer, inputs, bytes)?;
+        self.write(self.param_buffer, params.iter().copied(), bytes)?;
+
+        // Modules earn instructions as they're ticked, so one that runs for too long on average
+        // traps, however it spreads out its work.
+        self.fuel = (self.fuel + self.fuel_per_sample).min(FUEL_WINDOW * self.fuel_per_sample);
+        self.store.set_fuel(self.fuel).unwrap();
+        let result = self.tick.call(&mut self.store, connected as i32);
+        self.fuel = self.store.get_fuel().unwrap();
+        result?;
+
+        bytes.resize(4 * outputs.len(), 0);
+        self.memory
+            .read(&self.store, self.output_buffer, bytes)
+            .map_err(|e| WasmError::Invalid(e.to_string()))?;
+        for (output, value) in outputs.iter_mut().zip(bytes.chunks_exact(4)) {
+            *output = f32::from_le_bytes(value.try_into().unwrap());
+        }
+        Ok(())
+    }
+
+    fn write(
+        &mut self,
+        address: usize,
+        values: impl Iterator<Item = f32>,
+        bytes: &mut Vec<u8>,
+    ) -> Result<(), WasmError> {
+        bytes.clear();
+        for value in values {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        self.memory
+            .write(&mut self.store, address, bytes)
+            .map_err(|e| WasmError::Invalid(e.to_string()))
+    }
+
+    /// Calls an optional export giving a string's address, if the module has it.
+    fn optional_string(
+        &mut self,
+        export: &str,
+        index: Option<usize>,
+    ) -> Result<Option<String>, WasmError> {
+        self.store.set_fuel(SETUP_FUEL).unwrap();
+        let address = match index {
+            None => match self.instance.get_typed_func::<(), i32>(&self.store, export) {
+                Ok(func) => func.call(&mut self.store, ())?,
+                Err(_) => return Ok(None),
+            },
+            Some(i) => match self
+                .instance
+                .get_typed_func::<i32, i32>(&self.store, export)
+            {
+                Ok(func) => func.call(&mut self.store, i as i32)?,
+                Err(_) => return Ok(None),
+            },
+        };
+        let data = self.memory.data(&self.store);
+        let start = (address as u32 as usize).min(data.len());
+        let bytes = &data[start..(start + MAX_STRING).min(data.len())];
+        let end = bytes
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(|| WasmError::Invalid(format!("'{}' isn't nul terminated", export)))?;
+        let string = std::str::from_utf8(&bytes[..end])
+            .map_err(|_| WasmError::Invalid(format!("'{}' isn't UTF-8", export)))?;
+        Ok(Some(string.to_owned()))
+    }
+
+    /// Names each port, leaving them blank if the module doesn't.
+    fn names(&mut self, export: &str, count: usize) -> Result<Vec<String>, WasmError> {
+        (0..count)
+            .map(|i| Ok(self.optional_string(export, Some(i))?.unwrap_or_default()))
+            .collect()
+    }
+
+    /// Calls an optional export giving a number, if the module has it.
+    fn optional_f32(&mut self, export: &str, index: usize) -> Result<Option<f32>, WasmError> {
+        match self
+            .instance
+            .get_typed_func::<i32, f32>(&self.store, export)
+        {
+            Ok(func) => {
+                self.store.set_fuel(SETUP_FUEL).unwrap();
+                Ok(Some(func.call(&mut self.store, index as i32)?))
+            }
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+/// What a module's audio units share with its panel.
+#[derive(Default)]
+struct Shared {
+    /// Why the unit last stopped. The panel turns this into a message, so that the audio thread
+    /// doesn't have to.
+    fault: Mutex<Option<WasmError>>,
+    /// Whether the unit is stopped.
+    stopped: AtomicBool,
+    /// An instance made ahead of time, so that restarting a unit after a fault doesn't allocate
+    /// on the audio thread.
+    spare: Mutex<Option<Running>>,
+    /// An instance that faulted, left for the panel to free.
+    spent: Mutex<Option<Running>>,
+}
+
+impl Shared {
+    fn stop(&self, error: WasmError) {
+        // Faults are rare, but the audio thread still shouldn't wait on the panel. The flag is set
+        // while holding the lock, so that the panel never sees the error without it.
+        let fault = self.fault.try_lock();
+        self.stopped.store(true, Ordering::Relaxed);
+        if let Ok(mut fault) = fault {
+            *fault = Some(error);
+        }
+    }
+
+    fn restart(&self) {
+        self.stopped.store(false, Ordering::Relaxed);
+    }
+
+    /// Why the unit stopped, if it has.
+    fn fault(&self) -> Option<String> {
+        let fault = lock(&self.fault);
+        if !self.stopped.load(Ordering::Relaxed) {
+            return None;
+        }
+        Some(match &*fault {
+            Some(error) => error.to_string(),
+            None => "the module stopped".to_owned(),
+        })
+    }
+
+    /// Makes a spare instance if the last one was used, and frees any that faulted, along with
+    /// the error from a fault that's been recovered from. Called off the audio thread.
+    fn prepare(&self, module_type: &ModuleType) {
+        lock(&self.spent).take();
+        {
+            let mut fault = lock(&self.fault);
+            if !self.stopped.load(Ordering::Relaxed) {
+                fault.take();
+            }
+        }
+        let mut spare = lock(&self.spare);
+        if spare.is_none() {
+            *spare = Running::new(&module_type.engine, &module_type.module).ok();
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+struct WasmModule {
+    module_type: Arc<ModuleType>,
+    params: Arc<ParameterSet>,
+    shared: Arc<Shared>,
+}
+
+impl WasmModule {
+    fn new(module_type: Arc<ModuleType>) -> Self {
+        WasmModule {
+            params: Arc::new(module_type.params()),
+            module_type,
+            shared: Arc::default(),
+        }
+    }
+}
+
+impl Module for WasmModule {
+    fn inputs(&self) -> usize {
+        self.module_type.input_names.len()
+    }
+
+    fn outputs(&self) -> usize {
+        self.module_type.output_names.len()
+    }
+
+    fn params(&self) -> Option<&dyn Parameters> {
+        Some(self.params.as_ref())
+    }
+
+    fn input_names(&self) -> &'static [&'static str] {
+        self.module_type.input_names
+    }
+
+    fn output_names(&self) -> &'static [&'static str] {
+        self.module_type.output_names
+    }
+
+    fn create_audio_unit(&self) -> Box<dyn AudioUnit> {
+        let running = match Running::new(&self.module_type.engine, &self.module_type.module) {
+            Ok(running) => Some(running),
+            Err(e) => {
+                self.shared.stop(e);
+                None
+            }
+        };
+        self.shared.prepare(&self.module_type);
+        let ports = self.inputs().max(self.outputs()).max(self.params.len());
+        Box::new(WasmUnit {
+            running,
+            params: self.params.clone(),
+            param_values: vec![0.0; self.params.len()],
+            shared: self.shared.clone(),
+            bytes: Vec::with_capacity(4 * ports),
+            fault: None,
+        })
+    }
+
+    fn create_panel(&self) -> Box<dyn Panel> {
+        Box::new(WasmPanel {
+            panel: GenericPanel::new(&self.module_type.manifest.name, self)
+                .params(self.params.clone())
+                .width(self.module_type.manifest.hp),
+            module_type: self.module_type.clone(),
+            shared: self.shared.clone(),
+        })
+    }
+}
+
+struct WasmUnit {
+    /// The module's instance, until it faults.
+    running: Option<Running>,
+    params: Arc<ParameterSet>,
+    param_values: Vec<f32>,
+    shared: Arc<Shared>,
+    bytes: Vec<u8>,
+    /// A fault that the rack hasn't been told about yet.
+    fault: Option<ModuleFault>,
+}
+
+impl WasmUnit {
+    /// Stops the unit, and reports it to the rack as having panicked, so that it's flagged and can
+    /// be reset.
+    fn stop(&mut self, error: WasmError) {
+        self.shared.stop(error);
+        if let (Some(running), Ok(mut spent)) = (self.running.take(), self.shared.spent.try_lock())
+        {
+            *spent = Some(running);
+        }
+        self.fault = Some(ModuleFault::Panicked);
+    }
+}
+
+impl AudioUnit for WasmUnit {
+    /// Resetting also restarts a module that has faulted, from the spare instance.
+    fn reset(&mut self, sample_rate: usize) {
+        if self.running.is_none() {
+            self.running = match self.shared.spare.try_lock() {
+                Ok(mut spare) => spare.take(),
+                Err(_) => None,
+            };
+            if self.running.is_some() {
+                self.shared.restart();
+            }
+        }
+        if let Some(running) = &mut self.running {
+            if let Err(e) = running.reset(sample_rate) {
+                self.stop(e);
+            }
+        }
+    }
+
+    fn tick(&mut self, inputs: &[Option<Voltage>], outputs: &mut [Voltage]) {
+        let running = match &mut self.running {
+            Some(running) => running,
+            None => {
+                outputs.fill(0.0);
+                return;
+            }
+        };
+        self.params.read_into(&mut self.param_values);
+        if let Err(e) = running.tick(inputs, &self.param_values, outputs, &mut self.bytes) {
+            outputs.fill(0.0);
+            self.stop(e);
+        }
+    }
+
+    fn take_fault(&mut self) -> Option<ModuleFault> {
+        self.fault.take()
+    }
+}
+
+/// A generated panel, which also shows why the module stopped if it faults.
+struct WasmPanel {
+    panel: GenericPanel,
+    module_type: Arc<ModuleType>,
+    shared: Arc<Shared>,
+}
+
+impl Panel for WasmPanel {
+    fn width(&self) -> usize {
+        Panel::width(&self.panel)
+    }
+
+    fn update(&mut self, handle: &ModuleHandle, ui: &mut egui::Ui) {
+        self.shared.prepare(&self.module_type);
+        if let Some(fault) = self.shared.fault() {
+            ui.colored_label(egui::Color32::RED, "Stopped")
+                .on_hover_text(format!("{}\nReset the module to restart it.", fault));
+        }
+        self.panel.update(handle, ui);
+    }
+}
@@ -0,0 +1,179 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use module::{registry::ModuleRegistry, Module, SerializedParameter};
+use module_test::{assert_near, Harness, Input};
+use rack::{ModuleFault, Rack};
+use wasm_host::WasmError;
+
+const SAMPLE_RATE: usize = 48_000;
+
+/// The exports every module needs, for a module with the given ports and parameters. Inputs are
+/// at address 0, outputs at 16 and parameters at 32.
+fn required_exports(version: i32, inputs: i32, outputs: i32, params: i32) -> String {
+    format!(
+        r#"
+        (memory (export "memory") 1)
+        (func (export "oxcable_abi_version") (result i32) i32.const {version})
+        (func (export "inputs") (result i32) i32.const {inputs})
+        (func (export "outputs") (result i32) i32.const {outputs})
+        (func (export "params") (result i32) i32.const {params})
+        (func (export "input_buffer") (result i32) i32.const 0)
+        (func (export "output_buffer") (result i32) i32.const 16)
+        (func (export "param_buffer") (result i32) i32.const 32)
+        (func (export "reset") (param i32))
+        "#
+    )
+}
+
+/// Multiplies its audio input by its gain, plus its gain CV.
+fn gain_module() -> Vec<u8> {
+    wat::parse_str(format!(
+        r#"(module
+            {}
+            (data (i32.const 64) "Gain\00audio\00cv\00gain\00")
+            (func (export "name") (result i32) i32.const 64)
+            (func (export "input_name") (param i32) (result i32)
+                (select (i32.const 69) (i32.const 75) (i32.eqz (local.get 0))))
+            (func (export "output_name") (param i32) (result i32) i32.const 69)
+            (func (export "param_name") (param i32) (result i32) i32.const 78)
+            (func (export "param_max") (param i32) (result f32) f32.const 2)
+            (func (export "param_default") (param i32) (result f32) f32.const 1)
+            (func (export "tick") (param i32)
+                (f32.store (i32.const 16)
+                    (f32.mul
+                        (f32.load (i32.const 0))
+                        (f32.add (f32.load (i32.const 32)) (f32.load (i32.const 4))))))
+        )"#,
+        required_exports(1, 2, 1, 1)
+    ))
+    .unwrap()
+}
+
+/// Outputs 1V, after spinning for a thousand loops for each unit of its only parameter.
+fn slow_module() -> Vec<u8> {
+    wat::parse_str(format!(
+        r#"(module
+            {}
+            (func (export "tick") (param i32) (local $n i32)
+                (local.set $n
+                    (i32.trunc_f32_u (f32.mul (f32.load (i32.const 32)) (f32.const 1000))))
+                (block $done
+                    (loop $spin
+                        (br_if $done (i32.eqz (local.get $n)))
+                        (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+                        (br $spin)))
+                (f32.store (i32.const 16) (f32.const 1)))
+        )"#,
+        required_exports(1, 0, 1, 1)
+    ))
+    .unwrap()
+}
+
+/// Tries to grow its memory by 256MiB each sample, and outputs the result.
+fn greedy_module() -> Vec<u8> {
+    wat::parse_str(format!(
+        r#"(module
+            {}
+            (func (export "tick") (param i32)
+                (f32.store (i32.const 16)
+                    (f32.convert_i32_s (memory.grow (i32.const 4096)))))
+        )"#,
+        required_exports(1, 0, 1, 0)
+    ))
+    .unwrap()
+}
+
+#[test]
+fn loads_module_from_file() {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("gain.wasm");
+    fs::write(&path, gain_module()).unwrap();
+    let mut registry = ModuleRegistry::default();
+    assert_eq!(wasm_host::load(&mut registry, &path).unwrap(), "wasm::gain");
+    assert_eq!(registry.manifest("wasm::gain").unwrap().name, "Gain");
+
+    let mut harness = Harness::from_registry(&mut registry, "wasm::gain", SAMPLE_RATE).unwrap();
+    assert_eq!(harness.module().input_names(), ["audio", "cv"]);
+    assert_eq!(harness.module().output_names(), ["audio"]);
+    let info = harness.module().params().unwrap().info();
+    assert_eq!(info[0].name, "gain");
+    assert_eq!(info[0].range, Some(0.0..=2.0));
+    assert_eq!(info[0].default, Some(1.0));
+
+    harness.set_input(0, Input::Constant(3.0));
+    assert_near("unity", harness.run(0.01).output(0).dc_offset(), 3.0, 1e-6);
+    harness.set_param("gain", 0.5);
+    assert_near("gain", harness.run(0.01).output(0).dc_offset(), 1.5, 1e-6);
+    harness.set_input(1, Input::Constant(1.0));
+    assert_near("cv", harness.run(0.01).output(0).dc_offset(), 4.5, 1e-6);
+}
+
+fn set_param(module: &dyn Module, name: &str, value: f32) {
+    let params = HashMap::from([(name.to_owned(), SerializedParameter::Num(value))]);
+    module.params().unwrap().deserialize(&params);
+}
+
+#[test]
+fn slow_module_is_stopped_until_reset() {
+    let mut registry = ModuleRegistry::default();
+    wasm_host::load_bytes(&mut registry, "slow", &slow_module()).unwrap();
+    let (handle, module) = registry.create_module("wasm::slow").unwrap();
+    let mut rack = Rack::new();
+    rack.add_audio_unit(handle, 0, 1, Vec::new(), module.create_audio_unit());
+    rack.connect(handle.output(0), Rack::audio_output())
+        .unwrap();
+    rack.reset(SAMPLE_RATE);
+    assert_eq!(rack.tick(), 1.0);
+
+    // It can run over its budget for a few samples, but not for long, and the rack is told when
+    // it's stopped.
+    set_param(module.as_ref(), "param1", 1.0);
+    assert_eq!(rack.tick(), 1.0);
+    let outputs: Vec<f32> = (0..SAMPLE_RATE / 100).map(|_| rack.tick()).collect();
+    assert_eq!(outputs.last(), Some(&0.0));
+    let mut faults = Vec::new();
+    rack.drain_faults(|handle, fault| faults.push((handle, fault)));
+    assert_eq!(faults, [(handle, ModuleFault::Panicked)]);
+
+    // Even once it speeds up, it stays silent until it's restarted.
+    set_param(module.as_ref(), "param1", 0.0);
+    assert_eq!(rack.tick(), 0.0);
+    rack.reset_module(handle).unwrap();
+    assert_eq!(rack.tick(), 1.0);
+}
+
+#[test]
+fn limits_memory() {
+    let mut registry = ModuleRegistry::default();
+    wasm_host::load_bytes(&mut registry, "greedy", &greedy_module()).unwrap();
+    let mut harness = Harness::from_registry(&mut registry, "wasm::greedy", SAMPLE_RATE).unwrap();
+    // Growing memory past the limit fails as if the memory had run out, and the module carries on.
+    assert_eq!(harness.run(0.01).output(0).samples(), [-1.0; 480]);
+}
+
+#[test]
+fn rejects_invalid_modules() {
+    let mut registry = ModuleRegistry::default();
+    let newer = wat::parse_str(format!(
+        r#"(module {} (func (export "tick") (param i32)))"#,
+        required_exports(2, 0, 0, 0)
+    ))
+    .unwrap();
+    assert!(matches!(
+        wasm_host::load_bytes(&mut registry, "newer", &newer),
+        Err(WasmError::IncompatibleVersion {
+            found: 2,
+            expected: 1
+        })
+    ));
+
+    let no_tick = wat::parse_str(format!("(module {})", required_exports(1, 0, 0, 0))).unwrap();
+    assert!(matches!(
+        wasm_host::load_bytes(&mut registry, "no_tick", &no_tick),
+        Err(WasmError::MissingExport("tick"))
+    ));
+    assert!(matches!(
+        wasm_host::load_bytes(&mut registry, "garbage", b"not wasm"),
+        Err(WasmError::Wasm(_))
+    ));
+    assert!(registry.manifest("wasm::newer").is_none());
+}
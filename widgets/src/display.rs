@@ -0,0 +1,54 @@
+use egui::*;
+
+const DEFAULT_COLOR: Color32 = Color32::from_rgb(120, 230, 160);
+
+/// A small screen showing a short readout, such as a tempo or note name, in a monospace font.
+pub struct Display {
+    text: String,
+    chars: usize,
+    color: Color32,
+}
+
+impl Display {
+    pub fn new(text: impl ToString) -> Self {
+        Display {
+            text: text.to_string(),
+            chars: 4,
+            color: DEFAULT_COLOR,
+        }
+    }
+
+    /// The number of characters the display has room for, which sets its width. Longer text is
+    /// cut off.
+    pub fn chars(mut self, chars: usize) -> Self {
+        self.chars = chars.max(1);
+        self
+    }
+
+    pub fn color(mut self, color: Color32) -> Self {
+        self.color = color;
+        self
+    }
+}
+
+impl Widget for Display {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let font = TextStyle::Monospace.resolve(ui.style());
+        let char_width = ui.fonts().glyph_width(&font, '0');
+        let padding = vec2(4.0, 2.0);
+        let size = vec2(self.chars as f32 * char_width, font.size) + 2.0 * padding;
+        let (rect, response) = ui.allocate_exact_size(size, Sense::focusable_noninteractive());
+        if ui.is_rect_visible(rect) {
+            let painter = ui.painter();
+            painter.rect(
+                rect,
+                2.0,
+                Color32::from_gray(16),
+                ui.visuals().widgets.noninteractive.bg_stroke,
+            );
+            let text: String = self.text.chars().take(self.chars).collect();
+            painter.text(rect.center(), Align2::CENTER_CENTER, text, font, self.color);
+        }
+        response
+    }
+}
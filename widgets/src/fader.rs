@@ -0,0 +1,108 @@
+use std::ops::RangeInclusive;
+
+use egui::*;
+use module::Parameter;
+
+use crate::input;
+
+/// A vertical slider for a continuous parameter.
+pub struct Fader<'a> {
+    param: &'a dyn Parameter<Value = f32>,
+    range: RangeInclusive<f32>,
+    default: Option<f32>,
+    height: f32,
+    hover_text: Box<dyn Fn(f32) -> String>,
+}
+
+impl<'a> Fader<'a> {
+    pub fn new(param: &'a dyn Parameter<Value = f32>) -> Self {
+        Fader {
+            param,
+            range: 0.0..=1.0,
+            default: None,
+            height: 100.0,
+            hover_text: Box::new(|v| format!("{:0.3}", v)),
+        }
+    }
+
+    pub fn range(mut self, range: RangeInclusive<f32>) -> Self {
+        self.range = range;
+        self
+    }
+
+    /// The value restored by double clicking. Without one, the bottom of the range is used.
+    pub fn default_value(mut self, default: f32) -> Self {
+        self.default = Some(default);
+        self
+    }
+
+    pub fn height(mut self, height: f32) -> Self {
+        self.height = height;
+        self
+    }
+
+    pub fn hover_text<F>(mut self, format: F) -> Self
+    where
+        F: 'static + Fn(f32) -> String,
+    {
+        self.hover_text = Box::new(format);
+        self
+    }
+}
+
+impl<'a> Widget for Fader<'a> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let width = ui.spacing().interact_size.y;
+        let (rect, response) =
+            ui.allocate_exact_size(vec2(width, self.height), Sense::click_and_drag());
+        let mut response = response.on_hover_cursor(CursorIcon::ResizeVertical);
+        input::focus_on_click(&response);
+
+        // The cap travels between these, so that it never hangs off the ends.
+        let cap_height = 0.5 * width;
+        let travel = rect.y_range();
+        let travel = travel.start() + 0.5 * cap_height..=travel.end() - 0.5 * cap_height;
+
+        // Interact:
+        let value = self.param.read();
+        let mut normal = remap_clamp(value, self.range.clone(), 0.0..=1.0);
+        let precise = ui.input().modifiers.shift;
+        let fine = if precise { 0.1 } else { 1.0 };
+        if response.double_clicked() {
+            let default = self.default.unwrap_or(*self.range.start());
+            normal = remap_clamp(default, self.range.clone(), 0.0..=1.0);
+        } else if response.dragged() {
+            let delta = -response.drag_delta().y / (travel.end() - travel.start());
+            normal += fine * delta;
+        }
+        normal += fine * 0.05 * input::take_scroll(ui, &response);
+        normal += fine * 0.01 * input::arrow_steps(ui, &response) as f32;
+        let normal = normal.clamp(0.0, 1.0);
+        let new_value = remap(normal, 0.0..=1.0, self.range.clone());
+        if new_value != value {
+            self.param.write(new_value);
+            response.mark_changed();
+        }
+
+        if response.dragged() || response.hovered() {
+            show_tooltip_for(ui.ctx(), response.id, &rect, |ui| {
+                ui.small((self.hover_text)(new_value));
+            });
+        }
+
+        // Draw:
+        if ui.is_rect_visible(rect) {
+            let painter = ui.painter();
+            let widget = ui.style().interact(&response);
+            let slot = Rect::from_center_size(rect.center(), vec2(0.2 * width, rect.height()));
+            painter.rect(slot, 2.0, ui.visuals().extreme_bg_color, widget.bg_stroke);
+            let y = remap(normal, 0.0..=1.0, *travel.end()..=*travel.start());
+            let cap = Rect::from_center_size(pos2(rect.center().x, y), vec2(width, cap_height));
+            painter.rect(cap, 2.0, widget.bg_fill, widget.fg_stroke);
+            painter.line_segment([cap.left_center(), cap.right_center()], widget.fg_stroke);
+            input::paint_focus(ui, &response, rect, 2.0);
+        }
+
+        response
+    }
+}
@@ -0,0 +1,61 @@
+//! Input handling shared by the widgets that are bound to parameters.
+
+use egui::*;
+
+/// The scroll distance of one notch of a mouse wheel, in points.
+const SCROLL_NOTCH: f32 = 50.0;
+
+/// Takes any scrolling over a hovered widget, so that the rack doesn't scroll as well. Returns
+/// the distance in notches, where scrolling up is positive.
+pub(crate) fn take_scroll(ui: &Ui, response: &Response) -> f32 {
+    if !response.hovered() {
+        return 0.0;
+    }
+    let mut input = ui.ctx().input_mut();
+    let delta = input.scroll_delta.y;
+    input.scroll_delta = Vec2::ZERO;
+    delta / SCROLL_NOTCH
+}
+
+/// Takes scrolling as whole notches, for widgets with discrete values. Smooth scrolling, as from
+/// a trackpad, is added up until it reaches a notch.
+pub(crate) fn take_scroll_steps(ui: &Ui, response: &Response) -> i32 {
+    let id = response.id.with("scroll");
+    let total = ui.memory().data.get_temp::<f32>(id).unwrap_or(0.0) + take_scroll(ui, response);
+    let steps = total.trunc();
+    ui.memory().data.insert_temp(id, total - steps);
+    steps as i32
+}
+
+/// The number of steps to move a focused widget by, from the arrow keys. Up and right are
+/// positive.
+pub(crate) fn arrow_steps(ui: &Ui, response: &Response) -> i32 {
+    if !response.has_focus() {
+        return 0;
+    }
+    let input = ui.input();
+    let mut steps = 0;
+    if input.key_pressed(Key::ArrowUp) || input.key_pressed(Key::ArrowRight) {
+        steps += 1;
+    }
+    if input.key_pressed(Key::ArrowDown) || input.key_pressed(Key::ArrowLeft) {
+        steps -= 1;
+    }
+    steps
+}
+
+/// Gives a widget keyboard focus when it's clicked or dragged, so that the keyboard can adjust
+/// it afterwards.
+pub(crate) fn focus_on_click(response: &Response) {
+    if response.clicked() || response.drag_started() {
+        response.request_focus();
+    }
+}
+
+/// Outlines a widget that has keyboard focus.
+pub(crate) fn paint_focus(ui: &Ui, response: &Response, rect: Rect, rounding: f32) {
+    if response.has_focus() {
+        ui.painter()
+            .rect_stroke(rect.expand(2.0), rounding, ui.visuals().selection.stroke);
+    }
+}
@@ -1,7 +1,8 @@
 use std::{f32::consts::FRAC_PI_3, hash::Hash};
 
 use egui::*;
-use module::{ModuleInput, ModuleOutput};
+use eurorack::CV_VOLTS;
+use module::{ModuleInput, ModuleOutput, SignalLevel};
 
 pub fn inputs<R>(ui: &mut Ui, add_contents: impl FnOnce(&mut Ui) -> R) -> InnerResponse<R> {
     Frame::group(ui.style())
@@ -23,6 +24,11 @@ pub struct Jack {
 }
 
 impl Jack {
+    /// The space a jack takes up.
+    pub fn size(ui: &Ui) -> Vec2 {
+        1.5 * ui.spacing().interact_size.y * vec2(1.0, FRAC_PI_3.sin())
+    }
+
     pub fn input(input: ModuleInput) -> Self {
         Jack {
             type_: JackType::Input(input),
@@ -39,15 +45,20 @@ impl Jack {
 impl Widget for Jack {
     fn ui(self, ui: &mut Ui) -> Response {
         let radius = 0.75 * ui.spacing().interact_size.y;
-        let desired_size = 2.0 * radius * vec2(1.0, FRAC_PI_3.sin());
-        let (rect, response) = ui.allocate_exact_size(desired_size, Sense::click());
+        let (rect, response) = ui.allocate_exact_size(Jack::size(ui), Sense::click());
 
         // Update our position, for cable drawing:
         let origin = rect.center();
-        match self.type_ {
-            JackType::Input(input) => update_position(ui, input, origin),
-            JackType::Output(output) => update_position(ui, output, origin),
-        }
+        let level = match self.type_ {
+            JackType::Input(input) => {
+                update_position(ui, input, origin);
+                signal(ui, input)
+            }
+            JackType::Output(output) => {
+                update_position(ui, output, origin);
+                signal(ui, output)
+            }
+        };
 
         // Interact:
         if response.clicked_by(PointerButton::Primary) {
@@ -96,9 +107,27 @@ impl Widget for Jack {
                 widget.fg_stroke,
             );
             painter.circle_filled(origin, 0.4 * radius, widget.fg_stroke.color);
+
+            // Glow with the signal:
+            if let Some(level) = level {
+                let color = signal_color(&level);
+                let intensity = signal_intensity(&level);
+                painter.circle_filled(origin, 0.4 * radius, color.linear_multiply(intensity));
+                painter.circle_stroke(
+                    origin,
+                    0.6 * radius,
+                    Stroke::new(0.3 * radius, color.linear_multiply(0.4 * intensity)),
+                );
+            }
         }
 
-        response
+        match level {
+            Some(level) => response.on_hover_text(format!(
+                "{:+.2} V\npeak {:.2} V · RMS {:.2} V · mean {:+.2} V",
+                level.value, level.peak, level.rms, level.mean
+            )),
+            None => response,
+        }
     }
 }
 
@@ -131,6 +160,49 @@ impl JackInteraction {
     }
 }
 
+/// Sets the signal shown on a jack, or clears it if there is none. This must be done before the
+/// jack is drawn each frame.
+pub fn set_signal<T>(ui: &Ui, io: T, level: Option<SignalLevel>)
+where
+    T: Hash,
+{
+    let id = Id::new(io).with("signal");
+    match level {
+        Some(level) => ui.memory().data.insert_temp(id, level),
+        None => ui.memory().data.remove::<SignalLevel>(id),
+    }
+}
+
+fn signal<T>(ui: &Ui, io: T) -> Option<SignalLevel>
+where
+    T: Hash,
+{
+    ui.memory().data.get_temp(Id::new(io).with("signal"))
+}
+
+/// The colour for a signal's polarity: warm for positive signals, cool for negative ones, and
+/// pale for signals that swing both ways.
+pub fn signal_color(level: &SignalLevel) -> Color32 {
+    const BIPOLAR: [f32; 3] = [240.0, 230.0, 200.0];
+    const POSITIVE: [f32; 3] = [250.0, 120.0, 40.0];
+    const NEGATIVE: [f32; 3] = [60.0, 140.0, 250.0];
+    let polarity = if level.peak > 0.0 {
+        (level.mean / level.peak).clamp(-1.0, 1.0)
+    } else {
+        0.0
+    };
+    let target = if polarity >= 0.0 { POSITIVE } else { NEGATIVE };
+    let t = polarity.abs();
+    let [r, g, b] = [0, 1, 2].map(|i| (BIPOLAR[i] + t * (target[i] - BIPOLAR[i])) as u8);
+    Color32::from_rgb(r, g, b)
+}
+
+/// How brightly a signal should glow, from 0 to 1. Quiet signals are boosted so that they can
+/// still be seen.
+pub fn signal_intensity(level: &SignalLevel) -> f32 {
+    (level.peak / CV_VOLTS).clamp(0.0, 1.0).sqrt()
+}
+
 fn update_position<T>(ui: &Ui, io: T, position: Pos2)
 where
     T: Hash,
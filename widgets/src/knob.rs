@@ -3,11 +3,16 @@ use std::{f32::consts::PI, ops::RangeInclusive};
 use egui::*;
 use module::Parameter;
 
+use crate::input;
+
 pub struct Knob<'a> {
     param: &'a dyn Parameter<Value = f32>,
     scale: f32,
     range: Range,
+    step: Option<f32>,
+    default: Option<f32>,
     snap_to_center: bool,
+    display_scale: f32,
     hover_text: Box<dyn Fn(f32) -> String>,
 }
 
@@ -17,7 +22,10 @@ impl<'a> Knob<'a> {
             param,
             scale: 1.0,
             range: Range::Linear(0.0..=1.0),
+            step: None,
+            default: None,
             snap_to_center: false,
+            display_scale: 1.0,
             hover_text: Box::new(|v| format!("{:0.3}", v)),
         }
     }
@@ -35,16 +43,65 @@ impl<'a> Knob<'a> {
             .hover_text(|v| format!("{:.0} Hz", v))
     }
 
+    /// A transposition of up to an octave either way, in volts, stepped in semitones.
+    pub fn semitones(param: &'a dyn Parameter<Value = f32>) -> Self {
+        Knob::new(param)
+            .range(-1.0..=1.0)
+            .step(1.0 / 12.0)
+            .snap_to_center()
+            .display_scale(12.0)
+            .hover_text(|v| format!("{:+.0} st", 12.0 * v))
+    }
+
     pub fn range(mut self, range: RangeInclusive<f32>) -> Self {
         self.range = Range::Linear(range);
         self
     }
 
+    /// Spreads the range so that each ratio, such as an octave, turns the knob by the same
+    /// amount. The range must be above zero.
     pub fn logarithmic(mut self, range: RangeInclusive<f32>) -> Self {
         self.range = Range::Logarithmic(range);
         self
     }
 
+    /// Curves the range so that the value grows with the knob's position raised to `exponent`.
+    /// Exponents above 1 give finer control at the bottom of the range.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `exponent` isn't above zero.
+    pub fn exponential(mut self, range: RangeInclusive<f32>, exponent: f32) -> Self {
+        assert!(exponent > 0.0, "knob exponent must be above zero");
+        self.range = Range::Exponential(range, exponent);
+        self
+    }
+
+    /// For a gain parameter, spreads the knob evenly across a range in decibels. The bottom of
+    /// the knob mutes.
+    pub fn decibels(mut self, range: RangeInclusive<f32>) -> Self {
+        self.range = Range::Decibels(range);
+        self.hover_text = Box::new(|v| match v {
+            v if v > 0.0 => format!("{:+.1} dB", 20.0 * v.log10()),
+            _ => "-inf dB".to_string(),
+        });
+        self
+    }
+
+    /// Quantizes values to multiples of `step`, such as `1.0 / 12.0` for semitones in volts per
+    /// octave.
+    pub fn step(mut self, step: f32) -> Self {
+        self.step = Some(step).filter(|&s| s > 0.0);
+        self
+    }
+
+    /// The value restored by double clicking. Without one, knobs that snap to center return
+    /// there and others return to the bottom of their range.
+    pub fn default_value(mut self, default: f32) -> Self {
+        self.default = Some(default);
+        self
+    }
+
     pub fn scale(mut self, scale: f32) -> Self {
         self.scale = scale;
         self
@@ -55,6 +112,13 @@ impl<'a> Knob<'a> {
         self
     }
 
+    /// The factor between the parameter and the units shown in the hover text, such as `1000.0`
+    /// for seconds shown in ms. Typed values are taken in the same units.
+    pub fn display_scale(mut self, scale: f32) -> Self {
+        self.display_scale = scale;
+        self
+    }
+
     pub fn hover_text<F>(mut self, format: F) -> Self
     where
         F: 'static + Fn(f32) -> String,
@@ -62,6 +126,82 @@ impl<'a> Knob<'a> {
         self.hover_text = Box::new(format);
         self
     }
+
+    fn default(&self) -> f32 {
+        match self.default {
+            Some(default) => default,
+            None if self.snap_to_center => self.range.from_normal(0.5),
+            None => self.range.from_normal(0.0),
+        }
+    }
+
+    fn quantize(&self, value: f32) -> f32 {
+        let value = match self.step {
+            Some(step) => (value / step).round() * step,
+            None => value,
+        };
+        self.range.clamp(value)
+    }
+
+    /// Moves the value by a number of steps, or by `fraction` of the knob's travel for each one
+    /// if the knob isn't stepped.
+    fn nudge(&self, value: f32, steps: f32, fraction: f32) -> f32 {
+        match self.step {
+            Some(step) => self.quantize(value + steps.round() * step),
+            None => {
+                let normal = self.range.to_normal(value) + steps * fraction;
+                self.range.from_normal(normal.clamp(0.0, 1.0))
+            }
+        }
+    }
+
+    /// Shows a text box for typing in a value, after the knob is right clicked. Returns the
+    /// value once it's entered.
+    fn entry(&self, ui: &Ui, response: &Response, value: f32) -> Option<f32> {
+        let id = response.id.with("entry");
+        let opened = response.secondary_clicked();
+        if opened {
+            let text = match self.range {
+                Range::Decibels(_) if value > 0.0 => format!("{:.1}", 20.0 * value.log10()),
+                _ => format!("{}", value * self.display_scale),
+            };
+            ui.memory().data.insert_temp(id, text);
+        }
+        let mut text = ui.memory().data.get_temp::<String>(id)?;
+
+        let pos = response.rect.center_bottom() + vec2(-40.0, 4.0);
+        let edit = Area::new(id)
+            .order(Order::Foreground)
+            .fixed_pos(pos)
+            .show(ui.ctx(), |ui| {
+                Frame::popup(ui.style())
+                    .show(ui, |ui| {
+                        ui.add(TextEdit::singleline(&mut text).desired_width(64.0))
+                    })
+                    .inner
+            })
+            .inner;
+        if opened {
+            edit.request_focus();
+        }
+
+        let entered = ui.input().key_pressed(Key::Enter);
+        if ui.input().key_pressed(Key::Escape) || (edit.lost_focus() && !entered) {
+            ui.memory().data.remove::<String>(id);
+            None
+        } else if entered {
+            ui.memory().data.remove::<String>(id);
+            let typed = parse_number(&text)?;
+            let typed = match self.range {
+                Range::Decibels(_) => 10f32.powf(typed / 20.0),
+                _ => typed / self.display_scale,
+            };
+            Some(self.quantize(typed))
+        } else {
+            ui.memory().data.insert_temp(id, text);
+            None
+        }
+    }
 }
 
 impl<'a> Widget for Knob<'a> {
@@ -73,23 +213,52 @@ impl<'a> Widget for Knob<'a> {
         let (rect, response) = ui.allocate_exact_size(desired_size, Sense::click_and_drag());
         let mut response = response.on_hover_cursor(CursorIcon::Grab);
 
+        input::focus_on_click(&response);
+
         // Interact:
-        let mut value = self.param.read();
-        let mut normalized_value = self.range.to_normal(value);
-        if response.dragged() {
+        let value = self.param.read();
+        let mut new_value = value;
+        let precise = ui.ctx().input().modifiers.shift;
+        let fine = if precise { 0.1 } else { 1.0 };
+        if response.double_clicked() {
+            new_value = self.quantize(self.default());
+        }
+        // The knob's position is followed without quantizing while dragging, so that slow drags
+        // still reach the next step.
+        let drag_id = response.id.with("drag");
+        if !response.dragged() {
+            ui.memory().data.remove::<f32>(drag_id);
+        } else if !response.double_clicked() {
             ui.output().cursor_icon = CursorIcon::Grabbing;
-            let precise = ui.ctx().input().modifiers.shift;
-            let delta = -response.drag_delta().y / drag_height * if precise { 0.1 } else { 1.0 };
+            let delta = -fine * response.drag_delta().y / drag_height;
             if delta != 0.0 {
-                normalized_value = (normalized_value + delta).clamp(0.0, 1.0);
-                if self.snap_to_center && !precise && value_near(normalized_value, 0.5) {
-                    normalized_value = 0.5;
+                let normal = ui.memory().data.get_temp::<f32>(drag_id);
+                let mut normal = normal.unwrap_or_else(|| self.range.to_normal(value));
+                normal = (normal + delta).clamp(0.0, 1.0);
+                ui.memory().data.insert_temp(drag_id, normal);
+                if self.snap_to_center && !precise && value_near(normal, 0.5) {
+                    normal = 0.5;
                 }
-                value = self.range.from_normal(normalized_value);
-                self.param.write(value);
-                response.mark_changed();
+                new_value = self.quantize(self.range.from_normal(normal));
             }
         }
+        let scroll = match self.step {
+            Some(_) => input::take_scroll_steps(ui, &response) as f32,
+            None => input::take_scroll(ui, &response),
+        };
+        let steps = scroll + input::arrow_steps(ui, &response) as f32;
+        if steps != 0.0 {
+            new_value = self.nudge(new_value, steps, fine * 0.02);
+        }
+        if let Some(typed) = self.entry(ui, &response, value) {
+            new_value = typed;
+        }
+        if new_value != value {
+            self.param.write(new_value);
+            response.mark_changed();
+        }
+        let value = new_value;
+        let normalized_value = self.range.to_normal(value);
 
         if response.dragged() || response.hovered() {
             show_tooltip_for(ui.ctx(), Id::null(), &rect, |ui| {
@@ -129,6 +298,8 @@ impl<'a> Widget for Knob<'a> {
             let mut stroke = widget.fg_stroke;
             stroke.width = (3.0 * self.scale).max(1.5).max(stroke.width);
             painter.line_segment([origin + 0.4 * dir, origin + dir], stroke);
+
+            input::paint_focus(ui, &response, rect, radius);
         }
 
         response
@@ -139,6 +310,9 @@ impl<'a> Widget for Knob<'a> {
 enum Range {
     Linear(RangeInclusive<f32>),
     Logarithmic(RangeInclusive<f32>),
+    Exponential(RangeInclusive<f32>, f32),
+    /// A linear range in decibels, over a parameter holding a gain.
+    Decibels(RangeInclusive<f32>),
 }
 
 impl Range {
@@ -146,17 +320,54 @@ impl Range {
         match self {
             Self::Linear(range) => remap_clamp(value, range.clone(), 0.0..=1.0),
             Self::Logarithmic(range) => {
-                remap_clamp(value, range.clone(), 1.0..=100.0).log10() / 2.0
+                let (start, end) = log_bounds(range);
+                remap_clamp(value.max(f32::MIN_POSITIVE).ln(), start..=end, 0.0..=1.0)
+            }
+            Self::Exponential(range, exponent) => {
+                remap_clamp(value, range.clone(), 0.0..=1.0).powf(exponent.recip())
+            }
+            Self::Decibels(range) if value > 0.0 => {
+                remap_clamp(20.0 * value.log10(), range.clone(), 0.0..=1.0)
             }
+            Self::Decibels(_) => 0.0,
         }
     }
 
     fn from_normal(&self, normal: f32) -> f32 {
         match self {
             Self::Linear(range) => remap(normal, 0.0..=1.0, range.clone()),
-            Self::Logarithmic(range) => remap(10f32.powf(2.0 * normal), 1.0..=100.0, range.clone()),
+            Self::Logarithmic(range) => {
+                let (start, end) = log_bounds(range);
+                remap(normal, 0.0..=1.0, start..=end).exp()
+            }
+            Self::Exponential(range, exponent) => {
+                remap(normal.powf(*exponent), 0.0..=1.0, range.clone())
+            }
+            Self::Decibels(_) if normal <= 0.0 => 0.0,
+            Self::Decibels(range) => 10f32.powf(remap(normal, 0.0..=1.0, range.clone()) / 20.0),
         }
     }
+
+    fn clamp(&self, value: f32) -> f32 {
+        let (a, b) = (self.from_normal(0.0), self.from_normal(1.0));
+        value.clamp(a.min(b), a.max(b))
+    }
+}
+
+/// The natural logs of a logarithmic range's ends.
+fn log_bounds(range: &RangeInclusive<f32>) -> (f32, f32) {
+    let start = range.start().max(f32::MIN_POSITIVE);
+    (start.ln(), range.end().max(start).ln())
+}
+
+/// Reads the number at the start of some typed text, ignoring any units after it.
+fn parse_number(text: &str) -> Option<f32> {
+    let text = text.trim();
+    let end = text
+        .char_indices()
+        .find(|&(i, c)| !(c.is_ascii_digit() || c == '.' || (i == 0 && (c == '-' || c == '+'))))
+        .map_or(text.len(), |(i, _)| i);
+    text[..end].parse().ok()
 }
 
 fn value_near(value: f32, target: f32) -> bool {
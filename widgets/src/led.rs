@@ -0,0 +1,111 @@
+use egui::*;
+
+const DEFAULT_COLOR: Color32 = Color32::from_rgb(250, 70, 50);
+
+/// A round light, which may be dimmed to show how strong something is.
+pub struct Led {
+    brightness: f32,
+    color: Color32,
+    scale: f32,
+}
+
+impl Led {
+    pub fn new(on: bool) -> Self {
+        Led {
+            brightness: if on { 1.0 } else { 0.0 },
+            color: DEFAULT_COLOR,
+            scale: 1.0,
+        }
+    }
+
+    /// How brightly the light is lit, from 0 (off) to 1.
+    pub fn brightness(mut self, brightness: f32) -> Self {
+        self.brightness = brightness.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn color(mut self, color: Color32) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+}
+
+impl Widget for Led {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let radius = self.scale * 0.3 * ui.spacing().interact_size.y;
+        let (rect, response) =
+            ui.allocate_exact_size(vec2(2.0, 2.0) * radius, Sense::focusable_noninteractive());
+        if ui.is_rect_visible(rect) {
+            paint_led(ui, rect.center(), radius, self.color, self.brightness);
+        }
+        response
+    }
+}
+
+fn paint_led(ui: &Ui, center: Pos2, radius: f32, color: Color32, brightness: f32) {
+    let painter = ui.painter();
+    let visuals = ui.visuals();
+    // A soft halo, so that lit LEDs stand out from the panel. It goes underneath, so that it
+    // doesn't wash out the LED itself.
+    if brightness > 0.0 {
+        painter.circle_filled(
+            center,
+            1.4 * radius,
+            color.linear_multiply(0.2 * brightness),
+        );
+    }
+    painter.circle(
+        center,
+        radius,
+        visuals.extreme_bg_color,
+        visuals.widgets.noninteractive.bg_stroke,
+    );
+    if brightness > 0.0 {
+        painter.circle_filled(center, 0.8 * radius, color.linear_multiply(brightness));
+    }
+}
+
+/// A row of LEDs showing which step of a sequence is playing.
+pub struct StepIndicator {
+    steps: usize,
+    current: Option<usize>,
+    color: Color32,
+}
+
+impl StepIndicator {
+    pub fn new(steps: usize, current: Option<usize>) -> Self {
+        StepIndicator {
+            steps,
+            current,
+            color: DEFAULT_COLOR,
+        }
+    }
+
+    pub fn color(mut self, color: Color32) -> Self {
+        self.color = color;
+        self
+    }
+}
+
+impl Widget for StepIndicator {
+    /// Spreads the LEDs evenly across the available width.
+    fn ui(self, ui: &mut Ui) -> Response {
+        let radius = 0.3 * ui.spacing().interact_size.y;
+        let size = vec2(ui.available_width(), 2.0 * radius);
+        let (rect, response) = ui.allocate_exact_size(size, Sense::focusable_noninteractive());
+        if ui.is_rect_visible(rect) && self.steps > 0 {
+            let spacing = rect.width() / self.steps as f32;
+            for step in 0..self.steps {
+                let center = pos2(rect.left() + (step as f32 + 0.5) * spacing, rect.center().y);
+                let brightness = if self.current == Some(step) { 1.0 } else { 0.0 };
+                paint_led(ui, center, radius, self.color, brightness);
+            }
+        }
+        response
+    }
+}
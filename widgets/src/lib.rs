@@ -1,6 +1,15 @@
 pub use egui;
 
+pub mod display;
+pub mod fader;
 pub mod icons;
 pub mod jack;
 pub mod knob;
+pub mod led;
+pub mod meter;
+pub mod panel;
+pub mod selector;
 pub mod signal;
+pub mod toggle;
+
+mod input;
@@ -0,0 +1,110 @@
+use std::ops::RangeInclusive;
+
+use egui::*;
+
+/// How long the peak marker stays put before falling, in seconds.
+const PEAK_HOLD_SECS: f64 = 1.0;
+/// How quickly the peak marker falls once released, in decibels per second.
+const PEAK_FALL_DB: f32 = 20.0;
+
+/// A vertical level meter on a decibel scale, with a marker that holds recent peaks.
+pub struct Meter {
+    level: f32,
+    range: RangeInclusive<f32>,
+    height: f32,
+}
+
+impl Meter {
+    /// Shows a linear level, where 1 is 0 dB.
+    pub fn new(level: f32) -> Self {
+        Meter {
+            level,
+            range: -60.0..=6.0,
+            height: 120.0,
+        }
+    }
+
+    /// The decibel levels at the bottom and top of the meter.
+    pub fn range_db(mut self, range: RangeInclusive<f32>) -> Self {
+        self.range = range;
+        self
+    }
+
+    pub fn height(mut self, height: f32) -> Self {
+        self.height = height;
+        self
+    }
+}
+
+impl Widget for Meter {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let width = 0.5 * ui.spacing().interact_size.y;
+        let (rect, response) =
+            ui.allocate_exact_size(vec2(width, self.height), Sense::focusable_noninteractive());
+
+        // The held peak is remembered between frames, with the time it was reached.
+        let db = 20.0 * self.level.abs().max(1e-9).log10();
+        let now = ui.input().time;
+        let dt = ui.input().unstable_dt;
+        let (mut peak, mut peak_time) = ui
+            .memory()
+            .data
+            .get_temp::<(f32, f64)>(response.id)
+            .unwrap_or((db, now));
+        if db >= peak {
+            (peak, peak_time) = (db, now);
+        } else if now - peak_time > PEAK_HOLD_SECS {
+            peak = (peak - PEAK_FALL_DB * dt).max(db);
+        }
+        ui.memory().data.insert_temp(response.id, (peak, peak_time));
+
+        if ui.is_rect_visible(rect) {
+            let (min, max) = (*self.range.start(), *self.range.end());
+            let y_for = |db: f32| remap_clamp(db, min..=max, rect.bottom()..=rect.top());
+            let painter = ui.painter();
+            let visuals = ui.visuals();
+            painter.rect(
+                rect,
+                2.0,
+                visuals.extreme_bg_color,
+                visuals.widgets.noninteractive.bg_stroke,
+            );
+
+            // The bar is green up to -6 dB, yellow up to 0 dB, and red above.
+            let zones = [
+                (min, -6.0, Color32::from_rgb(80, 200, 100)),
+                (-6.0, 0.0, Color32::from_rgb(230, 200, 60)),
+                (0.0, max, Color32::from_rgb(230, 70, 50)),
+            ];
+            for (bottom, top, color) in zones {
+                let top = top.min(db);
+                if top > bottom {
+                    let zone = Rect::from_x_y_ranges(rect.x_range(), y_for(top)..=y_for(bottom));
+                    painter.rect_filled(zone.shrink(1.0), 0.0, color);
+                }
+            }
+
+            // Ticks every 6 dB, and the held peak:
+            let mut tick = 0.0;
+            while tick > min {
+                if tick < max {
+                    let y = y_for(tick);
+                    painter.line_segment(
+                        [pos2(rect.left(), y), pos2(rect.left() + 0.3 * width, y)],
+                        visuals.widgets.noninteractive.bg_stroke,
+                    );
+                }
+                tick -= 6.0;
+            }
+            if peak > min {
+                let y = y_for(peak);
+                painter.line_segment(
+                    [pos2(rect.left(), y), pos2(rect.right(), y)],
+                    Stroke::new(2.0, visuals.strong_text_color()),
+                );
+            }
+        }
+
+        response.on_hover_text(format!("{:.1} dB (peak {:.1} dB)", db, peak))
+    }
+}
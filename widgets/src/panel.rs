@@ -0,0 +1,202 @@
+use std::sync::Arc;
+
+use egui::*;
+use module::{Module, ModuleHandle, Panel, ParameterInfo, ParameterRef, Parameters};
+
+use crate::{
+    jack::{self, Jack},
+    knob::Knob,
+    selector::Selector,
+    signal::SignalFlow,
+    toggle::Toggle,
+};
+
+/// The number of controls or jacks laid out side by side in each 4 HP of width.
+const ITEMS_PER_4HP: usize = 2;
+
+/// A panel laid out from a module's port names and parameter metadata, for modules that don't
+/// need one of their own.
+///
+/// Knobs, switches and small choices are placed in rows under the title, with any choices that
+/// have named options given a row each. Inputs and outputs are placed along the bottom.
+///
+/// A module with derived [`Parameters`] only needs to return
+/// `Box::new(GenericPanel::new("Name", self).params(self.params.clone()))` from
+/// [`Module::create_panel`].
+pub struct GenericPanel {
+    title: String,
+    width: Option<usize>,
+    params: Option<Arc<dyn Parameters>>,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+}
+
+impl GenericPanel {
+    /// Creates a panel for a module, labelling its jacks with the module's port names.
+    pub fn new(title: impl ToString, module: &dyn Module) -> Self {
+        let names = |count: usize, names: &[&str], prefix: &str| -> Vec<String> {
+            (0..count)
+                .map(|i| match names.get(i) {
+                    Some(name) => capitalize(name),
+                    None => format!("{} {}", prefix, i + 1),
+                })
+                .collect()
+        };
+        GenericPanel {
+            title: title.to_string(),
+            width: None,
+            params: None,
+            inputs: names(module.inputs(), module.input_names(), "In"),
+            outputs: names(module.outputs(), module.output_names(), "Out"),
+        }
+    }
+
+    /// The parameters to show controls for, which the panel shares with the module.
+    pub fn params(mut self, params: Arc<dyn Parameters>) -> Self {
+        self.params = Some(params);
+        self
+    }
+
+    /// The panel's width in HP. Without one, the panel is made wide enough for its widest row.
+    pub fn width(mut self, hp: usize) -> Self {
+        self.width = Some(hp);
+        self
+    }
+
+    fn columns(&self) -> usize {
+        (Panel::width(self) / 4 * ITEMS_PER_4HP).max(1)
+    }
+}
+
+impl Panel for GenericPanel {
+    fn width(&self) -> usize {
+        self.width.unwrap_or_else(|| {
+            let controls = self.params.as_ref().map_or(0, |params| {
+                params.info().iter().filter(|info| !is_row(info)).count()
+            });
+            let widest = controls.max(self.inputs.len()).max(self.outputs.len());
+            4 * widest.div_ceil(ITEMS_PER_4HP).max(1)
+        })
+    }
+
+    fn update(&mut self, handle: &ModuleHandle, ui: &mut Ui) {
+        let columns = self.columns();
+        ui.heading(&self.title);
+        ui.add_space(10.0);
+
+        if let Some(params) = &self.params {
+            let info = params.info();
+            let (rows, controls): (Vec<_>, Vec<_>) = info.iter().partition(|info| is_row(info));
+            for row in controls.chunks(columns) {
+                ui.columns(columns, |uis| {
+                    for (ui, info) in uis.iter_mut().zip(row) {
+                        ui.vertical_centered(|ui| control(ui, info));
+                    }
+                });
+                ui.add_space(10.0);
+            }
+            for info in rows {
+                ui.vertical_centered(|ui| control(ui, info));
+                ui.add_space(10.0);
+            }
+        }
+
+        ui.with_layout(Layout::bottom_up(Align::Center), |ui| {
+            if !self.outputs.is_empty() {
+                jack::outputs(ui, |ui| {
+                    ports(ui, columns, &self.outputs, |i| {
+                        Jack::output(handle.output(i))
+                    });
+                });
+            }
+            if !self.inputs.is_empty() && !self.outputs.is_empty() {
+                ui.add(SignalFlow::join_vertical());
+            }
+            if !self.inputs.is_empty() {
+                jack::inputs(ui, |ui| {
+                    ports(ui, columns, &self.inputs, |i| Jack::input(handle.input(i)));
+                });
+            }
+        });
+    }
+}
+
+/// Whether a parameter is given a whole row, rather than sharing one with other controls.
+fn is_row(info: &ParameterInfo) -> bool {
+    matches!(info.param, ParameterRef::Choice(_)) && !info.options.is_empty()
+}
+
+/// Adds a labelled control suited to a parameter.
+fn control(ui: &mut Ui, info: &ParameterInfo) {
+    let default = info.default;
+    match info.param {
+        ParameterRef::Float(param) => {
+            let range = info.range.clone().unwrap_or(0.0..=1.0);
+            let knob = Knob::new(param).scale(0.75);
+            let knob = if info.logarithmic {
+                knob.logarithmic(range)
+            } else {
+                knob.range(range)
+            };
+            let knob = match info.unit {
+                Some(unit) => knob.hover_text(move |v| format!("{:.3} {}", v, unit)),
+                None => knob,
+            };
+            ui.add(match default {
+                Some(default) => knob.default_value(default),
+                None => knob,
+            });
+        }
+        ParameterRef::Choice(param) if !info.options.is_empty() => {
+            let default = default.unwrap_or(0.0) as u8;
+            ui.add(Selector::new(param, info.options).default_value(default));
+        }
+        ParameterRef::Choice(param) => {
+            let range = info.range.clone().unwrap_or(0.0..=255.0);
+            let range = *range.start() as u8..=*range.end() as u8;
+            let mut value = param.read();
+            if ui
+                .add(DragValue::new(&mut value).clamp_range(range))
+                .changed()
+            {
+                param.write(value);
+            }
+        }
+        ParameterRef::Switch(param) => {
+            let default = default.is_some_and(|d| d != 0.0);
+            ui.add(Toggle::new(param).default_value(default));
+        }
+    }
+    ui.small(&info.label);
+}
+
+/// Adds labelled jacks in rows of `columns`.
+fn ports(ui: &mut Ui, columns: usize, names: &[String], jack: impl Fn(usize) -> Jack) {
+    // Columns grow down from the cursor, which is at the bottom of a bottom-up layout, so each
+    // row is given a space of its own first.
+    let height =
+        ui.text_style_height(&TextStyle::Small) + ui.spacing().item_spacing.y + Jack::size(ui).y;
+    let size = vec2(ui.available_width(), height);
+    // Rows are added from the bottom, so the first row goes last.
+    let rows: Vec<_> = names.chunks(columns).enumerate().collect();
+    for (row, names) in rows.into_iter().rev() {
+        ui.allocate_ui_with_layout(size, Layout::top_down(Align::Center), |ui| {
+            ui.columns(columns, |uis| {
+                for (i, (ui, name)) in uis.iter_mut().zip(names).enumerate() {
+                    ui.vertical_centered(|ui| {
+                        ui.small(name);
+                        ui.add(jack(row * columns + i));
+                    });
+                }
+            });
+        });
+    }
+}
+
+fn capitalize(name: &str) -> String {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .map(|c| c.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
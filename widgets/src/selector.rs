@@ -0,0 +1,95 @@
+use egui::*;
+use module::Parameter;
+
+use crate::input;
+
+/// A row of segments for choosing one of a few options, stored as the option's index.
+pub struct Selector<'a> {
+    param: &'a dyn Parameter<Value = u8>,
+    options: &'a [&'a str],
+    default: u8,
+}
+
+impl<'a> Selector<'a> {
+    pub fn new(param: &'a dyn Parameter<Value = u8>, options: &'a [&'a str]) -> Self {
+        Selector {
+            param,
+            options,
+            default: 0,
+        }
+    }
+
+    /// The option restored by double clicking.
+    pub fn default_value(mut self, default: u8) -> Self {
+        self.default = default;
+        self
+    }
+}
+
+impl<'a> Widget for Selector<'a> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let count = self.options.len().max(1);
+        let size = vec2(ui.available_width(), ui.spacing().interact_size.y);
+        let (rect, mut response) = ui.allocate_exact_size(size, Sense::click());
+        input::focus_on_click(&response);
+        let segment_width = rect.width() / count as f32;
+
+        // Interact:
+        let mut value = self.param.read().min(count as u8 - 1);
+        let steps = input::take_scroll_steps(ui, &response) + input::arrow_steps(ui, &response);
+        let target = if response.double_clicked() {
+            Some(self.default)
+        } else if let (true, Some(pos)) = (response.clicked(), response.interact_pointer_pos()) {
+            Some(((pos.x - rect.left()) / segment_width).floor() as u8)
+        } else if steps != 0 {
+            Some((value as i32 + steps).clamp(0, count as i32 - 1) as u8)
+        } else {
+            None
+        };
+        if let Some(target) = target.map(|t| t.min(count as u8 - 1)) {
+            if target != value {
+                value = target;
+                self.param.write(value);
+                response.mark_changed();
+            }
+        }
+
+        // Draw:
+        if ui.is_rect_visible(rect) {
+            let painter = ui.painter();
+            let widget = ui.style().interact(&response);
+            let font = TextStyle::Small.resolve(ui.style());
+            painter.rect(rect, 4.0, ui.visuals().extreme_bg_color, widget.bg_stroke);
+            for (i, option) in self.options.iter().enumerate() {
+                let segment = Rect::from_min_size(
+                    rect.min + vec2(i as f32 * segment_width, 0.0),
+                    vec2(segment_width, rect.height()),
+                );
+                let selected = i == value as usize;
+                if selected {
+                    painter.rect_filled(segment.shrink(1.0), 3.0, ui.visuals().selection.bg_fill);
+                } else if i > 0 {
+                    painter.line_segment(
+                        [segment.left_top(), segment.left_bottom()],
+                        widget.bg_stroke,
+                    );
+                }
+                let color = if selected {
+                    ui.visuals().strong_text_color()
+                } else {
+                    widget.text_color()
+                };
+                painter.text(
+                    segment.center(),
+                    Align2::CENTER_CENTER,
+                    option,
+                    font.clone(),
+                    color,
+                );
+            }
+            input::paint_focus(ui, &response, rect, 4.0);
+        }
+
+        response
+    }
+}
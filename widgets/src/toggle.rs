@@ -0,0 +1,146 @@
+use egui::*;
+use module::Parameter;
+
+use crate::input;
+
+/// A toggle switch for a boolean parameter.
+pub struct Toggle<'a> {
+    param: &'a dyn Parameter<Value = bool>,
+    default: bool,
+}
+
+impl<'a> Toggle<'a> {
+    pub fn new(param: &'a dyn Parameter<Value = bool>) -> Self {
+        Toggle {
+            param,
+            default: false,
+        }
+    }
+
+    /// The value restored by double clicking.
+    pub fn default_value(mut self, default: bool) -> Self {
+        self.default = default;
+        self
+    }
+}
+
+impl<'a> Widget for Toggle<'a> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let height = 1.2 * ui.spacing().interact_size.y;
+        let size = vec2(0.6 * height, height);
+        let (rect, mut response) = ui.allocate_exact_size(size, Sense::click());
+        input::focus_on_click(&response);
+
+        // Interact:
+        let mut value = self.param.read();
+        let scroll = input::take_scroll_steps(ui, &response) + input::arrow_steps(ui, &response);
+        let target = if response.double_clicked() {
+            Some(self.default)
+        } else if response.clicked() {
+            Some(!value)
+        } else if scroll != 0 {
+            Some(scroll > 0)
+        } else {
+            None
+        };
+        if let Some(target) = target.filter(|&t| t != value) {
+            value = target;
+            self.param.write(value);
+            response.mark_changed();
+        }
+
+        // Draw:
+        if ui.is_rect_visible(rect) {
+            let painter = ui.painter();
+            let widget = ui.style().interact(&response);
+            let rounding = 0.5 * rect.width();
+            painter.rect(
+                rect,
+                rounding,
+                ui.visuals().extreme_bg_color,
+                widget.bg_stroke,
+            );
+            // The lever points up when on.
+            let lever = Rect::from_center_size(
+                if value {
+                    rect.center_top() + vec2(0.0, 0.5 * rect.width())
+                } else {
+                    rect.center_bottom() - vec2(0.0, 0.5 * rect.width())
+                },
+                Vec2::splat(0.8 * rect.width()),
+            );
+            let fill = if value {
+                ui.visuals().selection.bg_fill
+            } else {
+                widget.bg_fill
+            };
+            painter.rect(lever, 0.5 * lever.width(), fill, widget.fg_stroke);
+            input::paint_focus(ui, &response, rect, rounding);
+        }
+
+        response
+    }
+}
+
+/// A momentary push button for a boolean parameter, which is true only while the button is held.
+/// Units can turn this into a gate or trigger voltage.
+pub struct Button<'a> {
+    param: &'a dyn Parameter<Value = bool>,
+    label: Option<String>,
+}
+
+impl<'a> Button<'a> {
+    pub fn new(param: &'a dyn Parameter<Value = bool>) -> Self {
+        Button { param, label: None }
+    }
+
+    /// Text printed on the button's cap.
+    pub fn label(mut self, label: impl ToString) -> Self {
+        self.label = Some(label.to_string());
+        self
+    }
+}
+
+impl<'a> Widget for Button<'a> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let radius = 0.75 * ui.spacing().interact_size.y;
+        let (rect, mut response) =
+            ui.allocate_exact_size(Vec2::splat(2.0 * radius), Sense::click_and_drag());
+        input::focus_on_click(&response);
+
+        // Interact:
+        let pressed = response.is_pointer_button_down_on()
+            || (response.has_focus() && ui.input().key_down(Key::Space));
+        if pressed != self.param.read() {
+            self.param.write(pressed);
+            response.mark_changed();
+        }
+
+        // Draw:
+        if ui.is_rect_visible(rect) {
+            let painter = ui.painter();
+            let widget = ui.style().interact(&response);
+            let origin = rect.center();
+            painter.circle(origin, radius, widget.bg_fill, widget.bg_stroke);
+            let cap = if pressed { 0.6 } else { 0.7 } * radius;
+            let fill = if pressed {
+                ui.visuals().selection.bg_fill
+            } else {
+                ui.visuals().faint_bg_color
+            };
+            painter.circle(origin, cap, fill, widget.fg_stroke);
+            if let Some(label) = self.label {
+                painter.text(
+                    origin,
+                    Align2::CENTER_CENTER,
+                    label,
+                    TextStyle::Small.resolve(ui.style()),
+                    widget.text_color(),
+                );
+            }
+            input::paint_focus(ui, &response, rect, radius);
+        }
+
+        response
+    }
+}
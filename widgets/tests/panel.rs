@@ -0,0 +1,146 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicU8},
+    Arc,
+};
+
+use egui::{Align, CentralPanel, Context, Layout, Pos2, RawInput, Rect, Shape};
+use eurorack::Voltage;
+use module::*;
+use portable_atomic::AtomicF32;
+use widgets::panel::GenericPanel;
+
+#[derive(Default, Parameters)]
+struct TestParams {
+    #[param(
+        label = "Cutoff",
+        min = 20.0,
+        max = 20000.0,
+        default = 1000.0,
+        log,
+        unit = "Hz"
+    )]
+    cutoff: AtomicF32,
+    #[param(options("Sine", "Square"))]
+    shape: AtomicU8,
+    sync: AtomicBool,
+    level: [AtomicF32; 2],
+}
+
+#[derive(Default)]
+struct TestModule {
+    params: Arc<TestParams>,
+}
+
+impl Module for TestModule {
+    fn inputs(&self) -> usize {
+        2
+    }
+
+    fn outputs(&self) -> usize {
+        1
+    }
+
+    fn params(&self) -> Option<&dyn Parameters> {
+        Some(self.params.as_ref())
+    }
+
+    fn input_names(&self) -> &'static [&'static str] {
+        &["v_oct"]
+    }
+
+    fn create_audio_unit(&self) -> Box<dyn AudioUnit> {
+        Box::new(SilentUnit)
+    }
+
+    fn create_panel(&self) -> Box<dyn Panel> {
+        Box::new(GenericPanel::new("Test", self).params(self.params.clone()))
+    }
+}
+
+const PANEL: Rect = Rect {
+    min: Pos2::ZERO,
+    max: Pos2::new(240.0, 600.0),
+};
+
+/// The tests only draw the module, so it never makes a sound.
+struct SilentUnit;
+
+impl AudioUnit for SilentUnit {
+    fn reset(&mut self, _sample_rate: usize) {}
+
+    fn tick(&mut self, _inputs: &[Option<Voltage>], outputs: &mut [Voltage]) {
+        outputs.fill(0.0);
+    }
+}
+
+/// Draws a panel, returning every piece of text on it and where it is.
+fn render(panel: &mut dyn Panel) -> Vec<(String, Pos2)> {
+    let ctx = Context::default();
+    let mut texts = Vec::new();
+    // Laying out columns takes a frame to settle.
+    for _ in 0..2 {
+        let output = ctx.run(RawInput::default(), |ctx| {
+            CentralPanel::default().show(ctx, |ui| {
+                // Panels are given a fixed rect in the rack, as here.
+                let mut ui = ui.child_ui(PANEL, Layout::top_down(Align::Center));
+                panel.update(&ModuleHandle(0), &mut ui);
+            });
+        });
+        texts = output
+            .shapes
+            .iter()
+            .filter_map(|clipped| match &clipped.1 {
+                Shape::Text(text) => Some((text.galley.text().to_owned(), text.pos)),
+                _ => None,
+            })
+            .collect();
+    }
+    texts
+}
+
+#[test]
+fn lays_out_every_parameter_and_port() {
+    let module = TestModule::default();
+    let mut panel = module.create_panel();
+    // Four controls share rows two to each 4 HP, while the choice gets a row to itself.
+    assert_eq!(panel.width(), 8);
+
+    let texts = render(panel.as_mut());
+    let position = |text: &str| match texts.iter().find(|(t, _)| t == text) {
+        Some(&(_, pos)) => pos,
+        None => panic!("{:?} not in {:?}", text, texts),
+    };
+    for text in [
+        "Test", "Cutoff", "Sine", "Square", "Sync", "Level 1", "Level 2",
+    ] {
+        assert!(PANEL.contains(position(text)), "{:?} at {:?}", text, texts);
+    }
+    // The jacks are along the bottom, inputs above outputs.
+    let shape = position("Shape");
+    let (v_oct, in_2, out_1) = (position("V_oct"), position("In 2"), position("Out 1"));
+    assert!(shape.y < v_oct.y && v_oct.y == in_2.y && in_2.y < out_1.y);
+    assert!(
+        out_1.y > PANEL.center().y && out_1.y < PANEL.bottom(),
+        "{:?}",
+        out_1
+    );
+}
+
+#[test]
+fn describes_array_elements_separately() {
+    let params = TestParams::default();
+    let names: Vec<String> = params.info().into_iter().map(|info| info.name).collect();
+    assert_eq!(names, ["cutoff", "shape", "sync", "level[0]", "level[1]"]);
+    // But they're serialized together.
+    assert!(matches!(
+        params.serialize()["level"],
+        SerializedParameter::List(ref list) if list.len() == 2
+    ));
+}
+
+#[test]
+fn can_be_given_a_width() {
+    let module = TestModule::default();
+    let panel = GenericPanel::new("Test", &module).width(20);
+    assert_eq!(Panel::width(&panel), 20);
+}